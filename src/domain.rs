@@ -1,63 +1,292 @@
 #![forbid(unsafe_code)]
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
 
 use anyhow::{anyhow, Result as AnyResult};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::net::IpAddr;
 use std::str::FromStr;
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+/// Lifecycle of an experiment. `Pending`, `Running` and `Stopping` are active;
+/// the rest are terminal and carry a termination reason and end timestamp.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExperimentPhase {
+    #[default]
+    Pending,
+    Running,
+    Stopping,
+    Completed,
+    Stopped,
+    Failed,
+    TimedOut,
+}
+
+impl ExperimentPhase {
+    #[must_use]
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            Self::Completed | Self::Stopped | Self::Failed | Self::TimedOut
+        )
+    }
+
+    #[must_use]
+    pub fn can_transition_to(self, next: Self) -> bool {
+        matches!(
+            (self, next),
+            (
+                Self::Pending,
+                Self::Running | Self::Stopping | Self::Failed | Self::TimedOut
+            ) | (
+                Self::Running,
+                Self::Stopping | Self::Completed | Self::Failed | Self::TimedOut
+            ) | (
+                Self::Stopping,
+                Self::Stopped | Self::Failed | Self::TimedOut
+            )
+        )
+    }
+}
+
+impl std::fmt::Display for ExperimentPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExperimentPhase::Pending => f.write_str("PENDING"),
+            ExperimentPhase::Running => f.write_str("RUNNING"),
+            ExperimentPhase::Stopping => f.write_str("STOPPING"),
+            ExperimentPhase::Completed => f.write_str("COMPLETED"),
+            ExperimentPhase::Stopped => f.write_str("STOPPED"),
+            ExperimentPhase::Failed => f.write_str("FAILED"),
+            ExperimentPhase::TimedOut => f.write_str("TIMED_OUT"),
+        }
+    }
+}
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ExperimentState {
-    pub running: bool,
+    pub phase: ExperimentPhase,
     pub kind: String,
     pub total_duration_seconds: u32,
     pub remaining_seconds: u32,
     pub started_ts_seconds: i64,
     pub ends_ts_seconds: i64,
+    pub ended_ts_seconds: Option<i64>,
+    pub termination_reason: Option<String>,
+    pub error: Option<String>,
+    /// Memory hog footprint: the agent's resident set plus any ballast held
+    /// in tmpfs or the page cache.
+    pub resident_bytes: Option<u64>,
+    pub listen_addr: Option<String>,
+    /// Seed a randomised fault experiment drew from, so a run can be replayed.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Processes a process experiment is acting on. Pause and throttle drop
+    /// targets that exit at each stop; kill re-matches every round.
+    #[serde(default)]
+    pub target_pids: Vec<u32>,
+    /// What the experiment did, oldest first, capped at [`MAX_EVENTS`].
+    #[serde(default)]
+    pub events: Vec<ExperimentEvent>,
+}
+
+/// Most events an experiment keeps; older ones are dropped first.
+pub const MAX_EVENTS: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExperimentEvent {
+    pub ts_seconds: i64,
+    pub message: String,
+}
+
+/// Cancellation handle shared between the control plane and a running load.
+///
+/// `cancel` is observed by the load loops; `running` fires once the load is
+/// set up and its duration clock has started; `released` fires once the load
+/// has returned and the experiment has been finished.
+#[derive(Clone, Default)]
+pub struct ExperimentHandle {
+    pub cancel: CancellationToken,
+    pub running: CancellationToken,
+    pub released: CancellationToken,
 }
 
 #[derive(Clone, Default)]
 pub struct LoadController {
     pub state: Arc<Mutex<HashMap<String, ExperimentState>>>,
+    pub handles: Arc<Mutex<HashMap<String, ExperimentHandle>>>,
 }
 
 impl LoadController {
+    #[must_use]
     pub fn get_running_id(&self) -> Option<String> {
         self.state
             .lock()
             .iter()
-            .find(|(_, st)| st.running)
+            .find(|(_, st)| !st.phase.is_terminal())
             .map(|(k, _)| k.clone())
     }
 
-    pub fn start(&self, id: &str, exp: &Experiment) {
+    /// Registers `id` as `Pending`. Refused while another experiment is still
+    /// active, so at most one is ever in flight.
+    pub fn start(&self, id: &str, exp: &Experiment) -> AnyResult<ExperimentHandle> {
         let mut map = self.state.lock();
+        if let Some((active, _)) = map.iter().find(|(_, st)| !st.phase.is_terminal()) {
+            return Err(anyhow!("another experiment running: {active}"));
+        }
         map.insert(
             id.to_string(),
             ExperimentState {
-                running: true,
-                kind: match exp.kind {
-                    ExperimentKind::CPU => "CPU".into(),
-                    ExperimentKind::MEMORY => "MEMORY".into(),
-                },
+                phase: ExperimentPhase::Pending,
+                kind: exp.kind_label(),
                 total_duration_seconds: exp.duration_seconds,
                 remaining_seconds: exp.duration_seconds,
                 started_ts_seconds: exp.started_ts_seconds,
                 ends_ts_seconds: exp.ends_ts_seconds,
+                ended_ts_seconds: None,
+                termination_reason: None,
+                error: None,
+                resident_bytes: None,
+                listen_addr: None,
+                seed: None,
+                target_pids: Vec::new(),
+                events: Vec::new(),
             },
         );
+        let handle = ExperimentHandle::default();
+        self.handles.lock().insert(id.to_string(), handle.clone());
+        Ok(handle)
     }
 
-    pub fn finish(&self, id: &str) {
+    #[must_use]
+    pub fn phase(&self, id: &str) -> Option<ExperimentPhase> {
+        self.state.lock().get(id).map(|st| st.phase)
+    }
+
+    #[must_use]
+    pub fn handle(&self, id: &str) -> Option<ExperimentHandle> {
+        self.handles.lock().get(id).cloned()
+    }
+
+    /// Moves `id` to a non-terminal phase; terminal phases go through `finish`.
+    pub fn transition(&self, id: &str, next: ExperimentPhase) -> AnyResult<()> {
+        if next.is_terminal() {
+            return Err(anyhow!("{next} is terminal, use finish"));
+        }
         let mut map = self.state.lock();
-        if let Some(st) = map.get_mut(id) {
-            st.running = false;
-            st.remaining_seconds = 0;
+        let st = map
+            .get_mut(id)
+            .ok_or_else(|| anyhow!("experiment not found: {id}"))?;
+        if !st.phase.can_transition_to(next) {
+            return Err(anyhow!("invalid transition {} -> {next}", st.phase));
+        }
+        st.phase = next;
+        Ok(())
+    }
+
+    /// Moves `id` from `Pending` to `Running` once its load is set up, which
+    /// starts the deadline the runner holds it to. The first call restamps
+    /// the start and end times, so status reflects the clock actually held.
+    pub fn mark_running(&self, id: &str) {
+        let handle = self.handle(id);
+        if handle.as_ref().is_some_and(|h| !h.running.is_cancelled()) {
+            let now = chrono::Utc::now().timestamp();
+            if let Some(st) = self.state.lock().get_mut(id) {
+                st.started_ts_seconds = now;
+                st.ends_ts_seconds = now + i64::from(st.total_duration_seconds);
+            }
+        }
+        if let Err(e) = self.transition(id, ExperimentPhase::Running) {
+            debug!(experiment=%id, error=%format!("{e:#}"), "not entering running");
+        }
+        if let Some(handle) = handle {
+            handle.running.cancel();
+        }
+    }
+
+    pub fn set_error(&self, id: &str, error: String) {
+        if let Some(st) = self.state.lock().get_mut(id) {
+            st.error = Some(error);
+        }
+    }
+
+    pub fn set_resident_bytes(&self, id: &str, bytes: u64) {
+        if let Some(st) = self.state.lock().get_mut(id) {
+            st.resident_bytes = Some(bytes);
+        }
+    }
+
+    /// Records the processes a process experiment matched.
+    pub fn set_target_pids(&self, id: &str, pids: Vec<u32>) {
+        if let Some(st) = self.state.lock().get_mut(id) {
+            st.target_pids = pids;
         }
     }
+
+    /// Appends to the event log of `id`, dropping the oldest past [`MAX_EVENTS`].
+    pub fn record_event(&self, id: &str, ts_seconds: i64, message: String) {
+        if let Some(st) = self.state.lock().get_mut(id) {
+            if st.events.len() >= MAX_EVENTS {
+                st.events.remove(0);
+            }
+            st.events.push(ExperimentEvent {
+                ts_seconds,
+                message,
+            });
+        }
+    }
+
+    /// Records the address a proxy experiment actually bound.
+    pub fn set_listen_addr(&self, id: &str, addr: String) {
+        if let Some(st) = self.state.lock().get_mut(id) {
+            st.listen_addr = Some(addr);
+        }
+    }
+
+    /// Records the seed a randomised fault experiment actually used.
+    pub fn set_seed(&self, id: &str, seed: u64) {
+        if let Some(st) = self.state.lock().get_mut(id) {
+            st.seed = Some(seed);
+        }
+    }
+
+    /// Moves `id` to a terminal phase and releases its handle.
+    pub fn finish(
+        &self,
+        id: &str,
+        phase: ExperimentPhase,
+        reason: &str,
+        now_ts: i64,
+    ) -> AnyResult<()> {
+        if !phase.is_terminal() {
+            return Err(anyhow!("{phase} is not terminal"));
+        }
+        let res = {
+            let mut map = self.state.lock();
+            match map.get_mut(id) {
+                Some(st) if st.phase.can_transition_to(phase) => {
+                    st.phase = phase;
+                    st.remaining_seconds = 0;
+                    st.ended_ts_seconds = Some(now_ts);
+                    st.termination_reason = Some(reason.to_string());
+                    Ok(())
+                }
+                Some(st) => Err(anyhow!("invalid transition {} -> {phase}", st.phase)),
+                None => Err(anyhow!("experiment not found: {id}")),
+            }
+        };
+        if let Some(handle) = self.handles.lock().remove(id) {
+            handle.released.cancel();
+        }
+        res
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -75,9 +304,24 @@ pub struct AppState {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
 pub enum ExperimentKind {
     CPU,
     MEMORY,
+    DISK_IO,
+    DISK_FILL,
+    FD_EXHAUSTION,
+    PIDS_PRESSURE,
+    NETWORK_PROXY,
+    HTTP_FAULT,
+    UDP_PROXY,
+    DNS_FAULT,
+    PROCESS_PAUSE,
+    PROCESS_KILL,
+    PROCESS_THROTTLE,
+    PORT_EXHAUSTION,
+    CACHE_THRASH,
+    PAGE_CACHE_PRESSURE,
 }
 
 impl std::fmt::Display for ExperimentKind {
@@ -85,6 +329,20 @@ impl std::fmt::Display for ExperimentKind {
         match self {
             ExperimentKind::CPU => f.write_str("CPU"),
             ExperimentKind::MEMORY => f.write_str("MEMORY"),
+            ExperimentKind::DISK_IO => f.write_str("DISK_IO"),
+            ExperimentKind::DISK_FILL => f.write_str("DISK_FILL"),
+            ExperimentKind::FD_EXHAUSTION => f.write_str("FD_EXHAUSTION"),
+            ExperimentKind::PIDS_PRESSURE => f.write_str("PIDS_PRESSURE"),
+            ExperimentKind::NETWORK_PROXY => f.write_str("NETWORK_PROXY"),
+            ExperimentKind::HTTP_FAULT => f.write_str("HTTP_FAULT"),
+            ExperimentKind::UDP_PROXY => f.write_str("UDP_PROXY"),
+            ExperimentKind::DNS_FAULT => f.write_str("DNS_FAULT"),
+            ExperimentKind::PROCESS_PAUSE => f.write_str("PROCESS_PAUSE"),
+            ExperimentKind::PROCESS_KILL => f.write_str("PROCESS_KILL"),
+            ExperimentKind::PROCESS_THROTTLE => f.write_str("PROCESS_THROTTLE"),
+            ExperimentKind::PORT_EXHAUSTION => f.write_str("PORT_EXHAUSTION"),
+            ExperimentKind::CACHE_THRASH => f.write_str("CACHE_THRASH"),
+            ExperimentKind::PAGE_CACHE_PRESSURE => f.write_str("PAGE_CACHE_PRESSURE"),
         }
     }
 }
@@ -95,6 +353,20 @@ impl FromStr for ExperimentKind {
         match s {
             "CPU" => Ok(Self::CPU),
             "MEMORY" => Ok(Self::MEMORY),
+            "DISK_IO" => Ok(Self::DISK_IO),
+            "DISK_FILL" => Ok(Self::DISK_FILL),
+            "FD_EXHAUSTION" => Ok(Self::FD_EXHAUSTION),
+            "PIDS_PRESSURE" => Ok(Self::PIDS_PRESSURE),
+            "NETWORK_PROXY" => Ok(Self::NETWORK_PROXY),
+            "HTTP_FAULT" => Ok(Self::HTTP_FAULT),
+            "UDP_PROXY" => Ok(Self::UDP_PROXY),
+            "DNS_FAULT" => Ok(Self::DNS_FAULT),
+            "PROCESS_PAUSE" => Ok(Self::PROCESS_PAUSE),
+            "PROCESS_KILL" => Ok(Self::PROCESS_KILL),
+            "PROCESS_THROTTLE" => Ok(Self::PROCESS_THROTTLE),
+            "PORT_EXHAUSTION" => Ok(Self::PORT_EXHAUSTION),
+            "CACHE_THRASH" => Ok(Self::CACHE_THRASH),
+            "PAGE_CACHE_PRESSURE" => Ok(Self::PAGE_CACHE_PRESSURE),
             other => Err(anyhow::anyhow!("unsupported kind: {other}")),
         }
     }
@@ -111,6 +383,7 @@ pub struct Experiment {
 }
 
 impl Experiment {
+    #[must_use]
     pub fn new(
         id: String,
         kind: ExperimentKind,
@@ -118,7 +391,7 @@ impl Experiment {
         duration_seconds: u32,
         started_ts_seconds: i64,
     ) -> Self {
-        let ends_ts_seconds = started_ts_seconds + duration_seconds as i64;
+        let ends_ts_seconds = started_ts_seconds + i64::from(duration_seconds);
         Self {
             id,
             kind,
@@ -129,36 +402,367 @@ impl Experiment {
         }
     }
 
+    #[must_use]
     pub fn remaining_seconds(&self, now_ts: i64) -> u32 {
-        if now_ts >= self.ends_ts_seconds {
-            0
-        } else {
-            (self.ends_ts_seconds - now_ts) as u32
-        }
+        u32::try_from((self.ends_ts_seconds - now_ts).max(0)).unwrap_or(u32::MAX)
     }
 
+    #[must_use]
     pub fn kind_label(&self) -> String {
         self.kind.to_string()
     }
 
+    #[must_use]
+    #[allow(clippy::too_many_lines)]
     pub fn params_label(&self) -> String {
         match &self.params {
-            ExperimentParams::Cpu { duty_percent } => format!("duty_percent={}", duty_percent),
-            ExperimentParams::Memory { memory_mb } => format!("memory_mb={}", memory_mb),
+            ExperimentParams::Cpu {
+                duty_percent,
+                cores,
+                cpu_list,
+                closed_loop,
+                profile,
+                workload,
+            } => {
+                let mut label = format!("duty_percent={duty_percent},cores={cores}");
+                if let Some(list) = cpu_list {
+                    let list = list
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(";");
+                    let _ = write!(label, ",cpu_list={list}");
+                }
+                if *closed_loop {
+                    label.push_str(",closed_loop");
+                }
+                if *profile != CpuProfile::Constant {
+                    let _ = write!(label, ",profile={profile}");
+                }
+                if *workload != CpuWorkload::Spin {
+                    let _ = write!(label, ",workload={workload}");
+                }
+                label
+            }
+            ExperimentParams::Memory {
+                memory_mb,
+                touch,
+                retouch_seconds,
+                growth,
+                backing,
+            } => {
+                let mut label = format!(
+                    "memory_mb={memory_mb},touch={touch},retouch_seconds={retouch_seconds},growth={growth}"
+                );
+                if *backing != MemoryBacking::Anonymous {
+                    let _ = write!(label, ",backing={backing}");
+                }
+                label
+            }
+            ExperimentParams::DiskIo {
+                dir,
+                read_percent,
+                block_size_kb,
+                file_size_mb,
+                rate,
+                fsync_every,
+            } => format!(
+                "dir={dir},read_percent={read_percent},block_size_kb={block_size_kb},file_size_mb={file_size_mb},rate={rate},fsync_every={fsync_every}"
+            ),
+            ExperimentParams::DiskFill { path, amount } => format!("path={path},amount={amount}"),
+            ExperimentParams::FdExhaustion { target, resource } => {
+                format!("target={target},resource={resource}")
+            }
+            ExperimentParams::PidsPressure { count, mode } => format!("count={count},mode={mode}"),
+            ExperimentParams::NetworkProxy {
+                listen,
+                upstream,
+                faults,
+            } => format!("listen={listen},upstream={upstream},{faults}"),
+            ExperimentParams::HttpFault {
+                listen,
+                upstream,
+                rules,
+            } => {
+                let rules = rules
+                    .iter()
+                    .map(|r| format!("{}:{}", r.name, r.action))
+                    .collect::<Vec<_>>()
+                    .join(";");
+                format!("listen={listen},upstream={upstream},rules={rules}")
+            }
+            ExperimentParams::UdpProxy {
+                listen,
+                upstream,
+                faults,
+            } => format!("listen={listen},upstream={upstream},{faults}"),
+            ExperimentParams::DnsFault {
+                listen,
+                upstream,
+                rules,
+            } => {
+                let rules = rules
+                    .iter()
+                    .map(|r| format!("{}:{}", r.name, r.action))
+                    .collect::<Vec<_>>()
+                    .join(";");
+                format!("listen={listen},upstream={upstream},rules={rules}")
+            }
+            ExperimentParams::ProcessPause {
+                target,
+                max_targets,
+                duty,
+            } => {
+                let mut label = match duty {
+                    Some(duty) => format!("target={target},duty={duty}"),
+                    None => format!("target={target},duty=FREEZE"),
+                };
+                push_max_targets(&mut label, *max_targets);
+                label
+            }
+            ExperimentParams::ProcessKill {
+                target,
+                max_targets,
+                signal,
+                interval_seconds,
+                dry_run,
+            } => {
+                let mut label = format!("target={target},signal={signal}");
+                match interval_seconds {
+                    Some(every) => {
+                        let _ = write!(label, ",interval={every}s");
+                    }
+                    None => label.push_str(",interval=ONCE"),
+                }
+                if *dry_run {
+                    label.push_str(",dry_run");
+                }
+                push_max_targets(&mut label, *max_targets);
+                label
+            }
+            ExperimentParams::ProcessThrottle {
+                target,
+                max_targets,
+                cpu_percent,
+            } => {
+                let mut label = format!("target={target},cpu_percent={cpu_percent}");
+                push_max_targets(&mut label, *max_targets);
+                label
+            }
+            ExperimentParams::PortExhaustion { target, ports } => {
+                format!("target={target},ports={ports}")
+            }
+            ExperimentParams::CacheThrash {
+                level,
+                size_percent,
+                access,
+                cores,
+            } => format!("level={level},size_percent={size_percent},access={access},cores={cores}"),
+            ExperimentParams::PageCachePressure {
+                dir,
+                file_size_mb,
+                files,
+                rate,
+            } => format!("dir={dir},file_size_mb={file_size_mb},files={files},rate={rate}"),
         }
     }
 
+    #[allow(clippy::too_many_lines)]
     pub fn new_from_start_request(req: &StartRequest, now_ts: i64) -> AnyResult<Self> {
         let kind = ExperimentKind::from_str(&req.kind)?;
         let params = match (&kind, &req.params) {
-            (ExperimentKind::CPU, StartParams::Cpu { duty_percent }) => ExperimentParams::Cpu {
+            (
+                ExperimentKind::CPU,
+                StartParams::Cpu {
+                    duty_percent,
+                    cores,
+                    cpu_list,
+                    closed_loop,
+                    profile,
+                    workload,
+                },
+            ) => ExperimentParams::Cpu {
                 duty_percent: *duty_percent,
+                cores: *cores,
+                cpu_list: cpu_list.clone(),
+                closed_loop: *closed_loop,
+                profile: profile.clone(),
+                workload: *workload,
             },
-            (ExperimentKind::MEMORY, StartParams::Memory { memory_mb }) => {
-                ExperimentParams::Memory {
-                    memory_mb: *memory_mb,
+            (
+                ExperimentKind::MEMORY,
+                StartParams::Memory {
+                    memory_mb,
+                    touch,
+                    retouch_seconds,
+                    growth,
+                    backing,
+                },
+            ) => ExperimentParams::Memory {
+                memory_mb: *memory_mb,
+                touch: *touch,
+                retouch_seconds: *retouch_seconds,
+                growth: *growth,
+                backing: backing.clone(),
+            },
+            (
+                ExperimentKind::DISK_IO,
+                StartParams::DiskIo {
+                    dir,
+                    read_percent,
+                    block_size_kb,
+                    file_size_mb,
+                    rate,
+                    fsync_every,
+                },
+            ) => ExperimentParams::DiskIo {
+                dir: dir.clone(),
+                read_percent: *read_percent,
+                block_size_kb: *block_size_kb,
+                file_size_mb: *file_size_mb,
+                rate: *rate,
+                fsync_every: *fsync_every,
+            },
+            (ExperimentKind::DISK_FILL, StartParams::DiskFill { path, amount }) => {
+                ExperimentParams::DiskFill {
+                    path: path.clone(),
+                    amount: *amount,
                 }
             }
+            (ExperimentKind::FD_EXHAUSTION, StartParams::FdExhaustion { target, resource }) => {
+                ExperimentParams::FdExhaustion {
+                    target: *target,
+                    resource: *resource,
+                }
+            }
+            (ExperimentKind::PIDS_PRESSURE, StartParams::PidsPressure { count, mode }) => {
+                ExperimentParams::PidsPressure {
+                    count: *count,
+                    mode: *mode,
+                }
+            }
+            (
+                ExperimentKind::NETWORK_PROXY,
+                StartParams::NetworkProxy {
+                    listen,
+                    upstream,
+                    faults,
+                },
+            ) => ExperimentParams::NetworkProxy {
+                listen: listen.clone(),
+                upstream: upstream.clone(),
+                faults: *faults,
+            },
+            (
+                ExperimentKind::HTTP_FAULT,
+                StartParams::HttpFault {
+                    listen,
+                    upstream,
+                    rules,
+                },
+            ) => ExperimentParams::HttpFault {
+                listen: listen.clone(),
+                upstream: upstream.clone(),
+                rules: rules.clone(),
+            },
+            (
+                ExperimentKind::UDP_PROXY,
+                StartParams::UdpProxy {
+                    listen,
+                    upstream,
+                    faults,
+                },
+            ) => ExperimentParams::UdpProxy {
+                listen: listen.clone(),
+                upstream: upstream.clone(),
+                faults: *faults,
+            },
+            (
+                ExperimentKind::DNS_FAULT,
+                StartParams::DnsFault {
+                    listen,
+                    upstream,
+                    rules,
+                },
+            ) => ExperimentParams::DnsFault {
+                listen: listen.clone(),
+                upstream: upstream.clone(),
+                rules: rules.clone(),
+            },
+            (
+                ExperimentKind::PROCESS_PAUSE,
+                StartParams::ProcessPause {
+                    target,
+                    duty,
+                    max_targets,
+                },
+            ) => ExperimentParams::ProcessPause {
+                target: target.clone(),
+                max_targets: *max_targets,
+                duty: *duty,
+            },
+            (
+                ExperimentKind::PROCESS_KILL,
+                StartParams::ProcessKill {
+                    target,
+                    max_targets,
+                    signal,
+                    interval_seconds,
+                    dry_run,
+                },
+            ) => ExperimentParams::ProcessKill {
+                target: target.clone(),
+                max_targets: *max_targets,
+                signal: *signal,
+                interval_seconds: *interval_seconds,
+                dry_run: *dry_run,
+            },
+            (
+                ExperimentKind::PROCESS_THROTTLE,
+                StartParams::ProcessThrottle {
+                    target,
+                    max_targets,
+                    cpu_percent,
+                },
+            ) => ExperimentParams::ProcessThrottle {
+                target: target.clone(),
+                max_targets: *max_targets,
+                cpu_percent: *cpu_percent,
+            },
+            (ExperimentKind::PORT_EXHAUSTION, StartParams::PortExhaustion { target, ports }) => {
+                ExperimentParams::PortExhaustion {
+                    target: target.clone(),
+                    ports: *ports,
+                }
+            }
+            (
+                ExperimentKind::CACHE_THRASH,
+                StartParams::CacheThrash {
+                    level,
+                    size_percent,
+                    access,
+                    cores,
+                },
+            ) => ExperimentParams::CacheThrash {
+                level: *level,
+                size_percent: *size_percent,
+                access: *access,
+                cores: *cores,
+            },
+            (
+                ExperimentKind::PAGE_CACHE_PRESSURE,
+                StartParams::PageCachePressure {
+                    dir,
+                    file_size_mb,
+                    files,
+                    rate,
+                },
+            ) => ExperimentParams::PageCachePressure {
+                dir: dir.clone(),
+                file_size_mb: *file_size_mb,
+                files: *files,
+                rate: *rate,
+            },
             _ => return Err(anyhow!("kind and params mismatch")),
         };
         Ok(Self::new(
@@ -171,15 +775,936 @@ impl Experiment {
     }
 }
 
+/// Number of CPU hog workers: a fixed count or `"all"` available cores.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "CpuCoresRepr", into = "CpuCoresRepr")]
+pub enum CpuCores {
+    Count(u32),
+    All,
+}
+
+impl Default for CpuCores {
+    fn default() -> Self {
+        Self::Count(1)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum CpuCoresRepr {
+    Count(u32),
+    Keyword(String),
+}
+
+impl TryFrom<CpuCoresRepr> for CpuCores {
+    type Error = anyhow::Error;
+    fn try_from(repr: CpuCoresRepr) -> Result<Self, anyhow::Error> {
+        match repr {
+            CpuCoresRepr::Count(n) => Ok(Self::Count(n)),
+            CpuCoresRepr::Keyword(s) if s == "all" => Ok(Self::All),
+            CpuCoresRepr::Keyword(other) => Err(anyhow!("unsupported cores: {other}")),
+        }
+    }
+}
+
+impl From<CpuCores> for CpuCoresRepr {
+    fn from(cores: CpuCores) -> Self {
+        match cores {
+            CpuCores::Count(n) => Self::Count(n),
+            CpuCores::All => Self::Keyword("all".into()),
+        }
+    }
+}
+
+impl std::fmt::Display for CpuCores {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuCores::Count(n) => write!(f, "{n}"),
+            CpuCores::All => f.write_str("all"),
+        }
+    }
+}
+
+/// Shape of the CPU duty over time; `duty_percent` is the base or peak level.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CpuProfile {
+    #[default]
+    Constant,
+    /// Linear from `from_percent` to `duty_percent`, then held.
+    Ramp {
+        from_percent: u32,
+        ramp_seconds: u32,
+    },
+    /// Each entry held for `step_seconds`; the last one is held to the end.
+    Steps {
+        percents: Vec<u32>,
+        step_seconds: u32,
+    },
+    /// `duty_percent` plus a sine of `amplitude_percent`.
+    Sine {
+        period_seconds: u32,
+        amplitude_percent: u32,
+    },
+    /// Flaps between `duty_percent` and `low_percent` every half period.
+    Square {
+        period_seconds: u32,
+        low_percent: u32,
+    },
+}
+
+impl std::fmt::Display for CpuProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuProfile::Constant => f.write_str("CONSTANT"),
+            CpuProfile::Ramp {
+                from_percent,
+                ramp_seconds,
+            } => write!(f, "RAMP:{from_percent}%/{ramp_seconds}s"),
+            CpuProfile::Steps {
+                percents,
+                step_seconds,
+            } => {
+                let steps = percents
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(";");
+                write!(f, "STEPS:{steps}/{step_seconds}s")
+            }
+            CpuProfile::Sine {
+                period_seconds,
+                amplitude_percent,
+            } => write!(f, "SINE:{amplitude_percent}%/{period_seconds}s"),
+            CpuProfile::Square {
+                period_seconds,
+                low_percent,
+            } => write!(f, "SQUARE:{low_percent}%/{period_seconds}s"),
+        }
+    }
+}
+
+/// Code the CPU hog runs during the busy part of each duty window. The kinds
+/// differ in how their time splits between user and system mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CpuWorkload {
+    /// `spin_loop` hints only.
+    #[default]
+    Spin,
+    /// Dependent multiply/shift chains in the integer ALUs.
+    Integer,
+    /// Dependent fused multiply-add and square root chains.
+    Float,
+    /// Hashes a 64 KiB buffer and writes the digest back, like a checksum or
+    /// compressor would.
+    Hash,
+    /// `getpid` in a loop, so nearly all time is spent entering the kernel.
+    Syscall,
+    /// Ping-pong with a partner thread on the same CPU, blocking on every hop.
+    ContextSwitch,
+}
+
+impl std::fmt::Display for CpuWorkload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuWorkload::Spin => f.write_str("SPIN"),
+            CpuWorkload::Integer => f.write_str("INTEGER"),
+            CpuWorkload::Float => f.write_str("FLOAT"),
+            CpuWorkload::Hash => f.write_str("HASH"),
+            CpuWorkload::Syscall => f.write_str("SYSCALL"),
+            CpuWorkload::ContextSwitch => f.write_str("CONTEXT_SWITCH"),
+        }
+    }
+}
+
+/// Order in which the memory hog writes to its pages when faulting them in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TouchPattern {
+    #[default]
+    Sequential,
+    Reverse,
+    Scattered,
+}
+
+impl std::fmt::Display for TouchPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TouchPattern::Sequential => f.write_str("SEQUENTIAL"),
+            TouchPattern::Reverse => f.write_str("REVERSE"),
+            TouchPattern::Scattered => f.write_str("SCATTERED"),
+        }
+    }
+}
+
+/// How the memory hog reaches `memory_mb`, which is also its ceiling.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MemoryGrowth {
+    #[default]
+    Instant,
+    Ramp {
+        ramp_seconds: u32,
+    },
+    Leak {
+        rate_mb_per_second: u32,
+    },
+    Step {
+        step_mb: u32,
+        step_seconds: u32,
+    },
+}
+
+impl std::fmt::Display for MemoryGrowth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryGrowth::Instant => f.write_str("INSTANT"),
+            MemoryGrowth::Ramp { ramp_seconds } => write!(f, "RAMP:{ramp_seconds}s"),
+            MemoryGrowth::Leak { rate_mb_per_second } => {
+                write!(f, "LEAK:{rate_mb_per_second}MB/s")
+            }
+            MemoryGrowth::Step {
+                step_mb,
+                step_seconds,
+            } => write!(f, "STEP:{step_mb}MB/{step_seconds}s"),
+        }
+    }
+}
+
+/// Where the memory hog keeps its pages; each kind is charged and reclaimed
+/// differently by the kernel and the memory cgroup.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MemoryBacking {
+    /// Private heap pages, swappable, counted as anonymous memory.
+    #[default]
+    Anonymous,
+    /// A tmpfs file under `/dev/shm`, counted as shmem; never dropped, only
+    /// swapped.
+    Shm,
+    /// A scratch file in `dir`, held in the page cache and counted as file
+    /// pages the kernel may write back and evict. `dir` must lie within the
+    /// disk-fill allowlist.
+    File { dir: String },
+    /// Heap pages locked in RAM. The lock is taken with `mlockall`, so it
+    /// pins the whole agent, not just the ballast, until the experiment ends.
+    /// Rejected unless `RLIMIT_MEMLOCK` covers the ballast plus the agent's
+    /// resident set, even when the agent holds `CAP_IPC_LOCK`.
+    Mlocked,
+}
+
+impl std::fmt::Display for MemoryBacking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryBacking::Anonymous => f.write_str("ANONYMOUS"),
+            MemoryBacking::Shm => f.write_str("SHM"),
+            MemoryBacking::File { dir } => write!(f, "FILE:{dir}"),
+            MemoryBacking::Mlocked => f.write_str("MLOCKED"),
+        }
+    }
+}
+
+/// Pace of the disk I/O hog; unlimited issues operations back to back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "limit", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum IoRate {
+    #[default]
+    Unlimited,
+    Throughput {
+        mb_per_second: u32,
+    },
+    Iops {
+        ops_per_second: u32,
+    },
+}
+
+impl std::fmt::Display for IoRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoRate::Unlimited => f.write_str("UNLIMITED"),
+            IoRate::Throughput { mb_per_second } => write!(f, "THROUGHPUT:{mb_per_second}MB/s"),
+            IoRate::Iops { ops_per_second } => write!(f, "IOPS:{ops_per_second}"),
+        }
+    }
+}
+
+/// How much ballast the disk-fill hog writes: a fixed size or a share of the
+/// free space measured when the experiment starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FillAmount {
+    Size { size_mb: u64 },
+    FreePercent { percent: u32 },
+}
+
+impl std::fmt::Display for FillAmount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FillAmount::Size { size_mb } => write!(f, "SIZE:{size_mb}MB"),
+            FillAmount::FreePercent { percent } => write!(f, "FREE_PERCENT:{percent}%"),
+        }
+    }
+}
+
+/// Open descriptors the whole process should reach: an absolute count or a
+/// share of its soft `RLIMIT_NOFILE`. Either stops short of the limit by the
+/// descriptors the agent keeps for itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FdTarget {
+    Count { count: u64 },
+    LimitPercent { percent: u32 },
+}
+
+impl std::fmt::Display for FdTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FdTarget::Count { count } => write!(f, "COUNT:{count}"),
+            FdTarget::LimitPercent { percent } => write!(f, "LIMIT_PERCENT:{percent}%"),
+        }
+    }
+}
+
+/// Cache a `CACHE_THRASH` working set is sized against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CacheLevel {
+    L1,
+    L2,
+    #[default]
+    L3,
+}
+
+impl CacheLevel {
+    /// The `level` sysfs reports for this cache.
+    #[must_use]
+    pub fn number(self) -> u8 {
+        match self {
+            CacheLevel::L1 => 1,
+            CacheLevel::L2 => 2,
+            CacheLevel::L3 => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for CacheLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "L{}", self.number())
+    }
+}
+
+/// Order the cache hog visits the lines of its working set in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CacheAccess {
+    /// Line after line, the easiest case for hardware prefetchers.
+    #[default]
+    Sequential,
+    /// One line per page, then the next line of every page.
+    Strided,
+    /// A shuffled order fixed at start, defeating prefetching.
+    Random,
+}
+
+impl std::fmt::Display for CacheAccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheAccess::Sequential => f.write_str("SEQUENTIAL"),
+            CacheAccess::Strided => f.write_str("STRIDED"),
+            CacheAccess::Random => f.write_str("RANDOM"),
+        }
+    }
+}
+
+/// Ephemeral ports the port hog should hold: an absolute count or a share of
+/// `net.ipv4.ip_local_port_range`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PortTarget {
+    Count { count: u32 },
+    RangePercent { percent: u32 },
+}
+
+impl std::fmt::Display for PortTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PortTarget::Count { count } => write!(f, "COUNT:{count}"),
+            PortTarget::RangePercent { percent } => write!(f, "RANGE_PERCENT:{percent}%"),
+        }
+    }
+}
+
+/// What each descriptor held by the FD hog refers to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FdResource {
+    #[default]
+    File,
+    Socket,
+}
+
+impl std::fmt::Display for FdResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FdResource::File => f.write_str("FILE"),
+            FdResource::Socket => f.write_str("SOCKET"),
+        }
+    }
+}
+
+/// Kind of task the pids hog spawns to consume pid slots.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PidsMode {
+    #[default]
+    Threads,
+    Processes,
+}
+
+impl std::fmt::Display for PidsMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PidsMode::Threads => f.write_str("THREADS"),
+            PidsMode::Processes => f.write_str("PROCESSES"),
+        }
+    }
+}
+
+/// Faults the TCP proxy injects; every field defaults to off.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TcpFaults {
+    /// Delay added to each direction of every connection.
+    pub latency_ms: u32,
+    /// Uniform spread around `latency_ms`, never reordering bytes.
+    pub jitter_ms: u32,
+    /// Per-direction throughput cap; 0 leaves it unlimited.
+    pub bandwidth_kb_per_second: u32,
+    /// Share of connections reset as soon as they are accepted.
+    pub reset_percent: u32,
+    /// Share of connections accepted but never forwarded or answered.
+    pub blackhole_percent: u32,
+}
+
+impl std::fmt::Display for TcpFaults {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "latency_ms={},jitter_ms={},bandwidth_kb_per_second={},reset_percent={},blackhole_percent={}",
+            self.latency_ms,
+            self.jitter_ms,
+            self.bandwidth_kb_per_second,
+            self.reset_percent,
+            self.blackhole_percent
+        )
+    }
+}
+
+/// Faults the UDP proxy applies to each datagram; every field defaults to off.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UdpFaults {
+    /// Share of datagrams dropped.
+    pub loss_percent: u32,
+    /// Share of datagrams delivered twice.
+    pub duplicate_percent: u32,
+    /// Share of datagrams held back so later ones overtake them.
+    pub reorder_percent: u32,
+    /// Delay added to every datagram in both directions.
+    pub delay_ms: u32,
+    /// Uniform spread around `delay_ms`; unlike TCP this may reorder datagrams.
+    pub jitter_ms: u32,
+    /// Seed for the fault decisions, so a run can be replayed; random when unset.
+    pub seed: Option<u64>,
+}
+
+impl std::fmt::Display for UdpFaults {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "loss_percent={},duplicate_percent={},reorder_percent={},delay_ms={},jitter_ms={}",
+            self.loss_percent,
+            self.duplicate_percent,
+            self.reorder_percent,
+            self.delay_ms,
+            self.jitter_ms
+        )?;
+        match self.seed {
+            Some(seed) => write!(f, ",seed={seed}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Request header an HTTP fault rule requires; without `value` any value matches.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderMatch {
+    pub name: String,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// What the HTTP fault proxy does to a request picked by a rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HttpFaultAction {
+    /// Answers with `status` without contacting the upstream.
+    Status { status: u16 },
+    /// Holds the request for `delay_ms` before forwarding it.
+    Delay { delay_ms: u32 },
+    /// Forwards, then cuts the response body off after `keep_bytes`.
+    Truncate { keep_bytes: u32 },
+    /// Resets the connection before any part of a response is sent.
+    Abort,
+}
+
+impl std::fmt::Display for HttpFaultAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HttpFaultAction::Status { status } => write!(f, "STATUS:{status}"),
+            HttpFaultAction::Delay { delay_ms } => write!(f, "DELAY:{delay_ms}ms"),
+            HttpFaultAction::Truncate { keep_bytes } => write!(f, "TRUNCATE:{keep_bytes}B"),
+            HttpFaultAction::Abort => f.write_str("ABORT"),
+        }
+    }
+}
+
+/// Rules are tried in order; the first that matches and wins its
+/// `probability_percent` roll applies its action.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpFaultRule {
+    /// Label of the rule in `agent_http_fault_hits_total`.
+    pub name: String,
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub header: Option<HeaderMatch>,
+    #[serde(flatten)]
+    pub action: HttpFaultAction,
+    #[serde(default = "default_probability_percent")]
+    pub probability_percent: u32,
+}
+
+fn default_probability_percent() -> u32 {
+    100
+}
+
+/// What the DNS responder answers for a name picked by a rule.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DnsFaultAction {
+    /// Claims the name does not exist.
+    Nxdomain,
+    /// Reports a server failure.
+    Servfail,
+    /// Answers A and AAAA queries with `addresses` instead of the real records.
+    Address {
+        addresses: Vec<IpAddr>,
+        #[serde(default = "default_dns_ttl_seconds")]
+        ttl_seconds: u32,
+    },
+    /// Holds the query for `delay_ms` before forwarding it.
+    Delay { delay_ms: u32 },
+}
+
+impl std::fmt::Display for DnsFaultAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnsFaultAction::Nxdomain => f.write_str("NXDOMAIN"),
+            DnsFaultAction::Servfail => f.write_str("SERVFAIL"),
+            DnsFaultAction::Address { addresses, .. } => {
+                f.write_str("ADDRESS:")?;
+                for (i, addr) in addresses.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char('|')?;
+                    }
+                    write!(f, "{addr}")?;
+                }
+                Ok(())
+            }
+            DnsFaultAction::Delay { delay_ms } => write!(f, "DELAY:{delay_ms}ms"),
+        }
+    }
+}
+
+/// Rules are tried in order; the first whose name matches and wins its
+/// `probability_percent` roll answers the query.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsFaultRule {
+    /// Exact name, or `*.suffix` for every name below `suffix`; matched
+    /// case-insensitively and also the label in `agent_dns_fault_hits_total`.
+    pub name: String,
+    #[serde(flatten)]
+    pub action: DnsFaultAction,
+    #[serde(default = "default_probability_percent")]
+    pub probability_percent: u32,
+}
+
+/// Processes a process experiment acts on; the agent itself never matches.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "match", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ProcessMatch {
+    Pid {
+        pid: u32,
+    },
+    /// Exact name as in `/proc/<pid>/comm`, which the kernel cuts to 15 bytes.
+    Name {
+        name: String,
+    },
+    /// Substring of the command line with its arguments joined by spaces.
+    Cmdline {
+        contains: String,
+    },
+    /// File name of the executable `/proc/<pid>/exe` points to.
+    Exe {
+        name: String,
+    },
+    /// Regular expression searched in the space-joined command line.
+    CmdlineRegex {
+        regex: String,
+    },
+    /// Members of this cgroup or any cgroup below it, as listed in
+    /// `/proc/<pid>/cgroup`.
+    Cgroup {
+        path: String,
+    },
+}
+
+impl std::fmt::Display for ProcessMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessMatch::Pid { pid } => write!(f, "PID:{pid}"),
+            ProcessMatch::Name { name } => write!(f, "NAME:{name}"),
+            ProcessMatch::Cmdline { contains } => write!(f, "CMDLINE:{contains}"),
+            ProcessMatch::Exe { name } => write!(f, "EXE:{name}"),
+            ProcessMatch::CmdlineRegex { regex } => write!(f, "CMDLINE_REGEX:{regex}"),
+            ProcessMatch::Cgroup { path } => write!(f, "CGROUP:{path}"),
+        }
+    }
+}
+
+/// Repeating pause: stopped for `pause_ms`, then running for `resume_ms`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PauseDuty {
+    pub pause_ms: u32,
+    pub resume_ms: u32,
+}
+
+impl std::fmt::Display for PauseDuty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}ms/{}ms", self.pause_ms, self.resume_ms)
+    }
+}
+
+/// Signal a `PROCESS_KILL` experiment sends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum KillSignal {
+    Sighup,
+    Sigint,
+    Sigquit,
+    Sigabrt,
+    #[default]
+    Sigkill,
+    Sigusr1,
+    Sigusr2,
+    Sigterm,
+}
+
+impl std::fmt::Display for KillSignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KillSignal::Sighup => f.write_str("SIGHUP"),
+            KillSignal::Sigint => f.write_str("SIGINT"),
+            KillSignal::Sigquit => f.write_str("SIGQUIT"),
+            KillSignal::Sigabrt => f.write_str("SIGABRT"),
+            KillSignal::Sigkill => f.write_str("SIGKILL"),
+            KillSignal::Sigusr1 => f.write_str("SIGUSR1"),
+            KillSignal::Sigusr2 => f.write_str("SIGUSR2"),
+            KillSignal::Sigterm => f.write_str("SIGTERM"),
+        }
+    }
+}
+
+fn default_dns_ttl_seconds() -> u32 {
+    30
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum StartParams {
-    Cpu { duty_percent: u32 },
-    Memory { memory_mb: u32 },
+    Cpu {
+        duty_percent: u32,
+        #[serde(default)]
+        cores: CpuCores,
+        #[serde(default)]
+        cpu_list: Option<Vec<usize>>,
+        #[serde(default)]
+        closed_loop: bool,
+        #[serde(default)]
+        profile: CpuProfile,
+        #[serde(default)]
+        workload: CpuWorkload,
+    },
+    Memory {
+        memory_mb: u32,
+        #[serde(default)]
+        touch: TouchPattern,
+        #[serde(default = "default_retouch_seconds")]
+        retouch_seconds: u32,
+        #[serde(default)]
+        growth: MemoryGrowth,
+        #[serde(default)]
+        backing: MemoryBacking,
+    },
+    DiskIo {
+        dir: String,
+        #[serde(default = "default_read_percent")]
+        read_percent: u32,
+        #[serde(default = "default_block_size_kb")]
+        block_size_kb: u32,
+        #[serde(default = "default_file_size_mb")]
+        file_size_mb: u32,
+        #[serde(default)]
+        rate: IoRate,
+        /// Writes between `fsync` calls; 0 never syncs explicitly.
+        #[serde(default)]
+        fsync_every: u32,
+    },
+    DiskFill {
+        path: String,
+        amount: FillAmount,
+    },
+    FdExhaustion {
+        target: FdTarget,
+        #[serde(default)]
+        resource: FdResource,
+    },
+    PidsPressure {
+        count: u32,
+        #[serde(default)]
+        mode: PidsMode,
+    },
+    NetworkProxy {
+        listen: String,
+        upstream: String,
+        #[serde(flatten)]
+        faults: TcpFaults,
+    },
+    HttpFault {
+        listen: String,
+        /// Base URL requests are forwarded to, e.g. `http://10.0.0.5:8080`.
+        upstream: String,
+        #[serde(default)]
+        rules: Vec<HttpFaultRule>,
+    },
+    UdpProxy {
+        listen: String,
+        upstream: String,
+        #[serde(flatten)]
+        faults: UdpFaults,
+    },
+    DnsFault {
+        listen: String,
+        /// Resolver unmatched queries are forwarded to, as `host:port`.
+        upstream: String,
+        #[serde(default)]
+        rules: Vec<DnsFaultRule>,
+    },
+    ProcessPause {
+        #[serde(flatten)]
+        target: ProcessMatch,
+        /// Pauses intermittently instead of for the whole experiment.
+        #[serde(default)]
+        duty: Option<PauseDuty>,
+        /// Most processes the experiment may act on; a broader match is refused.
+        #[serde(default = "default_max_targets")]
+        max_targets: u32,
+    },
+    ProcessKill {
+        #[serde(flatten)]
+        target: ProcessMatch,
+        #[serde(default = "default_max_targets")]
+        max_targets: u32,
+        #[serde(default)]
+        signal: KillSignal,
+        /// Re-matches and signals again this often, so restarted processes
+        /// are hit too; absent means once, at the start.
+        #[serde(default)]
+        interval_seconds: Option<u32>,
+        /// Only records which processes would be signalled.
+        #[serde(default)]
+        dry_run: bool,
+    },
+    ProcessThrottle {
+        #[serde(flatten)]
+        target: ProcessMatch,
+        #[serde(default = "default_max_targets")]
+        max_targets: u32,
+        /// Share of wall time the targets may run; stopped for the rest.
+        cpu_percent: u32,
+    },
+    PortExhaustion {
+        /// Loopback `ip:port` the held connections go to.
+        target: String,
+        ports: PortTarget,
+    },
+    CacheThrash {
+        #[serde(default)]
+        level: CacheLevel,
+        /// Working set per worker as a share of the cache; above 100 it
+        /// cannot fit and every pass misses.
+        #[serde(default = "default_cache_size_percent")]
+        size_percent: u32,
+        #[serde(default)]
+        access: CacheAccess,
+        #[serde(default)]
+        cores: CpuCores,
+    },
+    PageCachePressure {
+        dir: String,
+        /// Size of each scratch file; together they should exceed the memory
+        /// left for the page cache.
+        #[serde(default = "default_page_cache_file_size_mb")]
+        file_size_mb: u32,
+        #[serde(default = "default_page_cache_files")]
+        files: u32,
+        /// Read pacing; `IOPS` counts reads of one block.
+        #[serde(default)]
+        rate: IoRate,
+    },
+}
+
+fn default_cache_size_percent() -> u32 {
+    200
+}
+
+fn default_retouch_seconds() -> u32 {
+    10
+}
+
+fn default_read_percent() -> u32 {
+    50
+}
+
+fn default_block_size_kb() -> u32 {
+    64
+}
+
+fn default_file_size_mb() -> u32 {
+    256
+}
+
+fn default_page_cache_file_size_mb() -> u32 {
+    1024
+}
+
+fn default_page_cache_files() -> u32 {
+    1
+}
+
+fn default_max_targets() -> u32 {
+    16
+}
+
+/// Appends `max_targets` to a process experiment label unless it is the default.
+fn push_max_targets(label: &mut String, max_targets: u32) {
+    if max_targets != default_max_targets() {
+        let _ = write!(label, ",max_targets={max_targets}");
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ExperimentParams {
-    Cpu { duty_percent: u32 },
-    Memory { memory_mb: u32 },
+    Cpu {
+        duty_percent: u32,
+        cores: CpuCores,
+        cpu_list: Option<Vec<usize>>,
+        closed_loop: bool,
+        profile: CpuProfile,
+        workload: CpuWorkload,
+    },
+    Memory {
+        memory_mb: u32,
+        touch: TouchPattern,
+        retouch_seconds: u32,
+        growth: MemoryGrowth,
+        backing: MemoryBacking,
+    },
+    DiskIo {
+        dir: String,
+        read_percent: u32,
+        block_size_kb: u32,
+        file_size_mb: u32,
+        rate: IoRate,
+        fsync_every: u32,
+    },
+    DiskFill {
+        path: String,
+        amount: FillAmount,
+    },
+    FdExhaustion {
+        target: FdTarget,
+        resource: FdResource,
+    },
+    PidsPressure {
+        count: u32,
+        mode: PidsMode,
+    },
+    NetworkProxy {
+        listen: String,
+        upstream: String,
+        faults: TcpFaults,
+    },
+    HttpFault {
+        listen: String,
+        upstream: String,
+        rules: Vec<HttpFaultRule>,
+    },
+    UdpProxy {
+        listen: String,
+        upstream: String,
+        faults: UdpFaults,
+    },
+    DnsFault {
+        listen: String,
+        upstream: String,
+        rules: Vec<DnsFaultRule>,
+    },
+    ProcessPause {
+        target: ProcessMatch,
+        max_targets: u32,
+        duty: Option<PauseDuty>,
+    },
+    ProcessKill {
+        target: ProcessMatch,
+        max_targets: u32,
+        signal: KillSignal,
+        interval_seconds: Option<u32>,
+        dry_run: bool,
+    },
+    ProcessThrottle {
+        target: ProcessMatch,
+        max_targets: u32,
+        cpu_percent: u32,
+    },
+    PortExhaustion {
+        target: String,
+        ports: PortTarget,
+    },
+    CacheThrash {
+        level: CacheLevel,
+        size_percent: u32,
+        access: CacheAccess,
+        cores: CpuCores,
+    },
+    PageCachePressure {
+        dir: String,
+        file_size_mb: u32,
+        files: u32,
+        rate: IoRate,
+    },
 }
@@ -1,17 +1,21 @@
 #![forbid(unsafe_code)]
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
 
 use actix_web::{get, post, web, App, HttpResponse, HttpServer};
 //
 use serde_json::json;
+use std::time::Duration;
 use tracing::{error, info, warn};
 
 use crate::domain::{AppState, StartRequest};
 use crate::metrics::Metrics;
-use crate::service::ExperimentRunner;
+use crate::service::{ExperimentRunner, StopOutcome};
 // validation performed by service
 
+const STOP_RELEASE_WAIT: Duration = Duration::from_secs(5);
+
 #[post("/experiments")]
 pub async fn start(payload: web::Json<StartRequest>, data: web::Data<AppState>) -> HttpResponse {
     let req = payload.into_inner();
@@ -23,7 +27,7 @@ pub async fn start(payload: web::Json<StartRequest>, data: web::Data<AppState>)
             &format!("another experiment running: {running_id}"),
         );
     }
-    if let Err(e) = runner.validate_request(&req) {
+    if let Err(e) = runner.validate_request(&req).await {
         return json_error(actix_web::http::StatusCode::BAD_REQUEST, &format!("{e:#}"));
     }
     let now = chrono::Utc::now().timestamp();
@@ -31,11 +35,18 @@ pub async fn start(payload: web::Json<StartRequest>, data: web::Data<AppState>)
         Ok(e) => e,
         Err(e) => return json_error(actix_web::http::StatusCode::BAD_REQUEST, &format!("{e:#}")),
     };
-    runner.begin(&exp);
-    let runner_clone = runner.clone();
-    tokio::spawn(async move {
-        runner_clone.run_to_completion(exp).await;
-    });
+    // Re-checked under the state lock: a concurrent start may have won the
+    // race since `running_id`.
+    let handle = match runner.begin(&exp) {
+        Ok(h) => h,
+        Err(e) => return json_error(actix_web::http::StatusCode::CONFLICT, &format!("{e:#}")),
+    };
+    if let Err(e) = runner.spawn(exp, handle) {
+        return json_error(
+            actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("{e:#}"),
+        );
+    }
     HttpResponse::Accepted().json(json!({"status":"ok"}))
 }
 
@@ -43,15 +54,20 @@ pub async fn start(payload: web::Json<StartRequest>, data: web::Data<AppState>)
 pub async fn stop(path: web::Path<String>, data: web::Data<AppState>) -> HttpResponse {
     let id = path.into_inner();
     let runner = ExperimentRunner::new(data.ctrl.clone(), data.metrics.clone());
-    if runner.stop(&id) {
-        info!(experiment=%id, "stop experiment request");
-        HttpResponse::Ok().json(json!({"status":"ok"}))
-    } else {
-        warn!(experiment=%id, "stop: not found");
-        json_error(
-            actix_web::http::StatusCode::NOT_FOUND,
-            "experiment not found",
-        )
+    info!(experiment=%id, "stop experiment request");
+    match runner.stop(&id, STOP_RELEASE_WAIT).await {
+        StopOutcome::Released => HttpResponse::Ok().json(json!({"status":"ok"})),
+        StopOutcome::Releasing => {
+            warn!(experiment=%id, "stop: load release still in progress");
+            HttpResponse::Accepted().json(json!({"status":"stopping"}))
+        }
+        StopOutcome::NotFound => {
+            warn!(experiment=%id, "stop: not found");
+            json_error(
+                actix_web::http::StatusCode::NOT_FOUND,
+                "experiment not found",
+            )
+        }
     }
 }
 
@@ -97,14 +113,18 @@ pub async fn scrape_metrics(data: web::Data<AppState>) -> HttpResponse {
 // no-op: logic moved to service::ExperimentRunner
 
 pub async fn serve(bind: &str) -> std::io::Result<()> {
-    let metrics = Metrics::new().map_err(|e| {
-        std::io::Error::new(std::io::ErrorKind::Other, format!("metrics init: {e:#}"))
-    })?;
+    serve_listener(std::net::TcpListener::bind(bind)?).await
+}
+
+pub async fn serve_listener(listener: std::net::TcpListener) -> std::io::Result<()> {
+    let metrics =
+        Metrics::new().map_err(|e| std::io::Error::other(format!("metrics init: {e:#}")))?;
     let state = AppState {
         ctrl: crate::domain::LoadController::default(),
         metrics,
     };
-    HttpServer::new(move || {
+    let runner = ExperimentRunner::new(state.ctrl.clone(), state.metrics.clone());
+    let served = HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(state.clone()))
             .service(healthz)
@@ -113,9 +133,16 @@ pub async fn serve(bind: &str) -> std::io::Result<()> {
             .service(status)
             .service(scrape_metrics)
     })
-    .bind(bind)?
+    .listen(listener)?
     .run()
-    .await
+    .await;
+    // Release a load still running at shutdown so it reaps its children and
+    // removes its scratch files before the process exits.
+    if let Some(id) = runner.running_id() {
+        info!(experiment=%id, "stopping experiment on shutdown");
+        runner.stop(&id, STOP_RELEASE_WAIT).await;
+    }
+    served
 }
 
 fn json_error(code: actix_web::http::StatusCode, reason: &str) -> HttpResponse {
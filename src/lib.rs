@@ -1,18 +1,32 @@
 #![forbid(unsafe_code)]
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
 
 pub mod domain;
 pub mod http;
+pub mod lib_cache;
 pub mod lib_cpu;
+pub mod lib_disk;
+pub mod lib_dns;
+pub mod lib_fd;
+pub mod lib_http_fault;
 pub mod lib_mem;
+pub mod lib_net;
+pub mod lib_pids;
+pub mod lib_port;
+pub mod lib_proc;
+pub mod lib_udp;
 pub mod metrics;
 pub mod service;
 pub mod validation;
 
-pub use domain::{AppState, ExperimentState, LoadController, StartRequest};
-pub use http::serve;
+pub use domain::{
+    AppState, CpuCores, CpuProfile, ExperimentPhase, ExperimentState, IoRate, LoadController,
+    MemoryGrowth, StartRequest, TouchPattern,
+};
 pub use http::{healthz, scrape_metrics, start, status, stop};
+pub use http::{serve, serve_listener};
 pub use metrics::Metrics;
-pub use service::ExperimentRunner;
+pub use service::{ExperimentRunner, StopOutcome};
 pub use validation::validate_start;
@@ -0,0 +1,246 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use anyhow::{anyhow, bail, Context, Result as AnyResult};
+use rand::seq::SliceRandom;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::domain::{CacheAccess, CacheLevel, CpuCores};
+use crate::lib_cpu::{lower_thread_priority, plan_workers};
+use crate::metrics::Metrics;
+
+/// Cache topology of the first CPU; the agent assumes a symmetric host.
+pub const CACHE_SYSFS: &str = "/sys/devices/system/cpu/cpu0/cache";
+
+/// Lines a worker touches between cancellation checks.
+const CANCEL_CHECK_LINES: usize = 1 << 16;
+
+/// How often the bandwidth gauge is recomputed.
+const BANDWIDTH_SAMPLE: Duration = Duration::from_secs(1);
+
+/// Page size the strided pattern jumps by, defeating the next-line prefetcher.
+const STRIDE_BYTES: u64 = 4096;
+
+#[derive(Clone, Debug)]
+pub struct CacheThrashSpec {
+    pub level: CacheLevel,
+    pub size_percent: u32,
+    pub access: CacheAccess,
+    pub cores: CpuCores,
+}
+
+/// One cache as described by a `/sys/.../cache/index<N>` directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheInfo {
+    pub level: u8,
+    /// `Data`, `Instruction` or `Unified`.
+    pub kind: String,
+    pub size_bytes: u64,
+    pub line_bytes: u64,
+}
+
+/// Every worker streams through its own working set of `size_percent` of the
+/// chosen cache, writing one byte per line so lines are also written back.
+pub async fn cache_thrash_load(
+    _experiment_id: String,
+    spec: CacheThrashSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let caches = read_caches(Path::new(CACHE_SYSFS))?;
+    let cache = data_cache(&caches, spec.level)
+        .with_context(|| format!("no {} data cache in {CACHE_SYSFS}", spec.level))?;
+    let set_bytes = working_set_bytes(cache, spec.size_percent);
+    let line_bytes = cache.line_bytes;
+    let lines = usize::try_from(set_bytes / line_bytes).context("working set too large")?;
+    let order = Arc::new(visit_order(lines, line_bytes, spec.access));
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let workers_cancel = cancel.child_token();
+    let moved = Arc::new(AtomicU64::new(0));
+    let mut handles = Vec::new();
+    let mut spawn_err = None;
+    for worker in 0..plan_workers(spec.cores, None).len() {
+        let order = order.clone();
+        let moved = moved.clone();
+        let cancel = workers_cancel.clone();
+        let spawned = thread::Builder::new()
+            .name(format!("cache-hog-{worker}"))
+            .spawn(move || thrash_worker(worker, &order, line_bytes, &cancel, &moved));
+        match spawned {
+            Ok(h) => handles.push((worker, h)),
+            Err(e) => {
+                spawn_err = Some(anyhow!(e).context(format!("spawn cache worker {worker}")));
+                break;
+            }
+        }
+    }
+    mtr.cache_thrash_working_set_bytes
+        .set(i64::try_from(set_bytes).unwrap_or(i64::MAX));
+    let mut sampled_at = Instant::now();
+    let mut sampled = 0;
+    while spawn_err.is_none() && !handles.iter().any(|(_, h)| h.is_finished()) {
+        let next = (sampled_at + BANDWIDTH_SAMPLE).min(end);
+        tokio::select! {
+            () = sleep_until(next) => {}
+            () = cancel.cancelled() => break,
+        }
+        let now = Instant::now();
+        let total = moved.load(Ordering::Relaxed);
+        let secs = now.duration_since(sampled_at).as_secs_f64();
+        if secs > 0.0 {
+            // Bytes per second fit an i64 by many orders of magnitude.
+            #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+            let rate = ((total - sampled) as f64 / secs) as i64;
+            mtr.cache_thrash_bandwidth_bytes.set(rate);
+        }
+        sampled_at = now;
+        sampled = total;
+        if now >= end {
+            break;
+        }
+    }
+    workers_cancel.cancel();
+    let joined = tokio::task::spawn_blocking(move || {
+        let mut first_err = None;
+        for (worker, h) in handles {
+            let res = h
+                .join()
+                .map_err(|_| anyhow!("cache worker {worker} panicked"))
+                .and_then(|r| r);
+            if let Err(e) = res {
+                first_err.get_or_insert(e);
+            }
+        }
+        first_err.map_or(Ok(()), Err)
+    })
+    .await
+    .context("join cache workers");
+    mtr.cache_thrash_working_set_bytes.set(0);
+    mtr.cache_thrash_bandwidth_bytes.set(0);
+    if let Some(e) = spawn_err {
+        return Err(e);
+    }
+    joined?
+}
+
+fn thrash_worker(
+    worker: usize,
+    order: &[u32],
+    line_bytes: u64,
+    cancel: &CancellationToken,
+    moved: &AtomicU64,
+) -> AnyResult<()> {
+    lower_thread_priority().with_context(|| format!("cache worker {worker}"))?;
+    let line = usize::try_from(line_bytes).context("line size")?;
+    let mut buf = vec![0u8; order.len() * line];
+    while !cancel.is_cancelled() {
+        for chunk in order.chunks(CANCEL_CHECK_LINES) {
+            for &idx in chunk {
+                let at = idx as usize * line;
+                buf[at] = buf[at].wrapping_add(1);
+            }
+            moved.fetch_add(chunk.len() as u64 * line_bytes, Ordering::Relaxed);
+            if cancel.is_cancelled() {
+                break;
+            }
+        }
+    }
+    std::hint::black_box(&buf);
+    Ok(())
+}
+
+/// Line indices in the order `access` visits them on every pass.
+#[must_use]
+pub fn visit_order(lines: usize, line_bytes: u64, access: CacheAccess) -> Vec<u32> {
+    let lines = u32::try_from(lines).unwrap_or(u32::MAX);
+    match access {
+        CacheAccess::Sequential => (0..lines).collect(),
+        CacheAccess::Strided => {
+            let per_page = u32::try_from((STRIDE_BYTES / line_bytes.max(1)).max(1)).unwrap_or(1);
+            (0..per_page)
+                .flat_map(|offset| (offset..lines).step_by(per_page as usize))
+                .collect()
+        }
+        CacheAccess::Random => {
+            let mut order: Vec<u32> = (0..lines).collect();
+            order.shuffle(&mut rand::rng());
+            order
+        }
+    }
+}
+
+/// `size_percent` of the cache, rounded up to whole lines.
+#[must_use]
+pub fn working_set_bytes(cache: &CacheInfo, size_percent: u32) -> u64 {
+    let bytes = cache.size_bytes.saturating_mul(u64::from(size_percent)) / 100;
+    bytes.div_ceil(cache.line_bytes.max(1)) * cache.line_bytes.max(1)
+}
+
+/// The data or unified cache at `level`; instruction caches are skipped.
+#[must_use]
+pub fn data_cache(caches: &[CacheInfo], level: CacheLevel) -> Option<&CacheInfo> {
+    caches
+        .iter()
+        .find(|c| c.level == level.number() && c.kind != "Instruction")
+}
+
+/// Reads every `index<N>` entry under a sysfs cache directory.
+pub fn read_caches(dir: &Path) -> AnyResult<Vec<CacheInfo>> {
+    let mut caches = Vec::new();
+    let entries = std::fs::read_dir(dir).with_context(|| format!("read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry.context("list cache directory")?.path();
+        let is_index = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("index"));
+        if !is_index {
+            continue;
+        }
+        let field = |name: &str| -> AnyResult<String> {
+            let file = path.join(name);
+            Ok(std::fs::read_to_string(&file)
+                .with_context(|| format!("read {}", file.display()))?
+                .trim()
+                .to_string())
+        };
+        caches.push(CacheInfo {
+            level: field("level")?.parse().context("cache level")?,
+            kind: field("type")?,
+            size_bytes: parse_cache_size(&field("size")?)?,
+            line_bytes: field("coherency_line_size")?
+                .parse()
+                .context("cache line size")?,
+        });
+    }
+    caches.sort_by_key(|c| c.level);
+    Ok(caches)
+}
+
+/// Parses sysfs sizes such as `48K`, `2048K` or `32M`.
+pub fn parse_cache_size(raw: &str) -> AnyResult<u64> {
+    let raw = raw.trim();
+    let (digits, unit) = match raw.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((at, _)) => raw.split_at(at),
+        None => (raw, ""),
+    };
+    let value: u64 = digits
+        .parse()
+        .with_context(|| format!("cache size {raw:?}"))?;
+    let scale = match unit {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        other => bail!("cache size {raw:?} has unknown unit {other:?}"),
+    };
+    Ok(value * scale)
+}
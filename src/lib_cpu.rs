@@ -1,36 +1,484 @@
 #![forbid(unsafe_code)]
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
 
-use anyhow::Result as AnyResult;
-use tokio::time::{sleep, Duration};
+use anyhow::{anyhow, Context, Result as AnyResult};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::domain::{CpuCores, CpuProfile, CpuWorkload};
+use crate::metrics::Metrics;
+
+/// Longest uninterrupted sleep of a worker, bounds how late it sees a cancel.
+const CANCEL_POLL: Duration = Duration::from_millis(10);
+
+/// Length of one busy/idle duty window.
+pub(crate) const DUTY_WINDOW: Duration = Duration::from_secs(1);
+
+/// Niceness of load threads, so the HTTP workers always win the CPU.
+const LOAD_THREAD_NICE: i32 = 19;
+
+#[derive(Clone, Debug)]
+pub struct CpuLoadSpec {
+    pub duty_percent: u32,
+    pub cores: CpuCores,
+    pub cpu_list: Option<Vec<usize>>,
+    pub closed_loop: bool,
+    pub profile: CpuProfile,
+    pub workload: CpuWorkload,
+}
+
+#[must_use]
+pub fn available_cores() -> usize {
+    thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+}
+
+/// CPU ids this process may be pinned to, if the platform exposes them.
+#[must_use]
+pub fn allowed_cpus() -> Option<Vec<usize>> {
+    core_affinity::get_core_ids().map(|ids| ids.into_iter().map(|c| c.id).collect())
+}
+
+/// Drops the calling thread to the lowest scheduling priority.
+pub fn lower_thread_priority() -> AnyResult<()> {
+    rustix::process::setpriority_process(Some(rustix::thread::gettid()), LOAD_THREAD_NICE)
+        .context("set load thread niceness")
+}
+
+/// One entry per worker thread, holding the CPU it is pinned to (if any).
+#[must_use]
+pub fn plan_workers(cores: CpuCores, cpu_list: Option<&[usize]>) -> Vec<Option<usize>> {
+    let count = match (cores, cpu_list) {
+        (CpuCores::Count(n), _) => n as usize,
+        (CpuCores::All, Some(list)) => list.len(),
+        (CpuCores::All, None) => available_cores(),
+    };
+    (0..count)
+        .map(|i| cpu_list.filter(|l| !l.is_empty()).map(|l| l[i % l.len()]))
+        .collect()
+}
 
 pub async fn cpu_load(
     _experiment_id: String,
-    cpu_percent: u32,
+    spec: CpuLoadSpec,
     duration_seconds: u32,
-    mtr: crate::metrics::Metrics,
+    mtr: Metrics,
+    cancel: CancellationToken,
 ) -> AnyResult<()> {
-    let cpu_percent = cpu_percent.max(1).min(100);
+    let cpu_percent = spec.duty_percent.clamp(1, 100);
+    let spec = std::sync::Arc::new(spec);
+    let workers = plan_workers(spec.cores, spec.cpu_list.as_deref());
     mtr.mark_cpu_active(cpu_percent);
-    // Model duty cycle per second: busy for (cpu_percent)% of 1s, sleep for the rest.
-    let on = Duration::from_millis((10 * cpu_percent) as u64); // scale to 1s window: 10ms * percent = X% of 1s
-    let off = Duration::from_millis((1000 - (10 * cpu_percent)) as u64);
-    let end = tokio::time::Instant::now() + Duration::from_secs(u64::from(duration_seconds));
-    let mut last_seconds_inc = 0u64;
-    while tokio::time::Instant::now() < end {
-        let spin_until = tokio::time::Instant::now() + on;
-        while tokio::time::Instant::now() < spin_until {
-            std::hint::spin_loop();
-        }
-        sleep(off).await;
-        // Increase cpu_seconds_total at 1 Hz
-        last_seconds_inc += 1;
-        if last_seconds_inc >= 1 {
-            mtr.cpu_seconds_total.inc();
-            last_seconds_inc = 0;
+    mtr.cpu_hog_cores
+        .set(i64::try_from(workers.len()).unwrap_or(i64::MAX));
+    let started = Instant::now();
+    let end = started + Duration::from_secs(u64::from(duration_seconds));
+    // Child token so a failed spawn or worker can stop the other workers
+    // without cancelling the experiment itself.
+    let workers_cancel = cancel.child_token();
+    let mut handles = Vec::with_capacity(workers.len());
+    let mut spawn_err = None;
+    for (worker, cpu) in workers.into_iter().enumerate() {
+        let mtr = mtr.clone();
+        let cancel = workers_cancel.clone();
+        let spec = spec.clone();
+        let spawned = thread::Builder::new()
+            .name(format!("cpu-hog-{worker}"))
+            .spawn(move || {
+                // However this worker ends, error and panic included, the
+                // rest stop with it instead of burning on until the deadline.
+                let _stop_rest = cancel.clone().drop_guard();
+                spin_worker(worker, cpu, &spec, (started, end), &cancel, &mtr)
+            });
+        match spawned {
+            Ok(h) => handles.push((worker, h)),
+            Err(e) => {
+                spawn_err = Some(anyhow!(e).context(format!("spawn cpu worker {worker}")));
+                workers_cancel.cancel();
+                break;
+            }
         }
     }
+    let joined = tokio::task::spawn_blocking(move || {
+        let mut first_err = None;
+        for (worker, h) in handles {
+            let res = h
+                .join()
+                .map_err(|_| anyhow!("cpu worker {worker} panicked"))
+                .and_then(|r| r);
+            if let Err(e) = res {
+                first_err.get_or_insert(e);
+            }
+        }
+        first_err.map_or(Ok(()), Err)
+    })
+    .await
+    .context("join cpu workers");
     mtr.clear_cpu_active();
+    if let Some(e) = spawn_err {
+        return Err(e);
+    }
+    joined?
+}
+
+fn spin_worker(
+    worker: usize,
+    cpu: Option<usize>,
+    spec: &CpuLoadSpec,
+    (started, end): (Instant, Instant),
+    cancel: &CancellationToken,
+    mtr: &Metrics,
+) -> AnyResult<()> {
+    if let Some(id) = cpu {
+        if !core_affinity::set_for_current(core_affinity::CoreId { id }) {
+            return Err(anyhow!("pin cpu worker {worker} to cpu {id}"));
+        }
+    }
+    lower_thread_priority().with_context(|| format!("cpu worker {worker}"))?;
+    let mut burner = Burner::new(spec.workload, worker, cpu)?;
+    let base_percent = spec.duty_percent.clamp(1, 100);
+    let worker_label = worker.to_string();
+    let cpu_label = cpu.map_or_else(|| "any".to_string(), |id| id.to_string());
+    let labels = [worker_label.as_str(), cpu_label.as_str()];
+    let _series = WorkerSeries {
+        mtr,
+        labels: &labels,
+    };
+    let mut controller = DutyController::new(base_percent);
+    let mut window_start = Instant::now();
+    let mut cpu_start = thread_cpu_split().ok();
+    while Instant::now() < end && !cancel.is_cancelled() {
+        let cpu_percent = duty_at(&spec.profile, base_percent, started.elapsed());
+        mtr.cpu_hog_core_duty_percent
+            .with_label_values(&labels)
+            .set(i64::from(cpu_percent));
+        if worker == 0 {
+            mtr.cpu_hog_duty_percent.set(i64::from(cpu_percent));
+        }
+        controller.set_target(cpu_percent);
+        // Model duty cycle per second: busy for on/DUTY_WINDOW, sleep for the rest.
+        let on = if spec.closed_loop {
+            controller.busy()
+        } else {
+            duty_on(cpu_percent)
+        };
+        let spin_until = Instant::now() + on;
+        while Instant::now() < spin_until && !cancel.is_cancelled() {
+            burner.burn();
+        }
+        sleep_cancellable(DUTY_WINDOW.saturating_sub(on), cancel);
+        mtr.cpu_seconds_total.inc();
+        let cpu_now = thread_cpu_split().ok();
+        if let (Some(before), Some(after)) = (cpu_start, cpu_now) {
+            let wall = window_start.elapsed().as_secs_f64();
+            let percent =
+                |from: Duration, to: Duration| to.saturating_sub(from).as_secs_f64() / wall * 100.0;
+            let user = percent(before.0, after.0);
+            let system = percent(before.1, after.1);
+            let measured = user + system;
+            controller.observe(measured);
+            mtr.set_cpu_measured(&labels, measured, f64::from(cpu_percent) - measured);
+            mtr.set_cpu_modes(&labels, user, system);
+        } else if spec.closed_loop {
+            return Err(anyhow!("cpu worker {worker} lost thread cpu time"));
+        }
+        window_start = Instant::now();
+        cpu_start = cpu_now;
+    }
     Ok(())
 }
+
+/// Removes a worker's labelled series however the worker ends, error and
+/// panic included, so a dead worker never lingers in the scrape.
+struct WorkerSeries<'a> {
+    mtr: &'a Metrics,
+    labels: &'a [&'a str],
+}
+
+impl Drop for WorkerSeries<'_> {
+    fn drop(&mut self) {
+        self.mtr.clear_cpu_worker(self.labels);
+    }
+}
+
+/// Per-worker state of the [`CpuWorkload`] run in the busy slice of a window.
+enum Burner {
+    Spin,
+    Integer(u64),
+    Float(f64),
+    Hash { buf: Vec<u8>, at: usize },
+    Syscall,
+    ContextSwitch(Echo),
+}
+
+/// Rendezvous channels to an echo thread, joined on drop so the thread never
+/// outlives its worker.
+struct Echo {
+    request: Option<SyncSender<u64>>,
+    reply: Receiver<u64>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for Echo {
+    fn drop(&mut self) {
+        // The echo thread exits once `request` disconnects.
+        self.request = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Burner {
+    /// Loop iterations per [`Burner::burn`] of the arithmetic workloads.
+    const BATCH: u32 = 1024;
+    /// `getpid` calls per [`Burner::burn`].
+    const SYSCALLS: u32 = 64;
+    /// Buffer the hash workload cycles through, sized to stay in L2.
+    const HASH_BYTES: usize = 64 << 10;
+    /// Bytes hashed per [`Burner::burn`].
+    const HASH_CHUNK: usize = 4 << 10;
+
+    fn new(workload: CpuWorkload, worker: usize, cpu: Option<usize>) -> AnyResult<Self> {
+        Ok(match workload {
+            CpuWorkload::Spin => Self::Spin,
+            // xorshift never leaves a non-zero seed.
+            CpuWorkload::Integer => Self::Integer(u64::try_from(worker).unwrap_or(0) | 1),
+            CpuWorkload::Float => Self::Float(1.0),
+            CpuWorkload::Hash => Self::Hash {
+                buf: vec![0x5a; Self::HASH_BYTES],
+                at: 0,
+            },
+            CpuWorkload::Syscall => Self::Syscall,
+            CpuWorkload::ContextSwitch => {
+                let (request, request_rx) = mpsc::sync_channel::<u64>(0);
+                let (reply_tx, reply) = mpsc::sync_channel(0);
+                let thread = thread::Builder::new()
+                    .name(format!("cpu-hog-{worker}-echo"))
+                    .spawn(move || {
+                        // Sharing the worker's CPU makes every hop a switch.
+                        if let Some(id) = cpu {
+                            core_affinity::set_for_current(core_affinity::CoreId { id });
+                        }
+                        let _ = lower_thread_priority();
+                        while let Ok(v) = request_rx.recv() {
+                            if reply_tx.send(v).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .with_context(|| format!("spawn echo thread of cpu worker {worker}"))?;
+                Self::ContextSwitch(Echo {
+                    request: Some(request),
+                    reply,
+                    thread: Some(thread),
+                })
+            }
+        })
+    }
+
+    /// Runs one short batch of work, a few microseconds at most.
+    fn burn(&mut self) {
+        match self {
+            Self::Spin => std::hint::spin_loop(),
+            Self::Integer(x) => {
+                for _ in 0..Self::BATCH {
+                    *x ^= *x << 13;
+                    *x ^= *x >> 7;
+                    *x ^= *x << 17;
+                    *x = x.wrapping_mul(0x2545_f491_4f6c_dd1d);
+                }
+                std::hint::black_box(*x);
+            }
+            Self::Float(v) => {
+                for _ in 0..Self::BATCH {
+                    *v = v.mul_add(1.000_001, 0.5).sqrt();
+                }
+                std::hint::black_box(*v);
+            }
+            Self::Hash { buf, at } => {
+                // FNV-1a over one chunk, digest stored back so the next pass
+                // hashes different bytes.
+                let chunk = &mut buf[*at..*at + Self::HASH_CHUNK];
+                let digest = chunk.iter().fold(0xcbf2_9ce4_8422_2325_u64, |h, &b| {
+                    (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+                });
+                chunk[..8].copy_from_slice(&digest.to_le_bytes());
+                *at = (*at + Self::HASH_CHUNK) % Self::HASH_BYTES;
+            }
+            Self::Syscall => {
+                for _ in 0..Self::SYSCALLS {
+                    std::hint::black_box(rustix::process::getpid());
+                }
+            }
+            Self::ContextSwitch(echo) => {
+                if echo.request.as_ref().is_some_and(|r| r.send(0).is_ok()) {
+                    let _ = echo.reply.recv();
+                }
+            }
+        }
+    }
+}
+
+/// Busy share of one [`DUTY_WINDOW`] at `percent`.
+pub(crate) fn duty_on(percent: u32) -> Duration {
+    DUTY_WINDOW * percent.min(100) / 100
+}
+
+/// Duty percentage the profile asks for `elapsed` into the experiment.
+#[must_use]
+pub fn duty_at(profile: &CpuProfile, base_percent: u32, elapsed: Duration) -> u32 {
+    let secs = elapsed.as_secs();
+    let percent = match profile {
+        CpuProfile::Constant => base_percent,
+        CpuProfile::Ramp {
+            from_percent,
+            ramp_seconds,
+        } => {
+            let ramp_millis = u128::from(*ramp_seconds) * 1000;
+            let millis = elapsed.as_millis().min(ramp_millis);
+            let from = i128::from(*from_percent);
+            let delta = i128::from(base_percent) - from;
+            let at = from
+                + delta * i128::try_from(millis).unwrap_or(i128::MAX)
+                    / i128::try_from(ramp_millis.max(1)).unwrap_or(i128::MAX);
+            u32::try_from(at).unwrap_or(base_percent)
+        }
+        CpuProfile::Steps {
+            percents,
+            step_seconds,
+        } => {
+            let idx =
+                usize::try_from(secs / u64::from((*step_seconds).max(1))).unwrap_or(usize::MAX);
+            percents
+                .get(idx)
+                .or(percents.last())
+                .copied()
+                .unwrap_or(base_percent)
+        }
+        CpuProfile::Sine {
+            period_seconds,
+            amplitude_percent,
+        } => {
+            let phase = elapsed.as_secs_f64() / f64::from((*period_seconds).max(1));
+            let wave = f64::from(*amplitude_percent) * (std::f64::consts::TAU * phase).sin();
+            let at = (f64::from(base_percent) + wave).round().clamp(0.0, 100.0);
+            // Clamped to 0..=100 above, so the cast cannot truncate or wrap.
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let at = at as u32;
+            at
+        }
+        CpuProfile::Square {
+            period_seconds,
+            low_percent,
+        } => {
+            let period = u64::from((*period_seconds).max(2));
+            if secs % period < period / 2 {
+                base_percent
+            } else {
+                *low_percent
+            }
+        }
+    };
+    percent.min(100)
+}
+
+/// Integral controller steering the busy slice of a duty window so that the
+/// measured thread utilization converges on the target percentage.
+#[derive(Clone, Debug)]
+pub struct DutyController {
+    target_percent: f64,
+    busy_ms: f64,
+}
+
+impl DutyController {
+    const GAIN: f64 = 0.5;
+
+    #[must_use]
+    pub fn new(target_percent: u32) -> Self {
+        let target_percent = f64::from(target_percent);
+        Self {
+            target_percent,
+            busy_ms: window_ms() * target_percent / 100.0,
+        }
+    }
+
+    /// Retargets the controller, keeping the correction learned so far.
+    pub fn set_target(&mut self, target_percent: u32) {
+        let target_percent = f64::from(target_percent);
+        let shift = (target_percent - self.target_percent) * window_ms() / 100.0;
+        self.busy_ms = (self.busy_ms + shift).clamp(0.0, window_ms());
+        self.target_percent = target_percent;
+    }
+
+    /// Feeds the utilization measured over the last window.
+    pub fn observe(&mut self, measured_percent: f64) {
+        let error = self.target_percent - measured_percent;
+        self.busy_ms =
+            (self.busy_ms + Self::GAIN * error * window_ms() / 100.0).clamp(0.0, window_ms());
+    }
+
+    #[must_use]
+    pub fn busy(&self) -> Duration {
+        Duration::from_secs_f64(self.busy_ms / 1000.0)
+    }
+}
+
+fn window_ms() -> f64 {
+    DUTY_WINDOW.as_secs_f64() * 1000.0
+}
+
+/// CPU time (user + system) consumed so far by the calling thread.
+pub fn thread_cpu_time() -> AnyResult<Duration> {
+    let (user, system) = thread_cpu_split()?;
+    Ok(user + system)
+}
+
+/// User and system CPU time consumed so far by the calling thread.
+pub fn thread_cpu_split() -> AnyResult<(Duration, Duration)> {
+    let tid = rustix::thread::gettid().as_raw_nonzero();
+    let path = format!("/proc/self/task/{tid}/stat");
+    let stat = std::fs::read_to_string(&path).with_context(|| format!("read {path}"))?;
+    let (user, system) = parse_stat_cpu_split(&stat).with_context(|| format!("parse {path}"))?;
+    let hz = rustix::param::clock_ticks_per_second().max(1);
+    let time = |ticks: u64| Duration::from_nanos(ticks.saturating_mul(1_000_000_000) / hz);
+    Ok((time(user), time(system)))
+}
+
+/// Sums `utime` and `stime` (fields 14 and 15) of a `/proc/.../stat` line.
+pub fn parse_stat_cpu_ticks(stat: &str) -> AnyResult<u64> {
+    let (user, system) = parse_stat_cpu_split(stat)?;
+    Ok(user + system)
+}
+
+/// `utime` and `stime` (fields 14 and 15) of a `/proc/.../stat` line.
+pub fn parse_stat_cpu_split(stat: &str) -> AnyResult<(u64, u64)> {
+    // comm (field 2) may contain spaces, so count fields after its closing paren.
+    let rest = stat
+        .rsplit_once(')')
+        .map(|(_, rest)| rest)
+        .ok_or_else(|| anyhow!("missing comm"))?;
+    let mut fields = rest.split_whitespace().skip(11);
+    let mut next = |name: &str| -> AnyResult<u64> {
+        fields
+            .next()
+            .ok_or_else(|| anyhow!("missing {name}"))?
+            .parse()
+            .with_context(|| format!("bad {name}"))
+    };
+    Ok((next("utime")?, next("stime")?))
+}
+
+pub(crate) fn sleep_cancellable(dur: Duration, cancel: &CancellationToken) {
+    let until = Instant::now() + dur;
+    loop {
+        let now = Instant::now();
+        if now >= until || cancel.is_cancelled() {
+            return;
+        }
+        thread::sleep((until - now).min(CANCEL_POLL));
+    }
+}
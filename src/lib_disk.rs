@@ -0,0 +1,400 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use anyhow::{anyhow, Context, Result as AnyResult};
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::domain::{FillAmount, IoRate};
+use crate::lib_cpu::sleep_cancellable;
+use crate::metrics::Metrics;
+
+/// How far the pacer may fall behind before it stops trying to catch up.
+const MAX_PACING_LAG: Duration = Duration::from_secs(1);
+
+/// Ballast allocated per step, bounds how late the fill sees a cancel.
+const FILL_CHUNK: u64 = 64 * MIB;
+
+/// Unit the page cache hog writes and reads its scratch files in.
+const PAGE_CACHE_BLOCK: usize = 1024 * KIB;
+
+const KIB: usize = 1024;
+const MIB: u64 = 1024 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct DiskFillSpec {
+    pub path: String,
+    pub amount: FillAmount,
+}
+
+#[derive(Clone, Debug)]
+pub struct DiskIoSpec {
+    pub dir: String,
+    pub read_percent: u32,
+    pub block_size_kb: u32,
+    pub file_size_mb: u32,
+    pub rate: IoRate,
+    pub fsync_every: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct PageCachePressureSpec {
+    pub dir: String,
+    pub file_size_mb: u32,
+    pub files: u32,
+    pub rate: IoRate,
+}
+
+pub async fn disk_io_load(
+    experiment_id: String,
+    spec: DiskIoSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let path = scratch_path(Path::new(&spec.dir), "disk-io", &experiment_id);
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    tokio::task::spawn_blocking(move || io_worker(&path, &spec, end, &cancel, &mtr))
+        .await
+        .context("join disk io worker")?
+}
+
+/// Scratch file a `purpose` hog keeps for one experiment; the id is sanitized
+/// so it cannot escape `dir`.
+#[must_use]
+pub fn scratch_path(dir: &Path, purpose: &str, experiment_id: &str) -> PathBuf {
+    let id: String = experiment_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    dir.join(format!(".chimp-{purpose}-{id}.tmp"))
+}
+
+/// Removes the scratch file however the worker exits, including on panic.
+pub(crate) struct ScratchFile {
+    path: PathBuf,
+    pub(crate) file: File,
+}
+
+impl ScratchFile {
+    pub(crate) fn create(path: &Path) -> AnyResult<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .with_context(|| format!("create scratch file {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+}
+
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(path=%self.path.display(), error=%e, "remove scratch file");
+            }
+        }
+    }
+}
+
+fn io_worker(
+    path: &Path,
+    spec: &DiskIoSpec,
+    end: Instant,
+    cancel: &CancellationToken,
+    mtr: &Metrics,
+) -> AnyResult<()> {
+    let scratch = ScratchFile::create(path)?;
+    let file = &scratch.file;
+    let block = spec.block_size_kb as usize * KIB;
+    let blocks = (u64::from(spec.file_size_mb) * MIB / block as u64).max(1);
+    let mut buf = vec![0xa5u8; block];
+    // Lay the file out first so reads never hit holes.
+    for i in 0..blocks {
+        if Instant::now() >= end || cancel.is_cancelled() {
+            return Ok(());
+        }
+        file.write_all_at(&buf, i * block as u64)
+            .context("fill scratch file")?;
+        mtr.record_disk_io("write", block);
+    }
+    // Flush and evict the layout too, or the whole first pass of reads would
+    // be served from memory.
+    file.sync_data().context("flush scratch file")?;
+    drop_cached_pages(file);
+    let mut pacer = Pacer::new(op_interval(spec.rate, block));
+    let mut writes_since_sync = 0u32;
+    let mut op = 0u64;
+    while Instant::now() < end && !cancel.is_cancelled() {
+        pacer.wait(cancel);
+        let idx = op % blocks;
+        if idx == 0 && op > 0 {
+            drop_cached_pages(file);
+        }
+        let offset = idx * block as u64;
+        if op_is_read(op, spec.read_percent) {
+            file.read_exact_at(&mut buf, offset)
+                .context("read scratch file")?;
+            mtr.record_disk_io("read", block);
+        } else {
+            file.write_all_at(&buf, offset)
+                .context("write scratch file")?;
+            mtr.record_disk_io("write", block);
+            writes_since_sync += 1;
+            if spec.fsync_every > 0 && writes_since_sync >= spec.fsync_every {
+                file.sync_data().context("fsync scratch file")?;
+                mtr.disk_io_fsyncs_total.inc();
+                writes_since_sync = 0;
+            }
+        }
+        op += 1;
+    }
+    Ok(())
+}
+
+/// Spaces operation starts `interval` apart, or not at all when unpaced.
+struct Pacer {
+    interval: Option<Duration>,
+    next_op: Instant,
+}
+
+impl Pacer {
+    fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            next_op: Instant::now(),
+        }
+    }
+
+    /// Sleeps until the next operation is due.
+    fn wait(&mut self, cancel: &CancellationToken) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        sleep_cancellable(
+            self.next_op.saturating_duration_since(Instant::now()),
+            cancel,
+        );
+        // After a stall, pace from now instead of bursting to catch up.
+        let now = Instant::now();
+        if now.saturating_duration_since(self.next_op) > MAX_PACING_LAG {
+            self.next_op = now;
+        }
+        self.next_op += interval;
+    }
+}
+
+/// Evicts the file's clean pages from the page cache so reads reach the device.
+fn drop_cached_pages(file: &File) {
+    let _ = rustix::fs::fadvise(file, 0, None, rustix::fs::Advice::DontNeed);
+}
+
+/// Whether operation `op` is a read, spreading `read_percent` reads evenly
+/// over every hundred operations.
+#[must_use]
+pub fn op_is_read(op: u64, read_percent: u32) -> bool {
+    let pct = u64::from(read_percent.min(100));
+    (op + 1) * pct / 100 != op * pct / 100
+}
+
+/// Time between operation starts for `rate`, or `None` when unpaced.
+#[must_use]
+pub fn op_interval(rate: IoRate, block_bytes: usize) -> Option<Duration> {
+    match rate {
+        IoRate::Unlimited => None,
+        IoRate::Throughput { mb_per_second } => {
+            let bytes_per_second = u64::from(mb_per_second.max(1)) * MIB;
+            let nanos = block_bytes as u64 * 1_000_000_000 / bytes_per_second;
+            Some(Duration::from_nanos(nanos))
+        }
+        IoRate::Iops { ops_per_second } => Some(Duration::from_secs(1) / ops_per_second.max(1)),
+    }
+}
+
+/// Rejects a directory the hog could not place its scratch file in.
+pub fn check_dir(dir: &str) -> AnyResult<()> {
+    if dir.trim().is_empty() {
+        return Err(anyhow!("dir is empty"));
+    }
+    let meta = std::fs::metadata(dir).with_context(|| format!("stat {dir}"))?;
+    if !meta.is_dir() {
+        return Err(anyhow!("{dir} is not a directory"));
+    }
+    Ok(())
+}
+
+/// Lays out `files` scratch files, then reads through all of them over and
+/// over. Every read pulls its pages into the page cache, pushing out the pages
+/// of other workloads once the files outgrow the memory left for caching.
+pub async fn page_cache_pressure_load(
+    experiment_id: String,
+    spec: PageCachePressureSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let paths: Vec<PathBuf> = (0..spec.files)
+        .map(|i| {
+            scratch_path(
+                Path::new(&spec.dir),
+                &format!("page-cache-{i}"),
+                &experiment_id,
+            )
+        })
+        .collect();
+    let worker_mtr = mtr.clone();
+    let res = tokio::task::spawn_blocking(move || {
+        page_cache_worker(&paths, &spec, end, &cancel, &worker_mtr)
+    })
+    .await
+    .context("join page cache worker")
+    .and_then(|r| r);
+    mtr.page_cache_pressure_file_bytes.set(0);
+    res
+}
+
+fn page_cache_worker(
+    paths: &[PathBuf],
+    spec: &PageCachePressureSpec,
+    end: Instant,
+    cancel: &CancellationToken,
+    mtr: &Metrics,
+) -> AnyResult<()> {
+    // Files are whole MiB, so every block is full.
+    let blocks = u64::from(spec.file_size_mb) * MIB / PAGE_CACHE_BLOCK as u64;
+    let mut buf = vec![0x5au8; PAGE_CACHE_BLOCK];
+    let mut scratch = Vec::with_capacity(paths.len());
+    let mut laid_out = 0u64;
+    for path in paths {
+        let file = ScratchFile::create(path)?;
+        for i in 0..blocks {
+            if Instant::now() >= end || cancel.is_cancelled() {
+                return Ok(());
+            }
+            file.file
+                .write_all_at(&buf, i * PAGE_CACHE_BLOCK as u64)
+                .context("lay out page cache file")?;
+            laid_out += PAGE_CACHE_BLOCK as u64;
+            mtr.page_cache_pressure_file_bytes
+                .set(i64::try_from(laid_out).unwrap_or(i64::MAX));
+        }
+        // Written back pages are clean, so they are evicted like any other
+        // cached page instead of waiting on writeback.
+        file.file.sync_data().context("sync page cache file")?;
+        scratch.push(file);
+    }
+    let mut pacer = Pacer::new(op_interval(spec.rate, PAGE_CACHE_BLOCK));
+    loop {
+        for file in &scratch {
+            for i in 0..blocks {
+                if Instant::now() >= end || cancel.is_cancelled() {
+                    return Ok(());
+                }
+                pacer.wait(cancel);
+                file.file
+                    .read_exact_at(&mut buf, i * PAGE_CACHE_BLOCK as u64)
+                    .context("read page cache file")?;
+                mtr.page_cache_pressure_read_bytes_total
+                    .inc_by(PAGE_CACHE_BLOCK as u64);
+            }
+        }
+        mtr.page_cache_pressure_passes_total.inc();
+    }
+}
+
+pub async fn disk_fill_load(
+    experiment_id: String,
+    spec: DiskFillSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let end = tokio::time::Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let path = scratch_path(Path::new(&spec.path), "disk-fill", &experiment_id);
+    let fill_cancel = cancel.clone();
+    let fill_mtr = mtr.clone();
+    let filled = tokio::task::spawn_blocking(move || {
+        let target = fill_target_bytes(spec.amount, free_bytes(&spec.path)?);
+        let ballast = ScratchFile::create(&path)?;
+        fill_file(&ballast.file, target, &fill_cancel, &fill_mtr)?;
+        Ok::<_, anyhow::Error>(ballast)
+    })
+    .await
+    .context("join disk fill worker")
+    .and_then(|r| r);
+    let res = match filled {
+        Ok(ballast) => {
+            tokio::select! {
+                () = tokio::time::sleep_until(end) => {}
+                () = cancel.cancelled() => {}
+            }
+            tokio::task::spawn_blocking(move || drop(ballast))
+                .await
+                .context("remove ballast")
+        }
+        Err(e) => Err(e),
+    };
+    mtr.disk_fill_bytes.set(0);
+    res
+}
+
+/// Bytes of ballast `amount` asks for given `free` bytes available.
+#[must_use]
+pub fn fill_target_bytes(amount: FillAmount, free: u64) -> u64 {
+    match amount {
+        FillAmount::Size { size_mb } => size_mb.saturating_mul(MIB),
+        FillAmount::FreePercent { percent } => {
+            let bytes = u128::from(free) * u128::from(percent.min(100)) / 100;
+            u64::try_from(bytes).unwrap_or(u64::MAX)
+        }
+    }
+}
+
+/// Space available to unprivileged writers on the filesystem holding `path`.
+pub fn free_bytes(path: &str) -> AnyResult<u64> {
+    let st = rustix::fs::statvfs(path).with_context(|| format!("statvfs {path}"))?;
+    Ok(st.f_bavail.saturating_mul(st.f_frsize))
+}
+
+/// Allocates `target` bytes of real blocks in `file`, falling back to writing
+/// zeros where the filesystem cannot preallocate.
+fn fill_file(file: &File, target: u64, cancel: &CancellationToken, mtr: &Metrics) -> AnyResult<()> {
+    let mut zeros = Vec::new();
+    let mut filled = 0u64;
+    while filled < target {
+        if cancel.is_cancelled() {
+            return Ok(());
+        }
+        let len = (target - filled).min(FILL_CHUNK);
+        match rustix::fs::fallocate(file, rustix::fs::FallocateFlags::empty(), filled, len) {
+            Ok(()) => {}
+            Err(rustix::io::Errno::OPNOTSUPP) => {
+                let len = usize::try_from(len).unwrap_or(usize::MAX);
+                zeros.resize(len, 0u8);
+                file.write_all_at(&zeros, filled).context("write ballast")?;
+            }
+            Err(e) => return Err(anyhow!(e).context(format!("allocate {len} bytes of ballast"))),
+        }
+        filled += len;
+        mtr.disk_fill_bytes
+            .set(i64::try_from(filled).unwrap_or(i64::MAX));
+    }
+    file.sync_all().context("sync ballast")
+}
@@ -0,0 +1,307 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use anyhow::{bail, Context, Result as AnyResult};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tokio::task::JoinSet;
+use tokio::time::{sleep, sleep_until, timeout, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::domain::{DnsFaultAction, DnsFaultRule, LoadController};
+use crate::lib_net::roll_percent;
+use crate::metrics::Metrics;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+pub const RCODE_NOERROR: u8 = 0;
+pub const RCODE_SERVFAIL: u8 = 2;
+pub const RCODE_NXDOMAIN: u8 = 3;
+
+const HEADER_LEN: usize = 12;
+
+/// Longest encoded name RFC 1035 allows.
+const MAX_NAME_LEN: usize = 255;
+
+/// Largest UDP message accepted from clients or the upstream (EDNS-sized).
+const MAX_MESSAGE: usize = 4096;
+
+/// How long a forwarded query may wait for the upstream before SERVFAIL.
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Pause after a failed receive, so a persistent error cannot spin.
+const RECV_BACKOFF: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug)]
+pub struct DnsFaultSpec {
+    pub listen: String,
+    pub upstream: String,
+    pub rules: Vec<DnsFaultRule>,
+}
+
+/// The single question of a query, with `name` lowercased and without the
+/// trailing dot; `end` is the offset just past the question section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Question {
+    pub name: String,
+    pub qtype: u16,
+    pub end: usize,
+}
+
+/// Serves DNS over UDP only; clients that retry over TCP after a truncated
+/// upstream answer will fail, which the experiment surfaces like any outage.
+pub async fn dns_fault_load(
+    experiment_id: String,
+    spec: DnsFaultSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    ctrl: LoadController,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let upstream = tokio::net::lookup_host(&spec.upstream)
+        .await
+        .with_context(|| format!("resolve {}", spec.upstream))?
+        .next()
+        .with_context(|| format!("{} resolved to no address", spec.upstream))?;
+    let sock = Arc::new(
+        UdpSocket::bind(&spec.listen)
+            .await
+            .with_context(|| format!("bind {}", spec.listen))?,
+    );
+    let local = sock.local_addr().context("responder local address")?;
+    ctrl.set_listen_addr(&experiment_id, local.to_string());
+    let rules = Arc::new(spec.rules);
+    ctrl.mark_running(&experiment_id);
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let mut queries = JoinSet::new();
+    let mut buf = vec![0u8; MAX_MESSAGE];
+    loop {
+        tokio::select! {
+            () = sleep_until(end) => break,
+            () = cancel.cancelled() => break,
+            received = sock.recv_from(&mut buf) => match received {
+                Ok((n, client)) => {
+                    queries.spawn(answer(
+                        sock.clone(),
+                        buf[..n].to_vec(),
+                        client,
+                        rules.clone(),
+                        upstream,
+                        mtr.clone(),
+                    ));
+                }
+                Err(e) => {
+                    warn!(error=%e, "dns responder receive failed");
+                    sleep(RECV_BACKOFF).await;
+                }
+            },
+            Some(_) = queries.join_next(), if !queries.is_empty() => {}
+        }
+    }
+    queries.shutdown().await;
+    Ok(())
+}
+
+async fn answer(
+    sock: Arc<UdpSocket>,
+    query: Vec<u8>,
+    client: SocketAddr,
+    rules: Arc<Vec<DnsFaultRule>>,
+    upstream: SocketAddr,
+    mtr: Metrics,
+) {
+    mtr.dns_fault_queries_total.inc();
+    if query.len() < HEADER_LEN {
+        return;
+    }
+    // Queries we cannot parse are still forwarded; only rules need the question.
+    let question = parse_question(&query).ok();
+    let rule = question.as_ref().and_then(|q| {
+        rules
+            .iter()
+            .find(|r| name_matches(&r.name, &q.name) && roll_percent(r.probability_percent))
+    });
+    if let Some(rule) = rule {
+        mtr.dns_fault_hits_total
+            .with_label_values(&[rule.name.as_str(), action_label(&rule.action)])
+            .inc();
+    }
+    let response = match (rule.map(|r| &r.action), &question) {
+        (Some(DnsFaultAction::Nxdomain), Some(q)) => {
+            build_response(&query, q, RCODE_NXDOMAIN, &[], 0)
+        }
+        (Some(DnsFaultAction::Servfail), Some(q)) => {
+            build_response(&query, q, RCODE_SERVFAIL, &[], 0)
+        }
+        (
+            Some(DnsFaultAction::Address {
+                addresses,
+                ttl_seconds,
+            }),
+            Some(q),
+        ) => {
+            let answers: Vec<IpAddr> = addresses
+                .iter()
+                .copied()
+                .filter(|addr| {
+                    matches!(
+                        (q.qtype, addr),
+                        (TYPE_A, IpAddr::V4(_)) | (TYPE_AAAA, IpAddr::V6(_))
+                    )
+                })
+                .collect();
+            build_response(&query, q, RCODE_NOERROR, &answers, *ttl_seconds)
+        }
+        (action, _) => {
+            if let Some(DnsFaultAction::Delay { delay_ms }) = action {
+                sleep(Duration::from_millis(u64::from(*delay_ms))).await;
+            }
+            match forward(&query, upstream).await {
+                Ok(response) => response,
+                Err(e) => {
+                    mtr.dns_fault_upstream_failures_total.inc();
+                    debug!(error=%format!("{e:#}"), "dns responder upstream failed");
+                    let Some(q) = &question else { return };
+                    build_response(&query, q, RCODE_SERVFAIL, &[], 0)
+                }
+            }
+        }
+    };
+    if let Err(e) = sock.send_to(&response, client).await {
+        debug!(error=%e, %client, "dns responder send failed");
+    }
+}
+
+/// Relays `query` to the upstream and returns the reply carrying its id.
+async fn forward(query: &[u8], upstream: SocketAddr) -> AnyResult<Vec<u8>> {
+    let bind: SocketAddr = if upstream.is_ipv4() {
+        "0.0.0.0:0".parse()?
+    } else {
+        "[::]:0".parse()?
+    };
+    let sock = UdpSocket::bind(bind)
+        .await
+        .context("bind upstream socket")?;
+    sock.connect(upstream)
+        .await
+        .with_context(|| format!("connect {upstream}"))?;
+    sock.send(query).await.context("send to upstream")?;
+    let mut buf = vec![0u8; MAX_MESSAGE];
+    timeout(FORWARD_TIMEOUT, async {
+        loop {
+            let n = sock.recv(&mut buf).await.context("receive from upstream")?;
+            if n >= HEADER_LEN && buf[..2] == query[..2] {
+                return Ok(buf[..n].to_vec());
+            }
+        }
+    })
+    .await
+    .with_context(|| format!("{upstream} did not answer"))?
+}
+
+/// Reads the one question a standard query carries.
+pub fn parse_question(msg: &[u8]) -> AnyResult<Question> {
+    if msg.len() < HEADER_LEN {
+        bail!("message shorter than a header");
+    }
+    if msg[2] & 0x80 != 0 {
+        bail!("message is a response");
+    }
+    if u16::from_be_bytes([msg[4], msg[5]]) != 1 {
+        bail!("expected exactly one question");
+    }
+    let mut labels = Vec::new();
+    let mut pos = HEADER_LEN;
+    loop {
+        let len = usize::from(*msg.get(pos).context("name runs past the message")?);
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        if len & 0xC0 != 0 {
+            bail!("compressed name in question");
+        }
+        let label = msg
+            .get(pos..pos + len)
+            .context("label runs past the message")?;
+        labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+        pos += len;
+        if pos - HEADER_LEN > MAX_NAME_LEN {
+            bail!("name longer than {MAX_NAME_LEN} bytes");
+        }
+    }
+    let fixed = msg.get(pos..pos + 4).context("question truncated")?;
+    Ok(Question {
+        name: labels.join("."),
+        qtype: u16::from_be_bytes([fixed[0], fixed[1]]),
+        end: pos + 4,
+    })
+}
+
+/// Answer to `query` with `rcode` and one IN record per address; the
+/// question is echoed and additional records such as EDNS are dropped.
+#[must_use]
+pub fn build_response(
+    query: &[u8],
+    question: &Question,
+    rcode: u8,
+    addresses: &[IpAddr],
+    ttl: u32,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(question.end + addresses.len() * 28);
+    out.extend_from_slice(&query[..2]);
+    // QR set; opcode and RD copied from the query.
+    out.push(0x80 | (query[2] & 0x79));
+    // RA set.
+    out.push(0x80 | (rcode & 0x0F));
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(
+        &u16::try_from(addresses.len())
+            .unwrap_or(u16::MAX)
+            .to_be_bytes(),
+    );
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    out.extend_from_slice(&query[HEADER_LEN..question.end]);
+    for addr in addresses {
+        // Pointer back to the question name at offset 12.
+        out.extend_from_slice(&[0xC0, 0x0C]);
+        let (rtype, rdata) = match addr {
+            IpAddr::V4(v4) => (TYPE_A, v4.octets().to_vec()),
+            IpAddr::V6(v6) => (TYPE_AAAA, v6.octets().to_vec()),
+        };
+        out.extend_from_slice(&rtype.to_be_bytes());
+        out.extend_from_slice(&CLASS_IN.to_be_bytes());
+        out.extend_from_slice(&ttl.to_be_bytes());
+        out.extend_from_slice(&u16::try_from(rdata.len()).unwrap_or(0).to_be_bytes());
+        out.extend_from_slice(&rdata);
+    }
+    out
+}
+
+/// Whether `name` (lowercase, no trailing dot) falls under `pattern`: an
+/// exact name, or `*.suffix` for any name strictly below `suffix`.
+#[must_use]
+pub fn name_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => name
+            .strip_suffix(suffix)
+            .is_some_and(|head| head.len() > 1 && head.ends_with('.')),
+        None => name == pattern,
+    }
+}
+
+fn action_label(action: &DnsFaultAction) -> &'static str {
+    match action {
+        DnsFaultAction::Nxdomain => "nxdomain",
+        DnsFaultAction::Servfail => "servfail",
+        DnsFaultAction::Address { .. } => "address",
+        DnsFaultAction::Delay { .. } => "delay",
+    }
+}
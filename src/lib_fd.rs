@@ -0,0 +1,144 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use anyhow::{anyhow, Context, Result as AnyResult};
+use rustix::io::Errno;
+use std::fs::File;
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixDatagram;
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::domain::{FdResource, FdTarget};
+use crate::metrics::Metrics;
+
+/// Descriptors opened between cancellation checks and gauge updates.
+const OPEN_BATCH: u64 = 256;
+
+/// Descriptors left free below `RLIMIT_NOFILE` so the agent can keep
+/// accepting connections and reading `/proc` while the hog is at its cap.
+pub const FD_RESERVE: u64 = 64;
+
+#[derive(Clone, Debug)]
+pub struct FdLoadSpec {
+    pub target: FdTarget,
+    pub resource: FdResource,
+}
+
+pub async fn fd_load(
+    _experiment_id: String,
+    spec: FdLoadSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let open_cancel = cancel.clone();
+    let open_mtr = mtr.clone();
+    let opened = tokio::task::spawn_blocking(move || {
+        let target = fd_target_count(spec.target, nofile_limit());
+        let open = open_fd_count()?;
+        open_fds(
+            spec.resource,
+            target.saturating_sub(open),
+            &open_cancel,
+            &open_mtr,
+        )
+    })
+    .await
+    .context("join fd opener")
+    .and_then(|r| r);
+    let res = match opened {
+        Ok(held) => {
+            tokio::select! {
+                () = sleep_until(end) => {}
+                () = cancel.cancelled() => {}
+            }
+            drop(held);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    };
+    mtr.fd_hog_held.set(0);
+    res
+}
+
+/// Opens up to `want` descriptors, stopping early at the process or system
+/// descriptor ceiling since reaching it is the point of the experiment.
+fn open_fds(
+    resource: FdResource,
+    want: u64,
+    cancel: &CancellationToken,
+    mtr: &Metrics,
+) -> AnyResult<Vec<OwnedFd>> {
+    let mut held = Vec::new();
+    for i in 0..want {
+        if i % OPEN_BATCH == 0 {
+            if cancel.is_cancelled() {
+                break;
+            }
+            mtr.fd_hog_held
+                .set(i64::try_from(held.len()).unwrap_or(i64::MAX));
+        }
+        match open_one(resource) {
+            Ok(fd) => held.push(fd),
+            Err(e) if matches!(Errno::from_io_error(&e), Some(Errno::MFILE | Errno::NFILE)) => {
+                break
+            }
+            Err(e) => return Err(anyhow!(e).context(format!("open {resource} descriptor"))),
+        }
+    }
+    mtr.fd_hog_held
+        .set(i64::try_from(held.len()).unwrap_or(i64::MAX));
+    Ok(held)
+}
+
+fn open_one(resource: FdResource) -> std::io::Result<OwnedFd> {
+    match resource {
+        FdResource::File => File::open("/dev/null").map(OwnedFd::from),
+        // Unbound datagram sockets cost a descriptor without claiming a port.
+        FdResource::Socket => UnixDatagram::unbound().map(OwnedFd::from),
+    }
+}
+
+/// Total open descriptors `target` asks the process to reach, never more
+/// than `limit` minus [`FD_RESERVE`].
+#[must_use]
+pub fn fd_target_count(target: FdTarget, limit: Option<u64>) -> u64 {
+    let usable = limit.map(|limit| limit.saturating_sub(FD_RESERVE));
+    match target {
+        FdTarget::Count { count } => usable.map_or(count, |usable| count.min(usable)),
+        FdTarget::LimitPercent { percent } => usable.map_or(0, |usable| {
+            let count = u128::from(usable) * u128::from(percent.min(100)) / 100;
+            u64::try_from(count).unwrap_or(u64::MAX)
+        }),
+    }
+}
+
+/// Soft `RLIMIT_NOFILE` of this process, `None` when unlimited.
+#[must_use]
+pub fn nofile_limit() -> Option<u64> {
+    rustix::process::getrlimit(rustix::process::Resource::Nofile).current
+}
+
+/// Descriptors this process may still open before eating into
+/// [`FD_RESERVE`]; `None` when `RLIMIT_NOFILE` is unlimited.
+pub fn fd_headroom() -> AnyResult<Option<u64>> {
+    let Some(limit) = nofile_limit() else {
+        return Ok(None);
+    };
+    Ok(Some(
+        limit
+            .saturating_sub(FD_RESERVE)
+            .saturating_sub(open_fd_count()?),
+    ))
+}
+
+/// Descriptors this process currently has open.
+pub fn open_fd_count() -> AnyResult<u64> {
+    let entries = std::fs::read_dir("/proc/self/fd").context("read /proc/self/fd")?;
+    // The directory handle being iterated shows up in its own listing.
+    Ok((entries.count() as u64).saturating_sub(1))
+}
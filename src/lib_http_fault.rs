@@ -0,0 +1,318 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::ServerHandle;
+use actix_web::http::header::HeaderMap;
+use actix_web::http::StatusCode;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use anyhow::{anyhow, Context, Result as AnyResult};
+use bytes::Bytes;
+use rustix::net::{shutdown, sockopt, Shutdown};
+use std::any::Any;
+use std::net::SocketAddr;
+use std::os::fd::OwnedFd;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use tokio::sync::oneshot;
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::domain::{HttpFaultAction, HttpFaultRule, LoadController};
+use crate::lib_net::roll_percent;
+use crate::metrics::Metrics;
+
+/// Headers that describe one hop and must not be copied across the proxy.
+const HOP_HEADERS: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "host",
+    "content-length",
+];
+
+/// How long the proxy waits to connect to the upstream.
+const UPSTREAM_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Upper bound on one upstream exchange, so a stalled upstream cannot pin a
+/// proxy worker for the rest of the experiment.
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug)]
+pub struct HttpFaultSpec {
+    pub listen: String,
+    pub upstream: String,
+    pub rules: Vec<HttpFaultRule>,
+}
+
+struct ProxyState {
+    client: reqwest::Client,
+    upstream: String,
+    rules: Vec<HttpFaultRule>,
+    mtr: Metrics,
+}
+
+pub async fn http_fault_load(
+    experiment_id: String,
+    spec: HttpFaultSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    ctrl: LoadController,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .connect_timeout(UPSTREAM_CONNECT_TIMEOUT)
+        .timeout(UPSTREAM_TIMEOUT)
+        .build()
+        .context("build upstream client")?;
+    let state = web::Data::new(ProxyState {
+        client,
+        upstream: spec.upstream.trim_end_matches('/').to_string(),
+        rules: spec.rules,
+        mtr,
+    });
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let listen = spec.listen.clone();
+    // actix servers are not `Send`, so the proxy gets its own system on a
+    // blocking thread and hands back a handle to stop it.
+    let mut running = tokio::task::spawn_blocking(move || {
+        actix_web::rt::System::new().block_on(run_server(state, listen, ready_tx))
+    });
+    let handle = match ready_rx.await {
+        Ok(Ok((addr, handle))) => {
+            ctrl.set_listen_addr(&experiment_id, addr.to_string());
+            handle
+        }
+        Ok(Err(e)) => return Err(e.context(format!("bind {}", spec.listen))),
+        Err(_) => return Err(anyhow!("http fault proxy exited before binding")),
+    };
+    ctrl.mark_running(&experiment_id);
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    tokio::select! {
+        () = sleep_until(end) => {}
+        () = cancel.cancelled() => {}
+        res = &mut running => {
+            return Err(match res {
+                Ok(Ok(())) => anyhow!("http fault proxy exited early"),
+                Ok(Err(e)) => anyhow!(e).context("http fault proxy"),
+                Err(e) => anyhow!(e).context("join http fault proxy"),
+            });
+        }
+    }
+    handle.stop(false).await;
+    running
+        .await
+        .context("join http fault proxy")?
+        .context("http fault proxy")
+}
+
+async fn run_server(
+    state: web::Data<ProxyState>,
+    listen: String,
+    ready: oneshot::Sender<AnyResult<(SocketAddr, ServerHandle)>>,
+) -> std::io::Result<()> {
+    let bound = HttpServer::new(move || {
+        App::new()
+            .app_data(state.clone())
+            .default_service(web::to(proxy_request))
+    })
+    .on_connect(keep_socket)
+    .workers(1)
+    .disable_signals()
+    .shutdown_timeout(1)
+    .bind(&listen);
+    let server = match bound {
+        Ok(server) => server,
+        Err(e) => {
+            let _ = ready.send(Err(e.into()));
+            return Ok(());
+        }
+    };
+    let Some(addr) = server.addrs().first().copied() else {
+        let _ = ready.send(Err(anyhow!("no listen address")));
+        return Ok(());
+    };
+    let server = server.run();
+    if ready.send(Ok((addr, server.handle()))).is_err() {
+        server.handle().stop(false).await;
+    }
+    server.await
+}
+
+async fn proxy_request(
+    req: HttpRequest,
+    body: web::Bytes,
+    state: web::Data<ProxyState>,
+) -> HttpResponse {
+    state.mtr.http_fault_requests_total.inc();
+    let rule = state.rules.iter().find(|rule| {
+        rule_matches(rule, req.method().as_str(), req.path(), req.headers())
+            && roll_percent(rule.probability_percent)
+    });
+    let action = rule.map(|rule| {
+        state
+            .mtr
+            .http_fault_hits_total
+            .with_label_values(&[rule.name.as_str(), action_label(rule.action)])
+            .inc();
+        rule.action
+    });
+    match action {
+        Some(HttpFaultAction::Status { status }) => {
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            return HttpResponse::build(status).body("fault injected by chaos agent");
+        }
+        Some(HttpFaultAction::Abort) => {
+            reset_connection(&req);
+            return HttpResponse::InternalServerError().finish();
+        }
+        Some(HttpFaultAction::Delay { delay_ms }) => {
+            sleep(Duration::from_millis(u64::from(delay_ms))).await;
+        }
+        Some(HttpFaultAction::Truncate { .. }) | None => {}
+    }
+    let (status, headers, body) = match forward(&state, &req, body).await {
+        Ok(res) => res,
+        Err(e) => {
+            debug!(error=%format!("{e:#}"), "http fault proxy upstream failed");
+            return HttpResponse::BadGateway().body(format!("upstream failed: {e:#}"));
+        }
+    };
+    let mut res = HttpResponse::build(status);
+    for (name, value) in &headers {
+        if !HOP_HEADERS.contains(&name.as_str()) {
+            res.append_header((name.as_str(), value.as_bytes()));
+        }
+    }
+    if let Some(HttpFaultAction::Truncate { keep_bytes }) = action {
+        let keep = body.len().min(keep_bytes as usize);
+        return res.body(CutBody {
+            head: Some(body.slice(..keep)),
+        });
+    }
+    res.body(body)
+}
+
+/// Duplicate of the client socket, kept so `ABORT` can reset the connection
+/// before actix writes any part of the response.
+struct ClientSocket(OwnedFd);
+
+fn keep_socket(conn: &dyn Any, ext: &mut actix_web::dev::Extensions) {
+    if let Some(sock) = conn.downcast_ref::<actix_web::rt::net::TcpStream>() {
+        match rustix::io::dup(sock) {
+            Ok(fd) => {
+                ext.insert(ClientSocket(fd));
+            }
+            Err(e) => debug!(error=%e, "http fault proxy could not keep client socket"),
+        }
+    }
+}
+
+/// Shuts the client socket with a zero linger, so the client sees the
+/// connection reset instead of a status line; actix's own write then fails
+/// and it drops the connection.
+fn reset_connection(req: &HttpRequest) {
+    let Some(ClientSocket(fd)) = req.conn_data::<ClientSocket>() else {
+        return;
+    };
+    if let Err(e) = sockopt::set_socket_linger(fd, Some(Duration::ZERO))
+        .and_then(|()| shutdown(fd, Shutdown::Both))
+    {
+        debug!(error=%e, "http fault proxy could not reset client connection");
+    }
+}
+
+/// Replays `req` against the upstream and buffers the whole response.
+async fn forward(
+    state: &ProxyState,
+    req: &HttpRequest,
+    body: web::Bytes,
+) -> AnyResult<(StatusCode, reqwest::header::HeaderMap, Bytes)> {
+    let path = req
+        .uri()
+        .path_and_query()
+        .map_or("/", actix_web::http::uri::PathAndQuery::as_str);
+    let url = format!("{}{path}", state.upstream);
+    let method = reqwest::Method::from_bytes(req.method().as_str().as_bytes())
+        .context("unsupported method")?;
+    let mut upstream = state.client.request(method, &url).body(body);
+    for (name, value) in req.headers() {
+        if !HOP_HEADERS.contains(&name.as_str()) {
+            upstream = upstream.header(name.as_str(), value.as_bytes());
+        }
+    }
+    let res = upstream
+        .send()
+        .await
+        .with_context(|| format!("request {url}"))?;
+    let status = StatusCode::from_u16(res.status().as_u16()).context("upstream status")?;
+    let headers = res.headers().clone();
+    let body = res.bytes().await.context("read upstream body")?;
+    Ok((status, headers, body))
+}
+
+/// Whether `rule` selects a request; unset criteria match everything.
+#[must_use]
+pub fn rule_matches(rule: &HttpFaultRule, method: &str, path: &str, headers: &HeaderMap) -> bool {
+    let method_ok = rule
+        .method
+        .as_deref()
+        .is_none_or(|m| m.eq_ignore_ascii_case(method));
+    let path_ok = rule
+        .path_prefix
+        .as_deref()
+        .is_none_or(|prefix| path.starts_with(prefix));
+    let header_ok = rule.header.as_ref().is_none_or(|h| {
+        headers.get(h.name.as_str()).is_some_and(|v| {
+            h.value
+                .as_deref()
+                .is_none_or(|want| v.as_bytes() == want.as_bytes())
+        })
+    });
+    method_ok && path_ok && header_ok
+}
+
+fn action_label(action: HttpFaultAction) -> &'static str {
+    match action {
+        HttpFaultAction::Status { .. } => "status",
+        HttpFaultAction::Delay { .. } => "delay",
+        HttpFaultAction::Truncate { .. } => "truncate",
+        HttpFaultAction::Abort => "abort",
+    }
+}
+
+/// Body that yields `head` and then fails, so actix drops the connection
+/// mid-response instead of completing it.
+struct CutBody {
+    head: Option<Bytes>,
+}
+
+impl MessageBody for CutBody {
+    type Error = std::io::Error;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        _cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        match self.head.take() {
+            Some(head) if !head.is_empty() => Poll::Ready(Some(Ok(head))),
+            _ => Poll::Ready(Some(Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionAborted,
+                "fault injected by chaos agent",
+            )))),
+        }
+    }
+}
@@ -1,26 +1,387 @@
 #![forbid(unsafe_code)]
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
 
-use anyhow::Result as AnyResult;
-use tokio::time::{sleep, Duration};
+use anyhow::{anyhow, Context, Result as AnyResult};
+use std::fs::File;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::domain::{LoadController, MemoryBacking, MemoryGrowth, TouchPattern};
+use crate::lib_disk::{scratch_path, ScratchFile};
+use crate::metrics::Metrics;
+
+/// tmpfs mount the `SHM` backing places its file on.
+pub const SHM_DIR: &str = "/dev/shm";
+
+/// How often the hog grows towards its target and samples the achieved RSS.
+const SAMPLE_TICK: Duration = Duration::from_millis(250);
+
+/// Pages touched between cancellation checks while faulting in a buffer.
+const TOUCH_BATCH_PAGES: usize = 4096;
+
+const MIB: usize = 1024 * 1024;
+
+/// Heap ballast is allocated in chunks this large. That is above glibc's
+/// largest mmap threshold, so each chunk is a fresh zero-filled mapping the
+/// kernel backs page by page only as the touch pattern writes it.
+pub const HEAP_CHUNK: usize = 64 * MIB;
+
+#[derive(Clone, Debug)]
+pub struct MemoryLoadSpec {
+    pub memory_mb: u32,
+    pub touch: TouchPattern,
+    /// Seconds between passes re-touching every page; 0 touches only once.
+    pub retouch_seconds: u32,
+    pub growth: MemoryGrowth,
+    pub backing: MemoryBacking,
+}
 
 pub async fn memory_load(
-    _experiment_id: String,
-    memory_mb: u32,
+    experiment_id: String,
+    spec: MemoryLoadSpec,
     duration_seconds: u32,
+    mtr: Metrics,
+    ctrl: LoadController,
+    cancel: CancellationToken,
 ) -> AnyResult<()> {
-    let bytes = (memory_mb as usize).saturating_mul(1024 * 1024);
-    let mut buf = Vec::<u8>::new();
-    if bytes > 0 {
-        buf.resize(bytes, 0u8);
+    let memory_mb = spec.memory_mb;
+    let bytes = (memory_mb as usize).saturating_mul(MIB);
+    let mut ballast = match &spec.backing {
+        MemoryBacking::Anonymous | MemoryBacking::Mlocked => Ballast::heap(bytes, memory_mb)?,
+        MemoryBacking::Shm => Ballast::file(Path::new(SHM_DIR), &experiment_id)?,
+        MemoryBacking::File { dir } => Ballast::file(Path::new(dir), &experiment_id)?,
+    };
+    let lock = match spec.backing {
+        MemoryBacking::Mlocked => Some(MemoryLock::acquire()?),
+        _ => None,
+    };
+    let page = rustix::param::page_size();
+    ctrl.mark_running(&experiment_id);
+    let started = Instant::now();
+    let end = started + Duration::from_secs(u64::from(duration_seconds));
+    let retouch = Duration::from_secs(u64::from(spec.retouch_seconds));
+    let mut pass = 0u8;
+    let mut next_touch = (!retouch.is_zero()).then(|| started + retouch);
+    let res = loop {
+        if Instant::now() >= end || cancel.is_cancelled() {
+            break Ok(());
+        }
+        let target = growth_target_bytes(spec.growth, bytes, started.elapsed());
+        let retouch_due = next_touch.is_some_and(|at| Instant::now() >= at);
+        if target > ballast.len() || retouch_due {
+            let touch_cancel = cancel.clone();
+            let mut held = std::mem::take(&mut ballast);
+            let touched = tokio::task::spawn_blocking(move || {
+                let mut res = held.grow(target, page, spec.touch, pass, &touch_cancel);
+                if retouch_due && res.is_ok() {
+                    res = held.retouch(page, spec.touch, pass, &touch_cancel);
+                }
+                (held, res)
+            })
+            .await;
+            let res = match touched {
+                Ok((held, res)) => {
+                    ballast = held;
+                    res
+                }
+                Err(e) => Err(anyhow!(e).context("touch pages")),
+            };
+            if let Err(e) = res {
+                break Err(e);
+            }
+            if retouch_due {
+                pass = pass.wrapping_add(1);
+                next_touch = Some(Instant::now() + retouch);
+            }
+            mtr.memory_hog_allocated_bytes
+                .set(i64::try_from(ballast.len()).unwrap_or(i64::MAX));
+        }
+        match resident_bytes() {
+            Ok(rss) => {
+                let rss = rss + ballast.file_bytes() as u64;
+                mtr.memory_hog_resident_bytes
+                    .set(i64::try_from(rss).unwrap_or(i64::MAX));
+                ctrl.set_resident_bytes(&experiment_id, rss);
+            }
+            Err(e) => break Err(e),
+        }
+        let wake = (Instant::now() + SAMPLE_TICK).min(end);
+        tokio::select! {
+            () = sleep_until(wake) => {}
+            () = cancel.cancelled() => {}
+        }
+    };
+    drop(lock);
+    // Freeing or unlinking gigabytes takes a while; keep it off the runtime.
+    let released = tokio::task::spawn_blocking(move || drop(ballast))
+        .await
+        .context("release memory ballast");
+    mtr.memory_hog_allocated_bytes.set(0);
+    mtr.memory_hog_resident_bytes.set(0);
+    res.and(released)
+}
+
+/// The pages the memory hog holds, kept where [`MemoryBacking`] asks.
+enum Ballast {
+    /// Lazily backed heap chunks, used for `ANONYMOUS` and `MLOCKED`; `len`
+    /// bytes of them have been touched.
+    Heap { chunks: Vec<Box<[u8]>>, len: usize },
+    /// Scratch file whose pages live on tmpfs or in the page cache. The agent
+    /// maps no memory itself, so pages are faulted in with positioned writes.
+    File { scratch: ScratchFile, len: usize },
+}
+
+impl Default for Ballast {
+    fn default() -> Self {
+        Self::Heap {
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl Ballast {
+    /// Maps the ceiling up front so an impossible size fails immediately;
+    /// growth then only touches more of it.
+    fn heap(bytes: usize, memory_mb: u32) -> AnyResult<Self> {
+        let chunks = zeroed_chunks(bytes).with_context(|| format!("allocate {memory_mb} MiB"))?;
+        Ok(Self::Heap { chunks, len: 0 })
+    }
+
+    fn file(dir: &Path, experiment_id: &str) -> AnyResult<Self> {
+        let scratch = ScratchFile::create(&scratch_path(dir, "memory", experiment_id))?;
+        Ok(Self::File { scratch, len: 0 })
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Heap { len, .. } | Self::File { len, .. } => *len,
+        }
+    }
+
+    /// Bytes held in tmpfs or the page cache. The agent never maps those
+    /// pages, so `VmRSS` misses them and the footprint counts them separately.
+    fn file_bytes(&self) -> usize {
+        match self {
+            Self::Heap { .. } => 0,
+            Self::File { len, .. } => *len,
+        }
+    }
+
+    /// Extends the ballast to `target` bytes, touching only the new pages.
+    fn grow(
+        &mut self,
+        target: usize,
+        page: usize,
+        pattern: TouchPattern,
+        pass: u8,
+        cancel: &CancellationToken,
+    ) -> AnyResult<()> {
+        let old_len = self.len();
+        if target <= old_len {
+            return Ok(());
+        }
+        match self {
+            Self::Heap { chunks, len } => {
+                *len = target;
+                touch_pages(chunks, old_len..target, page, pattern, pass, cancel);
+            }
+            Self::File { scratch, len } => {
+                scratch
+                    .file
+                    .set_len(target as u64)
+                    .context("extend memory ballast file")?;
+                *len = target;
+                let new = old_len..target;
+                touch_file_pages(&scratch.file, new, page, pattern, pass, cancel)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn retouch(
+        &mut self,
+        page: usize,
+        pattern: TouchPattern,
+        pass: u8,
+        cancel: &CancellationToken,
+    ) -> AnyResult<()> {
+        match self {
+            Self::Heap { chunks, len } => touch_pages(chunks, 0..*len, page, pattern, pass, cancel),
+            Self::File { scratch, len } => {
+                touch_file_pages(&scratch.file, 0..*len, page, pattern, pass, cancel)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Locks the agent's pages in RAM as they fault in, until dropped. Locking
+/// just the ballast would need `mlock` on a raw pointer, which this crate's
+/// `forbid(unsafe_code)` rules out, so every mapping the agent holds when the
+/// lock is taken is locked. Validation keeps that within `RLIMIT_MEMLOCK`.
+struct MemoryLock;
+
+impl MemoryLock {
+    fn acquire() -> AnyResult<Self> {
+        use rustix::mm::MlockAllFlags;
+        rustix::mm::mlockall(MlockAllFlags::CURRENT | MlockAllFlags::ONFAULT)
+            .context("mlockall (needs CAP_IPC_LOCK or a large enough RLIMIT_MEMLOCK)")?;
+        Ok(Self)
+    }
+}
+
+impl Drop for MemoryLock {
+    fn drop(&mut self) {
+        if let Err(e) = rustix::mm::munlockall() {
+            tracing::warn!(error=%e, "munlockall");
+        }
+    }
+}
+
+/// Bytes the hog should hold `elapsed` into the experiment, capped at `total`
+/// and rounded down to whole MiB.
+#[must_use]
+pub fn growth_target_bytes(growth: MemoryGrowth, total: usize, elapsed: Duration) -> usize {
+    let total_mb = total / MIB;
+    let millis = elapsed.as_millis();
+    let mb = match growth {
+        MemoryGrowth::Instant => u128::MAX,
+        MemoryGrowth::Ramp { ramp_seconds } => {
+            let ramp_millis = u128::from(ramp_seconds) * 1000;
+            if millis >= ramp_millis {
+                u128::MAX
+            } else {
+                total_mb as u128 * millis / ramp_millis
+            }
+        }
+        MemoryGrowth::Leak { rate_mb_per_second } => u128::from(rate_mb_per_second) * millis / 1000,
+        MemoryGrowth::Step {
+            step_mb,
+            step_seconds,
+        } => {
+            let steps = u128::from(elapsed.as_secs() / u64::from(step_seconds.max(1))) + 1;
+            u128::from(step_mb) * steps
+        }
+    };
+    usize::try_from(mb)
+        .unwrap_or(usize::MAX)
+        .min(total_mb)
+        .saturating_mul(MIB)
+        .min(total)
+}
+
+/// Zeroed [`HEAP_CHUNK`]-sized chunks covering `bytes`, none of them backed
+/// by memory until written.
+pub fn zeroed_chunks(bytes: usize) -> AnyResult<Vec<Box<[u8]>>> {
+    let count = bytes.div_ceil(HEAP_CHUNK);
+    // `vec![0; n]` aborts the process when it cannot allocate, so probe for
+    // the address space fallibly first.
+    let mut probe = Vec::<u8>::new();
+    probe.try_reserve_exact(count.saturating_mul(HEAP_CHUNK))?;
+    drop(probe);
+    Ok((0..count)
+        .map(|_| vec![0u8; HEAP_CHUNK].into_boxed_slice())
+        .collect())
+}
+
+/// Writes one non-zero byte into every page of `range`, an offset into the
+/// concatenated `chunks`, so the kernel has to back it with real memory.
+/// Returns early if `cancel` fires.
+pub fn touch_pages(
+    chunks: &mut [Box<[u8]>],
+    range: Range<usize>,
+    page: usize,
+    pattern: TouchPattern,
+    pass: u8,
+    cancel: &CancellationToken,
+) {
+    let pages = range.len().div_ceil(page.max(1));
+    let value = touch_value(pass);
+    for (i, idx) in page_order(pages, pattern).enumerate() {
+        if i % TOUCH_BATCH_PAGES == 0 && cancel.is_cancelled() {
+            return;
+        }
+        let at = range.start + idx * page;
+        chunks[at / HEAP_CHUNK][at % HEAP_CHUNK] = value;
     }
-    let end = tokio::time::Instant::now() + Duration::from_secs(u64::from(duration_seconds));
-    while tokio::time::Instant::now() < end {
-        if !buf.is_empty() {
-            buf[0] = buf[0].wrapping_add(1);
+}
+
+/// [`touch_pages`] for the `range` of a ballast file, one write per page.
+fn touch_file_pages(
+    file: &File,
+    range: Range<usize>,
+    page: usize,
+    pattern: TouchPattern,
+    pass: u8,
+    cancel: &CancellationToken,
+) -> AnyResult<()> {
+    let pages = range.len().div_ceil(page.max(1));
+    let value = [touch_value(pass)];
+    for (i, idx) in page_order(pages, pattern).enumerate() {
+        if i % TOUCH_BATCH_PAGES == 0 && cancel.is_cancelled() {
+            return Ok(());
         }
-        sleep(Duration::from_millis(50)).await;
+        file.write_all_at(&value, (range.start + idx * page) as u64)
+            .context("touch memory ballast file")?;
     }
     Ok(())
 }
+
+/// Non-zero byte written on pass `pass`, so every pass dirties the page again.
+fn touch_value(pass: u8) -> u8 {
+    pass.wrapping_add(1) | 1
+}
+
+/// Page indices in the order `pattern` touches them.
+fn page_order(pages: usize, pattern: TouchPattern) -> impl Iterator<Item = usize> {
+    let stride = scatter_stride(pages);
+    let mut scattered = 0;
+    (0..pages).map(move |i| match pattern {
+        TouchPattern::Sequential => i,
+        TouchPattern::Reverse => pages - 1 - i,
+        TouchPattern::Scattered => {
+            let idx = scattered;
+            scattered = (scattered + stride) % pages;
+            idx
+        }
+    })
+}
+
+/// Stride coprime with `pages`, so stepping by it modulo `pages` visits every page once
+/// in an order that defeats sequential prefetching.
+fn scatter_stride(pages: usize) -> usize {
+    if pages < 3 {
+        return 1;
+    }
+    let mut stride = pages * 5 / 8;
+    while gcd(stride, pages) != 1 {
+        stride += 1;
+    }
+    stride
+}
+
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Resident set size of this process, from `VmRSS` in `/proc/self/status`.
+pub fn resident_bytes() -> AnyResult<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").context("read /proc/self/status")?;
+    parse_vm_rss(&status).ok_or_else(|| anyhow!("VmRSS missing from /proc/self/status"))
+}
+
+#[must_use]
+pub fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
@@ -0,0 +1,245 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use anyhow::{Context, Result as AnyResult};
+use bytes::Bytes;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::domain::{LoadController, TcpFaults};
+use crate::metrics::Metrics;
+
+/// Largest chunk read from one side before it is queued for the other.
+const CHUNK_BYTES: usize = 16 * 1024;
+
+/// Chunks in flight per direction while they wait out the injected latency.
+const DELAY_QUEUE: usize = 64;
+
+/// Client connections relayed at once, each holding two descriptors; further
+/// connections are closed on accept so a flood cannot exhaust the agent's
+/// descriptors.
+pub const MAX_CONNECTIONS: usize = 256;
+
+/// Pause after a failed accept, so a persistent error (e.g. EMFILE) cannot spin.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug)]
+pub struct TcpProxySpec {
+    pub listen: String,
+    pub upstream: String,
+    pub faults: TcpFaults,
+}
+
+pub async fn tcp_proxy_load(
+    experiment_id: String,
+    spec: TcpProxySpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    ctrl: LoadController,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let listener = TcpListener::bind(&spec.listen)
+        .await
+        .with_context(|| format!("bind {}", spec.listen))?;
+    let local = listener.local_addr().context("proxy local address")?;
+    ctrl.set_listen_addr(&experiment_id, local.to_string());
+    ctrl.mark_running(&experiment_id);
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let spec = Arc::new(spec);
+    let conns_cancel = cancel.child_token();
+    let mut conns = JoinSet::new();
+    loop {
+        tokio::select! {
+            () = sleep_until(end) => break,
+            () = cancel.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((_, peer)) if conns.len() >= MAX_CONNECTIONS => {
+                    debug!(%peer, "proxy connection limit reached, closing");
+                    mtr.tcp_proxy_faults_total
+                        .with_label_values(&["connection_limit"])
+                        .inc();
+                }
+                Ok((client, _)) => {
+                    conns.spawn(handle_conn(client, spec.clone(), mtr.clone(), conns_cancel.clone()));
+                }
+                Err(e) => {
+                    warn!(error=%e, "proxy accept failed");
+                    sleep(ACCEPT_BACKOFF).await;
+                }
+            },
+            Some(_) = conns.join_next(), if !conns.is_empty() => {}
+        }
+    }
+    drop(listener);
+    conns_cancel.cancel();
+    while conns.join_next().await.is_some() {}
+    Ok(())
+}
+
+async fn handle_conn(
+    client: TcpStream,
+    spec: Arc<TcpProxySpec>,
+    mtr: Metrics,
+    cancel: CancellationToken,
+) {
+    mtr.tcp_proxy_connections_total.inc();
+    mtr.tcp_proxy_active_connections.inc();
+    tokio::select! {
+        res = proxy_conn(client, &spec, &mtr) => {
+            if let Err(e) = res {
+                debug!(error=%format!("{e:#}"), "proxy connection ended");
+            }
+        }
+        () = cancel.cancelled() => {}
+    }
+    mtr.tcp_proxy_active_connections.dec();
+}
+
+async fn proxy_conn(mut client: TcpStream, spec: &TcpProxySpec, mtr: &Metrics) -> AnyResult<()> {
+    let faults = spec.faults;
+    if roll_percent(faults.reset_percent) {
+        mtr.tcp_proxy_faults_total
+            .with_label_values(&["reset"])
+            .inc();
+        // Dropping a zero-linger socket sends RST instead of FIN.
+        client.set_zero_linger().context("set zero linger")?;
+        return Ok(());
+    }
+    if roll_percent(faults.blackhole_percent) {
+        mtr.tcp_proxy_faults_total
+            .with_label_values(&["blackhole"])
+            .inc();
+        tokio::io::copy(&mut client, &mut tokio::io::sink())
+            .await
+            .context("blackhole client")?;
+        return Ok(());
+    }
+    let upstream = TcpStream::connect(&spec.upstream)
+        .await
+        .with_context(|| format!("connect {}", spec.upstream))?;
+    let _ = client.set_nodelay(true);
+    let _ = upstream.set_nodelay(true);
+    let (client_rd, client_wr) = client.into_split();
+    let (upstream_rd, upstream_wr) = upstream.into_split();
+    tokio::try_join!(
+        pipe(client_rd, upstream_wr, faults, "upstream", mtr),
+        pipe(upstream_rd, client_wr, faults, "downstream", mtr),
+    )?;
+    Ok(())
+}
+
+/// Copies one direction, holding each chunk for the injected latency and
+/// pacing writes to the bandwidth cap, then half-closes the writer.
+async fn pipe<R, W>(
+    mut rd: R,
+    mut wr: W,
+    faults: TcpFaults,
+    direction: &'static str,
+    mtr: &Metrics,
+) -> AnyResult<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let (tx, mut rx) = mpsc::channel::<(Instant, Bytes)>(DELAY_QUEUE);
+    let reader = async move {
+        let mut buf = vec![0u8; CHUNK_BYTES];
+        let mut last_due = Instant::now();
+        loop {
+            let n = rd.read(&mut buf).await.context("read")?;
+            if n == 0 {
+                return Ok::<_, anyhow::Error>(());
+            }
+            let delay = chunk_delay(faults.latency_ms, faults.jitter_ms);
+            if !delay.is_zero() {
+                mtr.tcp_proxy_faults_total
+                    .with_label_values(&["latency"])
+                    .inc();
+            }
+            // Never deliver before the previous chunk, so jitter cannot reorder bytes.
+            last_due = (Instant::now() + delay).max(last_due);
+            if tx
+                .send((last_due, Bytes::copy_from_slice(&buf[..n])))
+                .await
+                .is_err()
+            {
+                return Ok(());
+            }
+        }
+    };
+    let writer = async move {
+        let mut throttle = Throttle::new(faults.bandwidth_kb_per_second);
+        while let Some((due, chunk)) = rx.recv().await {
+            sleep_until(due).await;
+            if throttle.wait(chunk.len()).await {
+                mtr.tcp_proxy_faults_total
+                    .with_label_values(&["throttle"])
+                    .inc();
+            }
+            wr.write_all(&chunk).await.context("write")?;
+            mtr.tcp_proxy_bytes_total
+                .with_label_values(&[direction])
+                .inc_by(chunk.len() as u64);
+        }
+        wr.shutdown().await.context("shutdown")
+    };
+    tokio::try_join!(reader, writer)?;
+    Ok(())
+}
+
+/// Latency for one chunk: `latency_ms` shifted uniformly by up to `jitter_ms`
+/// either way, never below zero.
+#[must_use]
+pub fn chunk_delay(latency_ms: u32, jitter_ms: u32) -> Duration {
+    let jitter = if jitter_ms == 0 {
+        0
+    } else {
+        rand::random_range(-i64::from(jitter_ms)..=i64::from(jitter_ms))
+    };
+    let ms = (i64::from(latency_ms) + jitter).max(0);
+    Duration::from_millis(u64::try_from(ms).unwrap_or(0))
+}
+
+pub(crate) fn roll_percent(percent: u32) -> bool {
+    percent > 0 && rand::random_range(0..100) < percent
+}
+
+/// Paces writes to a byte rate: each write books its transfer time at the cap and the
+/// next write waits until the previous one would have finished.
+pub struct Throttle {
+    bytes_per_second: u64,
+    next_free: Instant,
+}
+
+impl Throttle {
+    #[must_use]
+    pub fn new(kb_per_second: u32) -> Self {
+        Self {
+            bytes_per_second: u64::from(kb_per_second) * 1024,
+            next_free: Instant::now(),
+        }
+    }
+
+    /// Waits until `bytes` may be sent; returns whether it had to wait.
+    pub async fn wait(&mut self, bytes: usize) -> bool {
+        if self.bytes_per_second == 0 {
+            return false;
+        }
+        let now = Instant::now();
+        let waited = self.next_free > now;
+        if waited {
+            sleep_until(self.next_free).await;
+        }
+        let cost = Duration::from_nanos(bytes as u64 * 1_000_000_000 / self.bytes_per_second);
+        self.next_free = self.next_free.max(now) + cost;
+        waited
+    }
+}
@@ -0,0 +1,216 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use anyhow::{anyhow, Context, Result as AnyResult};
+use parking_lot::{Condvar, Mutex};
+use rustix::io::Errno;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::domain::PidsMode;
+use crate::metrics::Metrics;
+
+/// Tasks left free in the agent's pids cgroup so it can keep serving requests.
+const PIDS_RESERVE: u64 = 32;
+
+/// Stack of an idle hog thread; it only ever waits on a condvar.
+const IDLE_THREAD_STACK: usize = 64 * 1024;
+
+/// How often the live count is refreshed while the hog holds its tasks.
+const LIVE_TICK: Duration = Duration::from_secs(1);
+
+/// Tasks spawned between cancellation checks and gauge updates.
+const SPAWN_BATCH: u64 = 64;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+#[derive(Clone, Debug)]
+pub struct PidsLoadSpec {
+    pub count: u32,
+    pub mode: PidsMode,
+}
+
+pub async fn pids_load(
+    _experiment_id: String,
+    spec: PidsLoadSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let spawn_cancel = cancel.clone();
+    let spawn_mtr = mtr.clone();
+    let spawned = tokio::task::spawn_blocking(move || {
+        let want = u64::from(spec.count).min(pids_headroom().unwrap_or(u64::MAX));
+        PidsHog::spawn(spec.mode, want, &spawn_cancel, &spawn_mtr)
+    })
+    .await
+    .context("join pids spawner")
+    .and_then(|r| r);
+    let res = match spawned {
+        Ok(mut hog) => {
+            while Instant::now() < end && !cancel.is_cancelled() {
+                mtr.pids_hog_live
+                    .set(i64::try_from(hog.live()).unwrap_or(i64::MAX));
+                tokio::select! {
+                    () = sleep_until((Instant::now() + LIVE_TICK).min(end)) => {}
+                    () = cancel.cancelled() => {}
+                }
+            }
+            tokio::task::spawn_blocking(move || drop(hog))
+                .await
+                .context("reap pids hog")
+        }
+        Err(e) => Err(e),
+    };
+    mtr.pids_hog_live.set(0);
+    res
+}
+
+/// Idle threads or children holding pid slots. Dropping it wakes and joins
+/// the threads and kills and reaps the children, whichever way the load ends.
+struct PidsHog {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    threads: Vec<thread::JoinHandle<()>>,
+    children: Vec<Child>,
+}
+
+impl PidsHog {
+    /// Spawns up to `want` tasks, stopping early when the kernel or cgroup
+    /// refuses more since that ceiling is what the experiment exercises.
+    fn spawn(
+        mode: PidsMode,
+        want: u64,
+        cancel: &CancellationToken,
+        mtr: &Metrics,
+    ) -> AnyResult<Self> {
+        let mut hog = Self {
+            stop: Arc::default(),
+            threads: Vec::new(),
+            children: Vec::new(),
+        };
+        for i in 0..want {
+            if i % SPAWN_BATCH == 0 {
+                if cancel.is_cancelled() {
+                    break;
+                }
+                mtr.pids_hog_live.set(i64::try_from(i).unwrap_or(i64::MAX));
+            }
+            let spawned = match mode {
+                PidsMode::Threads => hog.spawn_thread(i),
+                PidsMode::Processes => hog.spawn_child(),
+            };
+            match spawned {
+                Ok(()) => {}
+                Err(e) if Errno::from_io_error(&e) == Some(Errno::AGAIN) => break,
+                Err(e) => return Err(anyhow!(e).context(format!("spawn {mode} task {i}"))),
+            }
+        }
+        mtr.pids_hog_live
+            .set(i64::try_from(hog.live()).unwrap_or(i64::MAX));
+        Ok(hog)
+    }
+
+    fn spawn_thread(&mut self, i: u64) -> std::io::Result<()> {
+        let stop = self.stop.clone();
+        let handle = thread::Builder::new()
+            .name(format!("pids-hog-{i}"))
+            .stack_size(IDLE_THREAD_STACK)
+            .spawn(move || {
+                let (lock, cvar) = &*stop;
+                let mut stopped = lock.lock();
+                while !*stopped {
+                    cvar.wait(&mut stopped);
+                }
+            })?;
+        self.threads.push(handle);
+        Ok(())
+    }
+
+    fn spawn_child(&mut self) -> std::io::Result<()> {
+        // `cat` blocks on a pipe only the agent holds, so it exits on EOF even
+        // if the agent is killed before it can reap it.
+        let child = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        self.children.push(child);
+        Ok(())
+    }
+
+    /// Tasks still alive; children that exited on their own are reaped here.
+    fn live(&mut self) -> usize {
+        self.children
+            .retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+        self.threads.len() + self.children.len()
+    }
+}
+
+impl Drop for PidsHog {
+    fn drop(&mut self) {
+        let (lock, cvar) = &*self.stop;
+        *lock.lock() = true;
+        cvar.notify_all();
+        for handle in self.threads.drain(..) {
+            let _ = handle.join();
+        }
+        for mut child in self.children.drain(..) {
+            drop(child.stdin.take());
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Tasks the agent's pids cgroup still admits after keeping a reserve for
+/// the agent itself, `None` when no `pids.max` applies.
+#[must_use]
+pub fn pids_headroom() -> Option<u64> {
+    let cgroup = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    let dir = pids_cgroup_dir(Path::new(CGROUP_ROOT), &cgroup)?;
+    let max = parse_pids_max(&std::fs::read_to_string(dir.join("pids.max")).ok()?)?;
+    let current: u64 = std::fs::read_to_string(dir.join("pids.current"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(max.saturating_sub(current).saturating_sub(PIDS_RESERVE))
+}
+
+/// Directory holding `pids.max` for this process: the v1 `pids` hierarchy
+/// when mounted, else the unified v2 one.
+#[must_use]
+pub fn pids_cgroup_dir(root: &Path, proc_cgroup: &str) -> Option<PathBuf> {
+    let mut v1 = None;
+    let mut v2 = None;
+    for line in proc_cgroup.lines() {
+        let mut parts = line.splitn(3, ':');
+        let (Some(_), Some(controllers), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let rel = path.trim_start_matches('/');
+        if controllers.split(',').any(|c| c == "pids") {
+            v1 = Some(root.join("pids").join(rel));
+        } else if controllers.is_empty() {
+            v2 = Some(root.join(rel));
+        }
+    }
+    [v1, v2]
+        .into_iter()
+        .flatten()
+        .find(|dir| dir.join("pids.max").exists())
+}
+
+/// `pids.max` contents as a number, `None` for `max` (unlimited).
+#[must_use]
+pub fn parse_pids_max(contents: &str) -> Option<u64> {
+    contents.trim().parse().ok()
+}
@@ -0,0 +1,143 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use anyhow::{bail, Context, Result as AnyResult};
+use rustix::io::Errno;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, sleep_until, timeout, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::domain::PortTarget;
+use crate::lib_fd::fd_headroom;
+use crate::metrics::Metrics;
+
+/// How long one connect may take before it counts as an error.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Pause after a connect error that may clear up, e.g. a refused connection.
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Connections opened between gauge updates.
+const GAUGE_BATCH: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct PortExhaustionSpec {
+    /// Loopback `ip:port` to connect to.
+    pub target: String,
+    pub ports: PortTarget,
+}
+
+/// Connects to `target` until the requested number of ephemeral ports is
+/// held, then keeps every connection open until the experiment ends. Running
+/// out of local addresses ends the ramp early: hitting that wall is what the
+/// experiment reproduces. It also stops short of the agent's
+/// [`FD_RESERVE`](crate::lib_fd::FD_RESERVE).
+pub async fn port_exhaustion_load(
+    _experiment_id: String,
+    spec: PortExhaustionSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let target: SocketAddr = spec
+        .target
+        .parse()
+        .with_context(|| format!("target must be ip:port, got {}", spec.target))?;
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let mut want = port_target_count(spec.ports, ephemeral_port_range()?);
+    if let Some(room) = fd_headroom()? {
+        let room = usize::try_from(room).unwrap_or(usize::MAX);
+        if room < want {
+            info!(
+                want,
+                room, "port exhaustion capped at the descriptor reserve"
+            );
+            want = room;
+        }
+    }
+    let mut held = Vec::new();
+    while held.len() < want && Instant::now() < end && !cancel.is_cancelled() {
+        let attempt = tokio::select! {
+            attempt = timeout(CONNECT_TIMEOUT, TcpStream::connect(target)) => attempt,
+            () = cancel.cancelled() => break,
+        };
+        let err = match attempt {
+            Ok(Ok(stream)) => {
+                held.push(stream);
+                if held.len() % GAUGE_BATCH == 0 {
+                    mtr.port_exhaustion_held
+                        .set(i64::try_from(held.len()).unwrap_or(i64::MAX));
+                }
+                continue;
+            }
+            Ok(Err(e)) => e,
+            Err(_) => std::io::Error::from(ErrorKind::TimedOut),
+        };
+        let reason = connect_error_reason(&err);
+        mtr.port_exhaustion_connect_errors_total
+            .with_label_values(&[reason])
+            .inc();
+        if matches!(reason, "addr_not_available" | "fd_limit") {
+            info!(held = held.len(), error=%err, "port exhaustion reached its ceiling");
+            break;
+        }
+        warn!(error=%err, %target, "port exhaustion connect failed");
+        sleep(RETRY_BACKOFF).await;
+    }
+    mtr.port_exhaustion_held
+        .set(i64::try_from(held.len()).unwrap_or(i64::MAX));
+    tokio::select! {
+        () = sleep_until(end) => {}
+        () = cancel.cancelled() => {}
+    }
+    drop(held);
+    mtr.port_exhaustion_held.set(0);
+    Ok(())
+}
+
+/// Metric label for a failed connect.
+#[must_use]
+pub fn connect_error_reason(err: &std::io::Error) -> &'static str {
+    match Errno::from_io_error(err) {
+        Some(Errno::ADDRNOTAVAIL) => "addr_not_available",
+        Some(Errno::MFILE | Errno::NFILE) => "fd_limit",
+        Some(Errno::CONNREFUSED) => "refused",
+        _ if err.kind() == ErrorKind::TimedOut => "timeout",
+        _ => "other",
+    }
+}
+
+/// Connections `ports` asks for, out of the `(low, high)` ephemeral range.
+#[must_use]
+pub fn port_target_count(ports: PortTarget, range: (u16, u16)) -> usize {
+    let size = usize::from(range.1.saturating_sub(range.0)) + 1;
+    match ports {
+        PortTarget::Count { count } => usize::try_from(count).unwrap_or(usize::MAX),
+        PortTarget::RangePercent { percent } => {
+            size * usize::try_from(percent.min(100)).unwrap_or(100) / 100
+        }
+    }
+}
+
+/// Inclusive local port range the kernel picks ephemeral ports from.
+pub fn ephemeral_port_range() -> AnyResult<(u16, u16)> {
+    let path = "/proc/sys/net/ipv4/ip_local_port_range";
+    let raw = std::fs::read_to_string(path).with_context(|| format!("read {path}"))?;
+    parse_port_range(&raw).with_context(|| format!("parse {path}"))
+}
+
+pub fn parse_port_range(raw: &str) -> AnyResult<(u16, u16)> {
+    let mut parts = raw.split_whitespace().map(str::parse::<u16>);
+    let (Some(Ok(low)), Some(Ok(high)), None) = (parts.next(), parts.next(), parts.next()) else {
+        bail!("expected two ports, got {raw:?}");
+    };
+    if low > high {
+        bail!("range {low}..{high} is empty");
+    }
+    Ok((low, high))
+}
@@ -0,0 +1,511 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use anyhow::{bail, Context, Result as AnyResult};
+use regex::Regex;
+use rustix::io::Errno;
+use rustix::process::{kill_process, Pid, Signal};
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::domain::{KillSignal, LoadController, PauseDuty, ProcessMatch};
+use crate::lib_cpu::{duty_on, DUTY_WINDOW};
+use crate::metrics::Metrics;
+
+/// How often a frozen pause re-stops its targets and drops the exited ones.
+const FREEZE_REFRESH: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug)]
+pub struct ProcessPauseSpec {
+    pub target: ProcessMatch,
+    pub max_targets: u32,
+    pub duty: Option<PauseDuty>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProcessThrottleSpec {
+    pub target: ProcessMatch,
+    pub max_targets: u32,
+    pub cpu_percent: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProcessKillSpec {
+    pub target: ProcessMatch,
+    pub max_targets: u32,
+    pub signal: KillSignal,
+    pub interval_seconds: Option<u32>,
+    pub dry_run: bool,
+}
+
+pub async fn process_pause_load(
+    experiment_id: String,
+    spec: ProcessPauseSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    ctrl: LoadController,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let targets = ProcessMatcher::new(&spec.target)?.scan_blocking().await?;
+    check_target_count(&spec.target, targets.len(), spec.max_targets)?;
+    ctrl.set_target_pids(&experiment_id, targets.iter().map(|p| p.pid).collect());
+    ctrl.mark_running(&experiment_id);
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let mut paused = Paused::new(&targets);
+    loop {
+        let stopped = paused.stop();
+        mtr.process_pause_cycles_total.inc();
+        mtr.process_paused
+            .set(i64::try_from(stopped).unwrap_or(i64::MAX));
+        ctrl.set_target_pids(&experiment_id, paused.pids());
+        if stopped == 0 {
+            break;
+        }
+        let Some(duty) = spec.duty else {
+            // Frozen for good: re-stop on every tick, which also drops the
+            // targets that exited from status.
+            while hold_until(deadline(FREEZE_REFRESH, end), end, &cancel).await {
+                let stopped = paused.stop();
+                mtr.process_paused
+                    .set(i64::try_from(stopped).unwrap_or(i64::MAX));
+                ctrl.set_target_pids(&experiment_id, paused.pids());
+                if stopped == 0 {
+                    break;
+                }
+            }
+            break;
+        };
+        if !hold_until(deadline(millis(duty.pause_ms), end), end, &cancel).await {
+            break;
+        }
+        paused.resume();
+        mtr.process_paused.set(0);
+        if !hold_until(deadline(millis(duty.resume_ms), end), end, &cancel).await {
+            break;
+        }
+    }
+    drop(paused);
+    mtr.process_paused.set(0);
+    Ok(())
+}
+
+/// Lets the targets run for `cpu_percent` of every duty window and holds them
+/// stopped for the rest, the same window `cpu_load` spins in.
+pub async fn process_throttle_load(
+    experiment_id: String,
+    spec: ProcessThrottleSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    ctrl: LoadController,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let targets = ProcessMatcher::new(&spec.target)?.scan_blocking().await?;
+    check_target_count(&spec.target, targets.len(), spec.max_targets)?;
+    ctrl.set_target_pids(&experiment_id, targets.iter().map(|p| p.pid).collect());
+    ctrl.mark_running(&experiment_id);
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let run = duty_on(spec.cpu_percent);
+    let mut paused = Paused::new(&targets);
+    mtr.process_throttle_percent
+        .set(i64::from(spec.cpu_percent));
+    loop {
+        if !hold_until(deadline(run, end), end, &cancel).await {
+            break;
+        }
+        let stopped = paused.stop();
+        mtr.process_pause_cycles_total.inc();
+        mtr.process_paused
+            .set(i64::try_from(stopped).unwrap_or(i64::MAX));
+        ctrl.set_target_pids(&experiment_id, paused.pids());
+        if stopped == 0 {
+            break;
+        }
+        let resume_at = deadline(DUTY_WINDOW.saturating_sub(run), end);
+        if !hold_until(resume_at, end, &cancel).await {
+            break;
+        }
+        paused.resume();
+        mtr.process_paused.set(0);
+    }
+    drop(paused);
+    mtr.process_paused.set(0);
+    mtr.process_throttle_percent.set(0);
+    Ok(())
+}
+
+/// Signals every match once, or again each interval until the experiment
+/// ends. Later rounds re-match, so a supervisor restarting the target under a
+/// new pid keeps being hit; only the first round must find something.
+pub async fn process_kill_load(
+    experiment_id: String,
+    spec: ProcessKillSpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    ctrl: LoadController,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let matcher = ProcessMatcher::new(&spec.target)?;
+    let signal = rustix_signal(spec.signal);
+    ctrl.mark_running(&experiment_id);
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let mut first = true;
+    loop {
+        let mut targets = matcher.scan_blocking().await?;
+        if let Err(e) = check_target_count(&spec.target, targets.len(), spec.max_targets) {
+            if first {
+                return Err(e);
+            }
+            record(&ctrl, &experiment_id, e.to_string());
+            targets.clear();
+        }
+        first = false;
+        ctrl.set_target_pids(&experiment_id, targets.iter().map(|p| p.pid).collect());
+        for target in &targets {
+            let outcome = if spec.dry_run {
+                record(
+                    &ctrl,
+                    &experiment_id,
+                    format!("dry run: would send {} to {target}", spec.signal),
+                );
+                "dry_run"
+            } else {
+                send(signal, target, spec.signal, &ctrl, &experiment_id)
+            };
+            mtr.process_kills_total
+                .with_label_values(&[&spec.signal.to_string(), outcome])
+                .inc();
+        }
+        let Some(every) = spec.interval_seconds else {
+            hold_until(end, end, &cancel).await;
+            break;
+        };
+        let every = Duration::from_secs(u64::from(every));
+        if !hold_until(deadline(every, end), end, &cancel).await {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Sends `signal` and logs the result; returns the metric outcome label.
+fn send(
+    signal: Signal,
+    target: &ProcessInfo,
+    name: KillSignal,
+    ctrl: &LoadController,
+    experiment_id: &str,
+) -> &'static str {
+    let Some(pid) = i32::try_from(target.pid).ok().and_then(Pid::from_raw) else {
+        return "failed";
+    };
+    // The pid may have been reused since the scan; never signal a stranger.
+    let result = if process_start_time(target.pid) == Some(target.start_time) {
+        kill_process(pid, signal)
+    } else {
+        Err(Errno::SRCH)
+    };
+    match result {
+        Ok(()) => {
+            info!(pid = target.pid, comm = %target.comm, signal = %name, "signalled process");
+            record(ctrl, experiment_id, format!("sent {name} to {target}"));
+            "sent"
+        }
+        Err(Errno::SRCH) => {
+            record(
+                ctrl,
+                experiment_id,
+                format!("{target} exited before {name}"),
+            );
+            "gone"
+        }
+        Err(e) => {
+            warn!(error=%e, pid = target.pid, signal = %name, "signal failed");
+            record(
+                ctrl,
+                experiment_id,
+                format!("{name} to {target} failed: {e}"),
+            );
+            "failed"
+        }
+    }
+}
+
+fn record(ctrl: &LoadController, experiment_id: &str, message: String) {
+    ctrl.record_event(experiment_id, chrono::Utc::now().timestamp(), message);
+}
+
+fn rustix_signal(signal: KillSignal) -> Signal {
+    match signal {
+        KillSignal::Sighup => Signal::HUP,
+        KillSignal::Sigint => Signal::INT,
+        KillSignal::Sigquit => Signal::QUIT,
+        KillSignal::Sigabrt => Signal::ABORT,
+        KillSignal::Sigkill => Signal::KILL,
+        KillSignal::Sigusr1 => Signal::USR1,
+        KillSignal::Sigusr2 => Signal::USR2,
+        KillSignal::Sigterm => Signal::TERM,
+    }
+}
+
+fn millis(ms: u32) -> Duration {
+    Duration::from_millis(u64::from(ms))
+}
+
+fn deadline(after: Duration, end: Instant) -> Instant {
+    (Instant::now() + after).min(end)
+}
+
+/// Sleeps until `until`; false once the experiment is over or cancelled.
+async fn hold_until(until: Instant, end: Instant, cancel: &CancellationToken) -> bool {
+    tokio::select! {
+        () = sleep_until(until) => until < end,
+        () = cancel.cancelled() => false,
+    }
+}
+
+/// Processes the agent holds stopped. Dropping it sends SIGCONT to every one
+/// of them, so they resume on completion, stop, shutdown or a panic alike.
+struct Paused {
+    /// Each target with the start time it was matched at, checked before
+    /// every signal so a reused pid is never stopped.
+    targets: Vec<(Pid, u64)>,
+}
+
+impl Paused {
+    fn new(targets: &[ProcessInfo]) -> Self {
+        Self {
+            targets: targets
+                .iter()
+                .filter_map(|p| Some((Pid::from_raw(i32::try_from(p.pid).ok()?)?, p.start_time)))
+                .collect(),
+        }
+    }
+
+    /// Stops every target still alive and forgets the ones that exited.
+    fn stop(&mut self) -> usize {
+        self.targets.retain(
+            |&(pid, started)| match signal_same(pid, started, Signal::STOP) {
+                Ok(()) => true,
+                Err(Errno::SRCH) => false,
+                Err(e) => {
+                    warn!(error=%e, pid = pid.as_raw_nonzero().get(), "SIGSTOP failed");
+                    false
+                }
+            },
+        );
+        self.targets.len()
+    }
+
+    fn pids(&self) -> Vec<u32> {
+        self.targets
+            .iter()
+            .map(|(pid, _)| pid.as_raw_nonzero().get().unsigned_abs())
+            .collect()
+    }
+
+    fn resume(&self) {
+        for &(pid, started) in &self.targets {
+            match signal_same(pid, started, Signal::CONT) {
+                Ok(()) | Err(Errno::SRCH) => {}
+                Err(e) => warn!(error=%e, pid = pid.as_raw_nonzero().get(), "SIGCONT failed"),
+            }
+        }
+    }
+}
+
+/// Signals `pid` only while it is still the process that started at
+/// `started`; a pid since reused reads as exited.
+fn signal_same(pid: Pid, started: u64, signal: Signal) -> Result<(), Errno> {
+    let raw = pid.as_raw_nonzero().get().unsigned_abs();
+    if process_start_time(raw) != Some(started) {
+        return Err(Errno::SRCH);
+    }
+    kill_process(pid, signal)
+}
+
+impl Drop for Paused {
+    fn drop(&mut self) {
+        self.resume();
+    }
+}
+
+/// What a matcher may look at for one process, read from `/proc/<pid>`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub comm: String,
+    /// Command line with its arguments joined by spaces.
+    pub cmdline: String,
+    /// File name of the executable; `None` for kernel threads.
+    pub exe: Option<String>,
+    /// Cgroup path from every line of `/proc/<pid>/cgroup`.
+    pub cgroups: Vec<String>,
+    /// Start time in clock ticks after boot, which tells a reused pid apart.
+    pub start_time: u64,
+}
+
+impl ProcessInfo {
+    /// `None` once the process has exited.
+    #[must_use]
+    pub fn read(pid: u32) -> Option<Self> {
+        let dir = format!("/proc/{pid}");
+        let start_time = process_start_time(pid)?;
+        let comm = std::fs::read_to_string(format!("{dir}/comm")).ok()?;
+        let cmdline = std::fs::read(format!("{dir}/cmdline")).ok()?;
+        let cgroups = std::fs::read_to_string(format!("{dir}/cgroup")).ok()?;
+        let exe = std::fs::read_link(format!("{dir}/exe"))
+            .ok()
+            .and_then(|path| {
+                let name = path.file_name()?.to_string_lossy();
+                Some(name.strip_suffix(" (deleted)").unwrap_or(&name).to_string())
+            });
+        Some(Self {
+            pid,
+            comm: comm.trim_end().to_string(),
+            cmdline: join_cmdline(&cmdline),
+            exe,
+            cgroups: cgroups
+                .lines()
+                .filter_map(|line| Some(line.splitn(3, ':').nth(2)?.to_string()))
+                .collect(),
+            start_time,
+        })
+    }
+}
+
+impl std::fmt::Display for ProcessInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pid {} ({})", self.pid, self.comm)
+    }
+}
+
+/// A [`ProcessMatch`] with its regular expression, if any, compiled once.
+#[derive(Clone, Debug)]
+pub struct ProcessMatcher {
+    target: ProcessMatch,
+    regex: Option<Regex>,
+}
+
+impl ProcessMatcher {
+    pub fn new(target: &ProcessMatch) -> AnyResult<Self> {
+        let regex = match target {
+            ProcessMatch::CmdlineRegex { regex } => {
+                Some(Regex::new(regex).with_context(|| format!("invalid cmdline regex {regex}"))?)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            target: target.clone(),
+            regex,
+        })
+    }
+
+    #[must_use]
+    pub fn matches(&self, info: &ProcessInfo) -> bool {
+        match &self.target {
+            ProcessMatch::Pid { pid } => info.pid == *pid,
+            ProcessMatch::Name { name } => info.comm == *name,
+            ProcessMatch::Cmdline { contains } => info.cmdline.contains(contains.as_str()),
+            ProcessMatch::Exe { name } => info.exe.as_deref() == Some(name.as_str()),
+            ProcessMatch::CmdlineRegex { .. } => self
+                .regex
+                .as_ref()
+                .is_some_and(|re| re.is_match(&info.cmdline)),
+            ProcessMatch::Cgroup { path } => {
+                let path = path.trim_end_matches('/');
+                info.cgroups.iter().any(|cg| {
+                    cg.strip_prefix(path)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+                })
+            }
+        }
+    }
+
+    /// [`ProcessMatcher::scan`] on the blocking pool, so walking all of
+    /// `/proc` does not stall the load runtime.
+    pub async fn scan_blocking(&self) -> AnyResult<Vec<ProcessInfo>> {
+        let matcher = self.clone();
+        tokio::task::spawn_blocking(move || matcher.scan())
+            .await
+            .context("join process scan")?
+    }
+
+    /// Live processes this matcher selects, by pid, never including init or
+    /// the agent.
+    pub fn scan(&self) -> AnyResult<Vec<ProcessInfo>> {
+        let own = std::process::id();
+        let protected = |pid| pid <= 1 || pid == own;
+        if let ProcessMatch::Pid { pid } = self.target {
+            return Ok(ProcessInfo::read(pid)
+                .filter(|_| !protected(pid))
+                .into_iter()
+                .collect());
+        }
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir("/proc").context("read /proc")? {
+            let Some(pid) = entry
+                .ok()
+                .and_then(|e| e.file_name().to_str()?.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            if protected(pid) {
+                continue;
+            }
+            // Processes can exit between listing and reading; skip them.
+            let Some(info) = ProcessInfo::read(pid) else {
+                continue;
+            };
+            if self.matches(&info) {
+                found.push(info);
+            }
+        }
+        found.sort_unstable_by_key(|p| p.pid);
+        Ok(found)
+    }
+}
+
+/// Fails unless `found`, the processes `target` matched, is between one and
+/// `max_targets`.
+pub fn check_target_count(target: &ProcessMatch, found: usize, max_targets: u32) -> AnyResult<()> {
+    if found == 0 {
+        bail!("no process matches {target}");
+    }
+    if found > usize::try_from(max_targets).unwrap_or(usize::MAX) {
+        bail!("{target} matches {found} processes, more than max_targets {max_targets}");
+    }
+    Ok(())
+}
+
+/// Start time of `pid` from `/proc/<pid>/stat`; `None` once it has exited.
+#[must_use]
+pub fn process_start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    parse_stat_start_time(&stat)
+}
+
+/// Field 22, `starttime`, of a `/proc/<pid>/stat` line.
+#[must_use]
+pub fn parse_stat_start_time(stat: &str) -> Option<u64> {
+    // comm (field 2) may contain spaces, so count fields after its closing paren.
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// Live processes `target` selects, never including init or the agent itself.
+pub fn find_processes(target: &ProcessMatch) -> AnyResult<Vec<u32>> {
+    Ok(ProcessMatcher::new(target)?
+        .scan()?
+        .into_iter()
+        .map(|p| p.pid)
+        .collect())
+}
+
+/// `/proc/<pid>/cmdline` with its NUL separators turned into spaces.
+#[must_use]
+pub fn join_cmdline(raw: &[u8]) -> String {
+    String::from_utf8_lossy(raw.strip_suffix(&[0]).unwrap_or(raw)).replace('\0', " ")
+}
@@ -0,0 +1,400 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use anyhow::{Context, Result as AnyResult};
+use bytes::Bytes;
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio::time::{interval, sleep, sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::domain::{LoadController, UdpFaults};
+use crate::metrics::Metrics;
+
+/// Largest payload a UDP datagram can carry.
+const MAX_DATAGRAM: usize = 65_535;
+
+/// Datagrams waiting out their delay before the delivery task drops new ones.
+const SEND_QUEUE: usize = 4096;
+
+/// Extra hold on a reordered datagram so the ones behind it get ahead.
+const REORDER_HOLD: Duration = Duration::from_millis(50);
+
+/// Client addresses silent this long lose their upstream socket.
+const SESSION_IDLE: Duration = Duration::from_secs(60);
+
+/// Client addresses relayed at once, each holding an upstream socket; datagrams
+/// from further new addresses are dropped so spraying source ports cannot
+/// exhaust the agent's descriptors.
+pub const MAX_SESSIONS: usize = 256;
+
+/// How often idle sessions are looked for.
+const SESSION_SWEEP: Duration = Duration::from_secs(5);
+
+/// Pause after a failed receive, so a persistent error cannot spin.
+const RECV_BACKOFF: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug)]
+pub struct UdpProxySpec {
+    pub listen: String,
+    pub upstream: String,
+    pub faults: UdpFaults,
+}
+
+/// What happens to one datagram.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Verdict {
+    pub lost: bool,
+    pub duplicated: bool,
+    pub reordered: bool,
+    pub delay: Duration,
+}
+
+/// Draws a [`Verdict`] per datagram from a seeded RNG, so the same seed and
+/// datagram order replay the same faults.
+pub struct UdpShaper {
+    faults: UdpFaults,
+    rng: StdRng,
+}
+
+impl UdpShaper {
+    #[must_use]
+    pub fn new(faults: UdpFaults, seed: u64) -> Self {
+        Self {
+            faults,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Every roll is drawn even when an earlier one decides the outcome, so
+    /// one datagram always consumes the same amount of randomness.
+    pub fn verdict(&mut self) -> Verdict {
+        let lost = self.roll(self.faults.loss_percent);
+        let duplicated = self.roll(self.faults.duplicate_percent) && !lost;
+        let reordered = self.roll(self.faults.reorder_percent) && !lost;
+        let jitter = i64::from(self.faults.jitter_ms);
+        let jitter = self.rng.random_range(-jitter..=jitter);
+        let ms = (i64::from(self.faults.delay_ms) + jitter).max(0);
+        let mut delay = Duration::from_millis(u64::try_from(ms).unwrap_or(0));
+        if reordered {
+            delay += REORDER_HOLD;
+        }
+        Verdict {
+            lost,
+            duplicated,
+            reordered,
+            delay,
+        }
+    }
+
+    fn roll(&mut self, percent: u32) -> bool {
+        self.rng.random_range(0..100) < percent
+    }
+}
+
+pub async fn udp_proxy_load(
+    experiment_id: String,
+    spec: UdpProxySpec,
+    duration_seconds: u32,
+    mtr: Metrics,
+    ctrl: LoadController,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    let upstream = tokio::net::lookup_host(&spec.upstream)
+        .await
+        .with_context(|| format!("resolve {}", spec.upstream))?
+        .next()
+        .with_context(|| format!("{} resolved to no address", spec.upstream))?;
+    let listen = Arc::new(
+        UdpSocket::bind(&spec.listen)
+            .await
+            .with_context(|| format!("bind {}", spec.listen))?,
+    );
+    let local = listen.local_addr().context("proxy local address")?;
+    ctrl.set_listen_addr(&experiment_id, local.to_string());
+    let seed = spec.faults.seed.unwrap_or_else(rand::random);
+    info!(experiment_id = %experiment_id, seed, "udp proxy fault seed");
+    ctrl.set_seed(&experiment_id, seed);
+    let shaper = Arc::new(Mutex::new(UdpShaper::new(spec.faults, seed)));
+
+    ctrl.mark_running(&experiment_id);
+    let end = Instant::now() + Duration::from_secs(u64::from(duration_seconds));
+    let tasks_cancel = cancel.child_token();
+    let (tx, rx) = mpsc::channel(SEND_QUEUE);
+    let mut tasks = JoinSet::new();
+    tasks.spawn(deliver(
+        rx,
+        listen.clone(),
+        mtr.clone(),
+        tasks_cancel.clone(),
+    ));
+    let mut sessions: HashMap<SocketAddr, Session> = HashMap::new();
+    let mut sweep = interval(SESSION_SWEEP);
+    let mut buf = vec![0u8; MAX_DATAGRAM];
+    let res = loop {
+        tokio::select! {
+            () = sleep_until(end) => break Ok(()),
+            () = cancel.cancelled() => break Ok(()),
+            received = listen.recv_from(&mut buf) => match received {
+                Ok((n, client)) => {
+                    let full = sessions.len() >= MAX_SESSIONS;
+                    let session = match sessions.entry(client) {
+                        Entry::Occupied(e) => e.into_mut(),
+                        Entry::Vacant(_) if full => {
+                            mtr.udp_proxy_faults_total
+                                .with_label_values(&["session_limit"])
+                                .inc();
+                            continue;
+                        }
+                        Entry::Vacant(e) => {
+                            match Session::open(upstream).await {
+                                Ok(session) => {
+                                    tasks.spawn(session_reader(
+                                        session.sock.clone(),
+                                        client,
+                                        shaper.clone(),
+                                        tx.clone(),
+                                        mtr.clone(),
+                                        session.cancel.clone(),
+                                    ));
+                                    e.insert(session)
+                                }
+                                // One client failing to get an upstream socket
+                                // must not end the proxy for the others.
+                                Err(e) => {
+                                    warn!(error=%format!("{e:#}"), %client, "udp proxy session open failed");
+                                    mtr.udp_proxy_faults_total
+                                        .with_label_values(&["session_open"])
+                                        .inc();
+                                    continue;
+                                }
+                            }
+                        }
+                    };
+                    session.last_active = Instant::now();
+                    let dest = Dest::Upstream(session.sock.clone());
+                    shape(&shaper, &tx, &mtr, dest, &buf[..n]);
+                }
+                Err(e) => {
+                    warn!(error=%e, "udp proxy receive failed");
+                    sleep(RECV_BACKOFF).await;
+                }
+            },
+            _ = sweep.tick() => {
+                sessions.retain(|_, session| {
+                    let live = session.last_active.elapsed() < SESSION_IDLE;
+                    if !live {
+                        session.cancel.cancel();
+                    }
+                    live
+                });
+            }
+            Some(_) = tasks.join_next(), if !tasks.is_empty() => {}
+        }
+        mtr.udp_proxy_sessions
+            .set(i64::try_from(sessions.len()).unwrap_or(i64::MAX));
+    };
+    tasks_cancel.cancel();
+    drop(sessions);
+    while tasks.join_next().await.is_some() {}
+    mtr.udp_proxy_sessions.set(0);
+    res
+}
+
+/// Upstream socket relaying one client's datagrams, so replies can be told apart.
+struct Session {
+    sock: Arc<UdpSocket>,
+    cancel: CancellationToken,
+    last_active: Instant,
+}
+
+impl Session {
+    async fn open(upstream: SocketAddr) -> AnyResult<Self> {
+        let bind: SocketAddr = if upstream.is_ipv4() {
+            "0.0.0.0:0".parse()?
+        } else {
+            "[::]:0".parse()?
+        };
+        let sock = UdpSocket::bind(bind)
+            .await
+            .context("bind upstream socket")?;
+        sock.connect(upstream)
+            .await
+            .with_context(|| format!("connect {upstream}"))?;
+        Ok(Self {
+            sock: Arc::new(sock),
+            cancel: CancellationToken::new(),
+            last_active: Instant::now(),
+        })
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+async fn session_reader(
+    sock: Arc<UdpSocket>,
+    client: SocketAddr,
+    shaper: Arc<Mutex<UdpShaper>>,
+    tx: mpsc::Sender<Pending>,
+    mtr: Metrics,
+    cancel: CancellationToken,
+) {
+    let mut buf = vec![0u8; MAX_DATAGRAM];
+    loop {
+        tokio::select! {
+            () = cancel.cancelled() => return,
+            received = sock.recv(&mut buf) => match received {
+                Ok(n) => shape(&shaper, &tx, &mtr, Dest::Client(client), &buf[..n]),
+                // An ICMP unreachable from the upstream surfaces once on a connected socket.
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {}
+                Err(e) => {
+                    debug!(error=%e, %client, "udp proxy upstream receive failed");
+                    return;
+                }
+            },
+        }
+    }
+}
+
+enum Dest {
+    Upstream(Arc<UdpSocket>),
+    Client(SocketAddr),
+}
+
+impl Dest {
+    fn direction(&self) -> &'static str {
+        match self {
+            Dest::Upstream(_) => "upstream",
+            Dest::Client(_) => "downstream",
+        }
+    }
+}
+
+/// Datagram due for delivery; ordered by due time, then arrival.
+struct Pending {
+    due: Instant,
+    seq: u64,
+    dest: Dest,
+    data: Bytes,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.due, self.seq).cmp(&(other.due, other.seq))
+    }
+}
+
+/// Applies a verdict to one datagram and queues the copies that survive it.
+fn shape(
+    shaper: &Mutex<UdpShaper>,
+    tx: &mpsc::Sender<Pending>,
+    mtr: &Metrics,
+    dest: Dest,
+    data: &[u8],
+) {
+    let verdict = shaper.lock().verdict();
+    let fault = |name: &str| mtr.udp_proxy_faults_total.with_label_values(&[name]).inc();
+    if verdict.lost {
+        fault("loss");
+        return;
+    }
+    if verdict.reordered {
+        fault("reorder");
+    }
+    if !verdict.delay.is_zero() {
+        fault("delay");
+    }
+    let due = Instant::now() + verdict.delay;
+    let data = Bytes::copy_from_slice(data);
+    if verdict.duplicated {
+        fault("duplicate");
+        let copy = match &dest {
+            Dest::Upstream(sock) => Dest::Upstream(sock.clone()),
+            Dest::Client(addr) => Dest::Client(*addr),
+        };
+        queue(tx, due, copy, data.clone());
+    }
+    queue(tx, due, dest, data);
+}
+
+fn queue(tx: &mpsc::Sender<Pending>, due: Instant, dest: Dest, data: Bytes) {
+    // A full queue drops the datagram, as a congested link would.
+    let _ = tx.try_send(Pending {
+        due,
+        seq: 0,
+        dest,
+        data,
+    });
+}
+
+/// Sends queued datagrams once they are due, earliest first.
+async fn deliver(
+    mut rx: mpsc::Receiver<Pending>,
+    listen: Arc<UdpSocket>,
+    mtr: Metrics,
+    cancel: CancellationToken,
+) {
+    let mut heap = BinaryHeap::new();
+    let mut seq = 0u64;
+    loop {
+        let next_due = heap.peek().map(|Reverse(p): &Reverse<Pending>| p.due);
+        tokio::select! {
+            () = cancel.cancelled() => return,
+            pending = rx.recv() => match pending {
+                Some(mut pending) => {
+                    pending.seq = seq;
+                    seq += 1;
+                    heap.push(Reverse(pending));
+                }
+                None => return,
+            },
+            () = sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {
+                let now = Instant::now();
+                while heap.peek().is_some_and(|Reverse(p)| p.due <= now) {
+                    let Some(Reverse(p)) = heap.pop() else { break };
+                    let sent = match &p.dest {
+                        Dest::Upstream(sock) => sock.send(&p.data).await,
+                        Dest::Client(addr) => listen.send_to(&p.data, addr).await,
+                    };
+                    match sent {
+                        Ok(_) => mtr
+                            .udp_proxy_datagrams_total
+                            .with_label_values(&[p.dest.direction()])
+                            .inc(),
+                        Err(e) => debug!(error=%e, "udp proxy send failed"),
+                    }
+                }
+            }
+        }
+    }
+}
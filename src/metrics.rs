@@ -1,9 +1,13 @@
 #![forbid(unsafe_code)]
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
 
 use anyhow::{Context, Result as AnyResult};
-use prometheus::{Encoder, IntCounter, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use prometheus::{
+    Encoder, GaugeVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
 
 #[derive(Clone)]
 pub struct Metrics {
@@ -11,13 +15,51 @@ pub struct Metrics {
     pub cpu_hog_active: IntGauge,
     pub cpu_hog_duty_percent: IntGauge,
     pub cpu_seconds_total: IntCounter,
+    pub cpu_hog_cores: IntGauge,
+    pub cpu_hog_core_duty_percent: IntGaugeVec,
+    pub cpu_hog_measured_percent: GaugeVec,
+    pub cpu_hog_duty_error_percent: GaugeVec,
+    pub cpu_hog_mode_percent: GaugeVec,
+    pub memory_hog_resident_bytes: IntGauge,
+    pub memory_hog_allocated_bytes: IntGauge,
+    pub disk_io_bytes_total: IntCounterVec,
+    pub disk_io_ops_total: IntCounterVec,
+    pub disk_io_fsyncs_total: IntCounter,
+    pub disk_fill_bytes: IntGauge,
+    pub fd_hog_held: IntGauge,
+    pub pids_hog_live: IntGauge,
+    pub tcp_proxy_bytes_total: IntCounterVec,
+    pub tcp_proxy_connections_total: IntCounter,
+    pub tcp_proxy_active_connections: IntGauge,
+    pub tcp_proxy_faults_total: IntCounterVec,
+    pub udp_proxy_datagrams_total: IntCounterVec,
+    pub udp_proxy_sessions: IntGauge,
+    pub udp_proxy_faults_total: IntCounterVec,
+    pub http_fault_requests_total: IntCounter,
+    pub http_fault_hits_total: IntCounterVec,
+    pub dns_fault_queries_total: IntCounter,
+    pub dns_fault_hits_total: IntCounterVec,
+    pub dns_fault_upstream_failures_total: IntCounter,
+    pub process_paused: IntGauge,
+    pub process_pause_cycles_total: IntCounter,
+    pub process_kills_total: IntCounterVec,
+    pub process_throttle_percent: IntGauge,
+    pub port_exhaustion_held: IntGauge,
+    pub port_exhaustion_connect_errors_total: IntCounterVec,
+    pub cache_thrash_working_set_bytes: IntGauge,
+    pub cache_thrash_bandwidth_bytes: IntGauge,
+    pub page_cache_pressure_file_bytes: IntGauge,
+    pub page_cache_pressure_read_bytes_total: IntCounter,
+    pub page_cache_pressure_passes_total: IntCounter,
     pub experiment_active: IntGauge,
     pub experiment_total_seconds: IntGauge,
     pub experiment_remaining_seconds: IntGauge,
     pub experiment_running: IntGaugeVec,
+    pub experiment_failures_total: IntCounterVec,
 }
 
 impl Metrics {
+    #[allow(clippy::too_many_lines)]
     pub fn new() -> AnyResult<Self> {
         let registry = Registry::new();
         let cpu_hog_active = IntGauge::with_opts(Opts::new("agent_cpu_hog_active", "active flag"))
@@ -37,6 +79,334 @@ impl Metrics {
         registry
             .register(Box::new(cpu_seconds_total.clone()))
             .context("register cpu_seconds_total")?;
+        let cpu_hog_cores =
+            IntGauge::with_opts(Opts::new("agent_cpu_hog_cores", "cpu hog worker threads"))
+                .context("create cpu_hog_cores")?;
+        let cpu_hog_core_duty_percent = IntGaugeVec::new(
+            Opts::new(
+                "agent_cpu_hog_core_duty_percent",
+                "duty percent per cpu hog worker",
+            ),
+            &["worker", "cpu"],
+        )
+        .context("create cpu_hog_core_duty_percent")?;
+        registry
+            .register(Box::new(cpu_hog_cores.clone()))
+            .context("register cpu_hog_cores")?;
+        registry
+            .register(Box::new(cpu_hog_core_duty_percent.clone()))
+            .context("register cpu_hog_core_duty_percent")?;
+        let cpu_hog_measured_percent = GaugeVec::new(
+            Opts::new(
+                "agent_cpu_hog_measured_percent",
+                "thread cpu utilization measured over the last duty window",
+            ),
+            &["worker", "cpu"],
+        )
+        .context("create cpu_hog_measured_percent")?;
+        let cpu_hog_duty_error_percent = GaugeVec::new(
+            Opts::new(
+                "agent_cpu_hog_duty_error_percent",
+                "target minus measured utilization",
+            ),
+            &["worker", "cpu"],
+        )
+        .context("create cpu_hog_duty_error_percent")?;
+        registry
+            .register(Box::new(cpu_hog_measured_percent.clone()))
+            .context("register cpu_hog_measured_percent")?;
+        registry
+            .register(Box::new(cpu_hog_duty_error_percent.clone()))
+            .context("register cpu_hog_duty_error_percent")?;
+        let cpu_hog_mode_percent = GaugeVec::new(
+            Opts::new(
+                "agent_cpu_hog_mode_percent",
+                "measured utilization split into user and system mode",
+            ),
+            &["worker", "cpu", "mode"],
+        )
+        .context("create cpu_hog_mode_percent")?;
+        registry
+            .register(Box::new(cpu_hog_mode_percent.clone()))
+            .context("register cpu_hog_mode_percent")?;
+        let memory_hog_resident_bytes = IntGauge::with_opts(Opts::new(
+            "agent_memory_hog_resident_bytes",
+            "memory hog footprint: agent resident set plus tmpfs or page cache ballast",
+        ))
+        .context("create memory_hog_resident_bytes")?;
+        registry
+            .register(Box::new(memory_hog_resident_bytes.clone()))
+            .context("register memory_hog_resident_bytes")?;
+        let memory_hog_allocated_bytes = IntGauge::with_opts(Opts::new(
+            "agent_memory_hog_allocated_bytes",
+            "bytes currently held by the memory hog",
+        ))
+        .context("create memory_hog_allocated_bytes")?;
+        registry
+            .register(Box::new(memory_hog_allocated_bytes.clone()))
+            .context("register memory_hog_allocated_bytes")?;
+        let disk_io_bytes_total = IntCounterVec::new(
+            Opts::new(
+                "agent_disk_io_bytes_total",
+                "bytes moved by the disk I/O hog",
+            ),
+            &["op"],
+        )
+        .context("create disk_io_bytes_total")?;
+        registry
+            .register(Box::new(disk_io_bytes_total.clone()))
+            .context("register disk_io_bytes_total")?;
+        let disk_io_ops_total = IntCounterVec::new(
+            Opts::new(
+                "agent_disk_io_ops_total",
+                "read and write calls issued by the disk I/O hog",
+            ),
+            &["op"],
+        )
+        .context("create disk_io_ops_total")?;
+        registry
+            .register(Box::new(disk_io_ops_total.clone()))
+            .context("register disk_io_ops_total")?;
+        let disk_io_fsyncs_total = IntCounter::with_opts(Opts::new(
+            "agent_disk_io_fsyncs_total",
+            "fsync calls issued by the disk I/O hog",
+        ))
+        .context("create disk_io_fsyncs_total")?;
+        registry
+            .register(Box::new(disk_io_fsyncs_total.clone()))
+            .context("register disk_io_fsyncs_total")?;
+        let disk_fill_bytes = IntGauge::with_opts(Opts::new(
+            "agent_disk_fill_bytes",
+            "ballast bytes currently held by the disk-fill hog",
+        ))
+        .context("create disk_fill_bytes")?;
+        registry
+            .register(Box::new(disk_fill_bytes.clone()))
+            .context("register disk_fill_bytes")?;
+        let fd_hog_held = IntGauge::with_opts(Opts::new(
+            "agent_fd_hog_held",
+            "file descriptors currently held by the FD hog",
+        ))
+        .context("create fd_hog_held")?;
+        registry
+            .register(Box::new(fd_hog_held.clone()))
+            .context("register fd_hog_held")?;
+        let pids_hog_live = IntGauge::with_opts(Opts::new(
+            "agent_pids_hog_live",
+            "threads or child processes currently alive in the pids hog",
+        ))
+        .context("create pids_hog_live")?;
+        registry
+            .register(Box::new(pids_hog_live.clone()))
+            .context("register pids_hog_live")?;
+        let tcp_proxy_bytes_total = IntCounterVec::new(
+            Opts::new(
+                "agent_tcp_proxy_bytes_total",
+                "bytes forwarded by the TCP proxy",
+            ),
+            &["direction"],
+        )
+        .context("create tcp_proxy_bytes_total")?;
+        registry
+            .register(Box::new(tcp_proxy_bytes_total.clone()))
+            .context("register tcp_proxy_bytes_total")?;
+        let tcp_proxy_connections_total = IntCounter::with_opts(Opts::new(
+            "agent_tcp_proxy_connections_total",
+            "client connections accepted by the TCP proxy",
+        ))
+        .context("create tcp_proxy_connections_total")?;
+        registry
+            .register(Box::new(tcp_proxy_connections_total.clone()))
+            .context("register tcp_proxy_connections_total")?;
+        let tcp_proxy_active_connections = IntGauge::with_opts(Opts::new(
+            "agent_tcp_proxy_active_connections",
+            "client connections currently open through the TCP proxy",
+        ))
+        .context("create tcp_proxy_active_connections")?;
+        registry
+            .register(Box::new(tcp_proxy_active_connections.clone()))
+            .context("register tcp_proxy_active_connections")?;
+        let tcp_proxy_faults_total = IntCounterVec::new(
+            Opts::new(
+                "agent_tcp_proxy_faults_total",
+                "faults injected by the TCP proxy",
+            ),
+            &["fault"],
+        )
+        .context("create tcp_proxy_faults_total")?;
+        registry
+            .register(Box::new(tcp_proxy_faults_total.clone()))
+            .context("register tcp_proxy_faults_total")?;
+        let udp_proxy_datagrams_total = IntCounterVec::new(
+            Opts::new(
+                "agent_udp_proxy_datagrams_total",
+                "datagrams delivered by the UDP proxy",
+            ),
+            &["direction"],
+        )
+        .context("create udp_proxy_datagrams_total")?;
+        registry
+            .register(Box::new(udp_proxy_datagrams_total.clone()))
+            .context("register udp_proxy_datagrams_total")?;
+        let udp_proxy_sessions = IntGauge::with_opts(Opts::new(
+            "agent_udp_proxy_sessions",
+            "client addresses the UDP proxy currently relays for",
+        ))
+        .context("create udp_proxy_sessions")?;
+        registry
+            .register(Box::new(udp_proxy_sessions.clone()))
+            .context("register udp_proxy_sessions")?;
+        let udp_proxy_faults_total = IntCounterVec::new(
+            Opts::new(
+                "agent_udp_proxy_faults_total",
+                "faults injected by the UDP proxy",
+            ),
+            &["fault"],
+        )
+        .context("create udp_proxy_faults_total")?;
+        registry
+            .register(Box::new(udp_proxy_faults_total.clone()))
+            .context("register udp_proxy_faults_total")?;
+        let http_fault_requests_total = IntCounter::with_opts(Opts::new(
+            "agent_http_fault_requests_total",
+            "requests received by the HTTP fault proxy",
+        ))
+        .context("create http_fault_requests_total")?;
+        registry
+            .register(Box::new(http_fault_requests_total.clone()))
+            .context("register http_fault_requests_total")?;
+        let http_fault_hits_total = IntCounterVec::new(
+            Opts::new(
+                "agent_http_fault_hits_total",
+                "requests an HTTP fault rule applied its action to",
+            ),
+            &["rule", "action"],
+        )
+        .context("create http_fault_hits_total")?;
+        registry
+            .register(Box::new(http_fault_hits_total.clone()))
+            .context("register http_fault_hits_total")?;
+        let dns_fault_queries_total = IntCounter::with_opts(Opts::new(
+            "agent_dns_fault_queries_total",
+            "queries received by the DNS fault responder",
+        ))
+        .context("create dns_fault_queries_total")?;
+        registry
+            .register(Box::new(dns_fault_queries_total.clone()))
+            .context("register dns_fault_queries_total")?;
+        let dns_fault_hits_total = IntCounterVec::new(
+            Opts::new(
+                "agent_dns_fault_hits_total",
+                "queries a DNS fault rule applied its action to",
+            ),
+            &["rule", "action"],
+        )
+        .context("create dns_fault_hits_total")?;
+        registry
+            .register(Box::new(dns_fault_hits_total.clone()))
+            .context("register dns_fault_hits_total")?;
+        let dns_fault_upstream_failures_total = IntCounter::with_opts(Opts::new(
+            "agent_dns_fault_upstream_failures_total",
+            "forwarded queries the upstream resolver did not answer",
+        ))
+        .context("create dns_fault_upstream_failures_total")?;
+        registry
+            .register(Box::new(dns_fault_upstream_failures_total.clone()))
+            .context("register dns_fault_upstream_failures_total")?;
+        let process_paused = IntGauge::with_opts(Opts::new(
+            "agent_process_paused",
+            "target processes currently held stopped by the agent",
+        ))
+        .context("create process_paused")?;
+        registry
+            .register(Box::new(process_paused.clone()))
+            .context("register process_paused")?;
+        let process_pause_cycles_total = IntCounter::with_opts(Opts::new(
+            "agent_process_pause_cycles_total",
+            "times the agent stopped its target processes",
+        ))
+        .context("create process_pause_cycles_total")?;
+        registry
+            .register(Box::new(process_pause_cycles_total.clone()))
+            .context("register process_pause_cycles_total")?;
+        let process_kills_total = IntCounterVec::new(
+            Opts::new(
+                "agent_process_kills_total",
+                "signals a process kill experiment sent or, in a dry run, would have sent",
+            ),
+            &["signal", "outcome"],
+        )
+        .context("create process_kills_total")?;
+        registry
+            .register(Box::new(process_kills_total.clone()))
+            .context("register process_kills_total")?;
+        let process_throttle_percent = IntGauge::with_opts(Opts::new(
+            "agent_process_throttle_percent",
+            "share of wall time throttled processes are allowed to run",
+        ))
+        .context("create process_throttle_percent")?;
+        registry
+            .register(Box::new(process_throttle_percent.clone()))
+            .context("register process_throttle_percent")?;
+        let port_exhaustion_held = IntGauge::with_opts(Opts::new(
+            "agent_port_exhaustion_held",
+            "outbound connections held open by the port hog",
+        ))
+        .context("create port_exhaustion_held")?;
+        registry
+            .register(Box::new(port_exhaustion_held.clone()))
+            .context("register port_exhaustion_held")?;
+        let port_exhaustion_connect_errors_total = IntCounterVec::new(
+            Opts::new(
+                "agent_port_exhaustion_connect_errors_total",
+                "connects the port hog could not complete",
+            ),
+            &["reason"],
+        )
+        .context("create port_exhaustion_connect_errors_total")?;
+        registry
+            .register(Box::new(port_exhaustion_connect_errors_total.clone()))
+            .context("register port_exhaustion_connect_errors_total")?;
+        let cache_thrash_working_set_bytes = IntGauge::with_opts(Opts::new(
+            "agent_cache_thrash_working_set_bytes",
+            "working set each cache hog worker streams through",
+        ))
+        .context("create cache_thrash_working_set_bytes")?;
+        registry
+            .register(Box::new(cache_thrash_working_set_bytes.clone()))
+            .context("register cache_thrash_working_set_bytes")?;
+        let cache_thrash_bandwidth_bytes = IntGauge::with_opts(Opts::new(
+            "agent_cache_thrash_bandwidth_bytes_per_second",
+            "cache lines the cache hog moved over the last second, in bytes",
+        ))
+        .context("create cache_thrash_bandwidth_bytes")?;
+        registry
+            .register(Box::new(cache_thrash_bandwidth_bytes.clone()))
+            .context("register cache_thrash_bandwidth_bytes")?;
+        let page_cache_pressure_file_bytes = IntGauge::with_opts(Opts::new(
+            "agent_page_cache_pressure_file_bytes",
+            "scratch file bytes the page cache hog has laid out",
+        ))
+        .context("create page_cache_pressure_file_bytes")?;
+        registry
+            .register(Box::new(page_cache_pressure_file_bytes.clone()))
+            .context("register page_cache_pressure_file_bytes")?;
+        let page_cache_pressure_read_bytes_total = IntCounter::with_opts(Opts::new(
+            "agent_page_cache_pressure_read_bytes_total",
+            "bytes the page cache hog read from its scratch files",
+        ))
+        .context("create page_cache_pressure_read_bytes_total")?;
+        registry
+            .register(Box::new(page_cache_pressure_read_bytes_total.clone()))
+            .context("register page_cache_pressure_read_bytes_total")?;
+        let page_cache_pressure_passes_total = IntCounter::with_opts(Opts::new(
+            "agent_page_cache_pressure_passes_total",
+            "complete reads through every scratch file",
+        ))
+        .context("create page_cache_pressure_passes_total")?;
+        registry
+            .register(Box::new(page_cache_pressure_passes_total.clone()))
+            .context("register page_cache_pressure_passes_total")?;
         let experiment_active = IntGauge::with_opts(Opts::new(
             "agent_experiment_active",
             "1 if an experiment is running",
@@ -72,15 +442,63 @@ impl Metrics {
         registry
             .register(Box::new(experiment_running.clone()))
             .context("register experiment_running")?;
+        let experiment_failures_total = IntCounterVec::new(
+            Opts::new(
+                "agent_experiment_failures_total",
+                "experiments whose load failed or panicked",
+            ),
+            &["kind"],
+        )
+        .context("create experiment_failures_total")?;
+        registry
+            .register(Box::new(experiment_failures_total.clone()))
+            .context("register experiment_failures_total")?;
         Ok(Self {
             registry,
             cpu_hog_active,
             cpu_hog_duty_percent,
             cpu_seconds_total,
+            cpu_hog_cores,
+            cpu_hog_core_duty_percent,
+            cpu_hog_measured_percent,
+            cpu_hog_duty_error_percent,
+            cpu_hog_mode_percent,
+            memory_hog_resident_bytes,
+            memory_hog_allocated_bytes,
+            disk_io_bytes_total,
+            disk_io_ops_total,
+            disk_io_fsyncs_total,
+            disk_fill_bytes,
+            fd_hog_held,
+            pids_hog_live,
+            tcp_proxy_bytes_total,
+            tcp_proxy_connections_total,
+            tcp_proxy_active_connections,
+            tcp_proxy_faults_total,
+            udp_proxy_datagrams_total,
+            udp_proxy_sessions,
+            udp_proxy_faults_total,
+            http_fault_requests_total,
+            http_fault_hits_total,
+            dns_fault_queries_total,
+            dns_fault_hits_total,
+            dns_fault_upstream_failures_total,
+            process_paused,
+            process_pause_cycles_total,
+            process_kills_total,
+            process_throttle_percent,
+            port_exhaustion_held,
+            port_exhaustion_connect_errors_total,
+            cache_thrash_working_set_bytes,
+            cache_thrash_bandwidth_bytes,
+            page_cache_pressure_file_bytes,
+            page_cache_pressure_read_bytes_total,
+            page_cache_pressure_passes_total,
             experiment_active,
             experiment_total_seconds,
             experiment_remaining_seconds,
             experiment_running,
+            experiment_failures_total,
         })
     }
 
@@ -104,6 +522,12 @@ impl Metrics {
         self.experiment_remaining_seconds.set(0);
     }
 
+    pub fn mark_experiment_failed(&self, kind: &str) {
+        self.experiment_failures_total
+            .with_label_values(&[kind])
+            .inc();
+    }
+
     pub fn update_remaining(&self, remaining_seconds: u32) {
         self.experiment_remaining_seconds
             .set(i64::from(remaining_seconds));
@@ -112,12 +536,50 @@ impl Metrics {
     pub fn mark_cpu_active(&self, duty_percent: u32) {
         self.cpu_hog_active.set(1);
         self.cpu_hog_duty_percent
-            .set(i64::from(duty_percent.min(100).max(1)));
+            .set(i64::from(duty_percent.clamp(1, 100)));
     }
 
     pub fn clear_cpu_active(&self) {
         self.cpu_hog_active.set(0);
         self.cpu_hog_duty_percent.set(0);
+        self.cpu_hog_cores.set(0);
+    }
+
+    pub fn set_cpu_measured(&self, labels: &[&str], measured_percent: f64, error_percent: f64) {
+        self.cpu_hog_measured_percent
+            .with_label_values(labels)
+            .set(measured_percent);
+        self.cpu_hog_duty_error_percent
+            .with_label_values(labels)
+            .set(error_percent);
+    }
+
+    /// Splits a worker's measured utilization into `user` and `system` mode.
+    pub fn set_cpu_modes(&self, labels: &[&str], user_percent: f64, system_percent: f64) {
+        for (mode, percent) in [("user", user_percent), ("system", system_percent)] {
+            self.cpu_hog_mode_percent
+                .with_label_values(&[labels[0], labels[1], mode])
+                .set(percent);
+        }
+    }
+
+    pub fn clear_cpu_worker(&self, labels: &[&str]) {
+        let _ = self.cpu_hog_core_duty_percent.remove_label_values(labels);
+        let _ = self.cpu_hog_measured_percent.remove_label_values(labels);
+        let _ = self.cpu_hog_duty_error_percent.remove_label_values(labels);
+        for mode in ["user", "system"] {
+            let _ = self
+                .cpu_hog_mode_percent
+                .remove_label_values(&[labels[0], labels[1], mode]);
+        }
+    }
+
+    /// Counts one completed disk operation of `bytes`; `op` is `read` or `write`.
+    pub fn record_disk_io(&self, op: &str, bytes: usize) {
+        self.disk_io_ops_total.with_label_values(&[op]).inc();
+        self.disk_io_bytes_total
+            .with_label_values(&[op])
+            .inc_by(u64::try_from(bytes).unwrap_or(u64::MAX));
     }
 
     pub fn set_running_info(
@@ -1,14 +1,55 @@
 #![forbid(unsafe_code)]
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
 
 use anyhow::Result as AnyResult;
 use serde::Serialize;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
 
-use crate::domain::{Experiment, ExperimentParams, ExperimentState, LoadController, StartRequest};
+use crate::domain::{
+    Experiment, ExperimentHandle, ExperimentParams, ExperimentPhase, ExperimentState,
+    LoadController, StartRequest,
+};
 use crate::metrics::Metrics;
 use crate::validation::validate_start;
 
+/// Extra time a load may run past its configured duration before it is
+/// cancelled and the experiment is marked `TimedOut`.
+const LOAD_DEADLINE_GRACE: Duration = Duration::from_secs(10);
+
+/// Longest a load may spend setting up before it reports running.
+const LOAD_SETUP_LIMIT: Duration = Duration::from_secs(60);
+
+/// How long an overdue load gets to release what it holds once cancelled,
+/// before its task is aborted.
+const LOAD_RELEASE_WAIT: Duration = Duration::from_secs(10);
+
+const LOAD_RUNTIME_THREADS: usize = 2;
+
+static LOAD_RUNTIME: OnceLock<Result<Runtime, String>> = OnceLock::new();
+
+/// Runtime driving experiment loads, kept apart from the actix workers so a
+/// busy load cannot delay `/healthz` or `/metrics`.
+fn load_runtime() -> AnyResult<&'static Runtime> {
+    LOAD_RUNTIME
+        .get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(LOAD_RUNTIME_THREADS)
+                .thread_name("load-rt")
+                .enable_all()
+                .build()
+                .map_err(|e| format!("build load runtime: {e}"))
+        })
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("{e}"))
+}
+
 #[derive(Clone)]
 pub struct ExperimentRunner {
     ctrl: LoadController,
@@ -16,24 +57,31 @@ pub struct ExperimentRunner {
 }
 
 impl ExperimentRunner {
+    #[must_use]
     pub fn new(ctrl: LoadController, metrics: Metrics) -> Self {
         Self { ctrl, metrics }
     }
 
+    #[must_use]
     pub fn running_id(&self) -> Option<String> {
         self.ctrl.get_running_id()
     }
 
-    pub fn validate_request(&self, req: &StartRequest) -> AnyResult<()> {
-        validate_start(req).map_err(|e| anyhow::anyhow!(e))
+    /// Runs on the blocking pool, since matching processes walks all of
+    /// `/proc` and must not stall the HTTP worker.
+    pub async fn validate_request(&self, req: &StartRequest) -> AnyResult<()> {
+        let req = req.clone();
+        tokio::task::spawn_blocking(move || validate_start(&req))
+            .await
+            .map_err(|e| anyhow::anyhow!(e).context("join request validation"))?
     }
 
     pub fn create_experiment(&self, req: &StartRequest, now_ts: i64) -> AnyResult<Experiment> {
         Experiment::new_from_start_request(req, now_ts)
     }
 
-    pub fn begin(&self, exp: &Experiment) {
-        self.ctrl.start(&exp.id, exp);
+    pub fn begin(&self, exp: &Experiment) -> AnyResult<ExperimentHandle> {
+        let handle = self.ctrl.start(&exp.id, exp)?;
         self.metrics.mark_experiment_started(exp.duration_seconds);
         self.metrics.set_running_info(
             &exp.id,
@@ -41,10 +89,10 @@ impl ExperimentRunner {
             &exp.params_label(),
             exp.duration_seconds,
         );
+        Ok(handle)
     }
 
-    pub fn finish(&self, exp: &Experiment) {
-        self.ctrl.finish(&exp.id);
+    pub fn finish(&self, exp: &Experiment, phase: ExperimentPhase, reason: &str) {
         self.metrics.clear_running_info(
             &exp.id,
             &exp.kind_label(),
@@ -52,38 +100,133 @@ impl ExperimentRunner {
             exp.duration_seconds,
         );
         self.metrics.mark_experiment_finished();
+        let now = chrono::Utc::now().timestamp();
+        if let Err(e) = self.ctrl.finish(&exp.id, phase, reason, now) {
+            warn!(experiment=%exp.id, error=%format!("{e:#}"), "finish rejected");
+        }
     }
 
-    pub async fn run_to_completion(self, exp: Experiment) {
-        match exp.params {
-            ExperimentParams::Cpu { duty_percent } => {
-                let _ = crate::lib_cpu::cpu_load(
-                    exp.id.clone(),
-                    duty_percent,
-                    exp.duration_seconds,
-                    self.metrics.clone(),
-                )
-                .await;
+    /// Drives `exp` to completion on the load runtime.
+    pub fn spawn(&self, exp: Experiment, handle: ExperimentHandle) -> AnyResult<()> {
+        match load_runtime() {
+            Ok(rt) => {
+                rt.spawn(self.clone().run_to_completion(exp, handle));
+                Ok(())
             }
-            ExperimentParams::Memory { memory_mb } => {
-                let _ =
-                    crate::lib_mem::memory_load(exp.id.clone(), memory_mb, exp.duration_seconds)
-                        .await;
+            Err(e) => {
+                self.fail(&exp, format!("{e:#}"));
+                Err(e)
             }
         }
-        self.finish(&exp);
     }
 
-    pub fn stop(&self, id: &str) -> bool {
-        let mut map = self.ctrl.state.lock();
-        if let Some(st) = map.get_mut(id) {
-            st.running = false;
-            true
+    fn fail(&self, exp: &Experiment, err: String) {
+        error!(experiment=%exp.id, kind=%exp.kind_label(), error=%err, "experiment load failed");
+        self.metrics.mark_experiment_failed(&exp.kind_label());
+        self.ctrl.set_error(&exp.id, err);
+        self.finish(exp, ExperimentPhase::Failed, "load failed");
+    }
+
+    pub async fn run_to_completion(self, exp: Experiment, handle: ExperimentHandle) {
+        let mut load = tokio::spawn(run_load(
+            exp.clone(),
+            self.metrics.clone(),
+            self.ctrl.clone(),
+            handle.cancel.clone(),
+        ));
+        // The deadline runs from when the load reports running, so a slow
+        // setup does not eat into its grace.
+        let setup = tokio::time::timeout(LOAD_SETUP_LIMIT, async {
+            tokio::select! {
+                () = handle.running.cancelled() => None,
+                joined = &mut load => Some(joined),
+            }
+        })
+        .await;
+        let joined = match setup {
+            Err(_) => {
+                self.time_out(&exp, &handle, load, "load did not start in time")
+                    .await;
+                return;
+            }
+            Ok(Some(joined)) => {
+                // Ended during setup, e.g. stopped; it ran for as long as it could.
+                self.ctrl.mark_running(&exp.id);
+                joined
+            }
+            Ok(None) => {
+                let deadline =
+                    Duration::from_secs(u64::from(exp.duration_seconds)) + LOAD_DEADLINE_GRACE;
+                let Ok(joined) = tokio::time::timeout(deadline, &mut load).await else {
+                    self.time_out(&exp, &handle, load, "load exceeded its deadline")
+                        .await;
+                    return;
+                };
+                joined
+            }
+        };
+        let failure = match joined {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(format!("{e:#}")),
+            Err(e) if e.is_panic() => Some(format!("load panicked: {}", panic_message(e))),
+            Err(e) => Some(format!("load task aborted: {e}")),
+        };
+        if let Some(err) = failure {
+            self.fail(&exp, err);
+            return;
+        }
+        let (phase, reason) = if self.ctrl.phase(&exp.id) == Some(ExperimentPhase::Stopping) {
+            (ExperimentPhase::Stopped, "stopped by operator")
         } else {
-            false
+            (ExperimentPhase::Completed, "duration elapsed")
+        };
+        self.finish(&exp, phase, reason);
+    }
+
+    /// Cancels an overdue load and gives it [`LOAD_RELEASE_WAIT`] to release
+    /// what it holds before the experiment is marked `TimedOut`.
+    async fn time_out(
+        &self,
+        exp: &Experiment,
+        handle: &ExperimentHandle,
+        mut load: JoinHandle<AnyResult<()>>,
+        reason: &str,
+    ) {
+        handle.cancel.cancel();
+        if tokio::time::timeout(LOAD_RELEASE_WAIT, &mut load)
+            .await
+            .is_err()
+        {
+            warn!(experiment=%exp.id, "load ignored cancellation, aborting it");
+            load.abort();
         }
+        self.finish(exp, ExperimentPhase::TimedOut, reason);
     }
 
+    /// Cancels the load of `id` and waits up to `wait` for it to be released.
+    pub async fn stop(&self, id: &str, wait: Duration) -> StopOutcome {
+        let Some(phase) = self.ctrl.phase(id) else {
+            return StopOutcome::NotFound;
+        };
+        if phase.is_terminal() {
+            return StopOutcome::Released;
+        }
+        let Some(handle) = self.ctrl.handle(id) else {
+            return StopOutcome::Released;
+        };
+        if phase != ExperimentPhase::Stopping {
+            if let Err(e) = self.ctrl.transition(id, ExperimentPhase::Stopping) {
+                debug!(experiment=%id, error=%format!("{e:#}"), "stop raced with finish");
+            }
+        }
+        handle.cancel.cancel();
+        match tokio::time::timeout(wait, handle.released.cancelled()).await {
+            Ok(()) => StopOutcome::Released,
+            Err(_) => StopOutcome::Releasing,
+        }
+    }
+
+    #[must_use]
     pub fn status(&self, id: &str) -> Option<ExperimentState> {
         let map = self.ctrl.state.lock();
         map.get(id).cloned()
@@ -93,18 +236,34 @@ impl ExperimentRunner {
         self.metrics.encode_text()
     }
 
+    #[must_use]
     pub fn health(&self) -> HealthReport {
         let map = self.ctrl.state.lock();
-        let running_entry = map.iter().find(|(_, st)| st.running);
+        let running_entry = map.iter().find(|(_, st)| !st.phase.is_terminal());
         let running = running_entry.is_some();
         let running_id = running_entry.map(|(k, _)| k.clone());
-        let invariants_ok = map.values().all(|st| {
-            let duration = i64::from(st.total_duration_seconds);
-            let diff = st.ends_ts_seconds - st.started_ts_seconds;
-            diff == duration
-                && st.ends_ts_seconds >= st.started_ts_seconds
-                && st.remaining_seconds <= st.total_duration_seconds
-        });
+        let running_phase = running_entry.map(|(_, st)| st.phase);
+        let active = map.values().filter(|st| !st.phase.is_terminal()).count();
+        let invariants_ok = active <= 1
+            && map.values().all(|st| {
+                let duration = i64::from(st.total_duration_seconds);
+                let diff = st.ends_ts_seconds - st.started_ts_seconds;
+                let error_ok = st.phase != ExperimentPhase::Failed || st.error.is_some();
+                let ended_ok = if st.phase.is_terminal() {
+                    st.remaining_seconds == 0
+                        && st.termination_reason.is_some()
+                        && st
+                            .ended_ts_seconds
+                            .is_some_and(|ended| ended >= st.started_ts_seconds)
+                } else {
+                    st.ended_ts_seconds.is_none() && st.termination_reason.is_none()
+                };
+                diff == duration
+                    && st.ends_ts_seconds >= st.started_ts_seconds
+                    && st.remaining_seconds <= st.total_duration_seconds
+                    && ended_ok
+                    && error_ok
+            });
         let metrics_ok = self.metrics.encode_text().is_ok();
         let registry_metrics = self.metrics.registry.gather().len();
         let status = if metrics_ok && invariants_ok {
@@ -116,6 +275,7 @@ impl ExperimentRunner {
             status: status.to_string(),
             running,
             running_id,
+            running_phase,
             metrics_ok,
             registry_metrics,
             invariants_ok,
@@ -123,11 +283,314 @@ impl ExperimentRunner {
     }
 }
 
+#[allow(clippy::too_many_lines)]
+async fn run_load(
+    exp: Experiment,
+    metrics: Metrics,
+    ctrl: LoadController,
+    cancel: CancellationToken,
+) -> AnyResult<()> {
+    // Loads handed the controller report running themselves once set up;
+    // the rest have no setup to speak of.
+    let reports_running = matches!(
+        exp.params,
+        ExperimentParams::Memory { .. }
+            | ExperimentParams::NetworkProxy { .. }
+            | ExperimentParams::HttpFault { .. }
+            | ExperimentParams::UdpProxy { .. }
+            | ExperimentParams::DnsFault { .. }
+            | ExperimentParams::ProcessPause { .. }
+            | ExperimentParams::ProcessKill { .. }
+            | ExperimentParams::ProcessThrottle { .. }
+    );
+    if !reports_running {
+        ctrl.mark_running(&exp.id);
+    }
+    match exp.params {
+        ExperimentParams::Cpu {
+            duty_percent,
+            cores,
+            cpu_list,
+            closed_loop,
+            profile,
+            workload,
+        } => {
+            let spec = crate::lib_cpu::CpuLoadSpec {
+                duty_percent,
+                cores,
+                cpu_list,
+                closed_loop,
+                profile,
+                workload,
+            };
+            crate::lib_cpu::cpu_load(exp.id, spec, exp.duration_seconds, metrics, cancel).await
+        }
+        ExperimentParams::Memory {
+            memory_mb,
+            touch,
+            retouch_seconds,
+            growth,
+            backing,
+        } => {
+            let spec = crate::lib_mem::MemoryLoadSpec {
+                memory_mb,
+                touch,
+                retouch_seconds,
+                growth,
+                backing,
+            };
+            crate::lib_mem::memory_load(exp.id, spec, exp.duration_seconds, metrics, ctrl, cancel)
+                .await
+        }
+        ExperimentParams::DiskIo {
+            dir,
+            read_percent,
+            block_size_kb,
+            file_size_mb,
+            rate,
+            fsync_every,
+        } => {
+            let spec = crate::lib_disk::DiskIoSpec {
+                dir,
+                read_percent,
+                block_size_kb,
+                file_size_mb,
+                rate,
+                fsync_every,
+            };
+            crate::lib_disk::disk_io_load(exp.id, spec, exp.duration_seconds, metrics, cancel).await
+        }
+        ExperimentParams::DiskFill { path, amount } => {
+            let spec = crate::lib_disk::DiskFillSpec { path, amount };
+            crate::lib_disk::disk_fill_load(exp.id, spec, exp.duration_seconds, metrics, cancel)
+                .await
+        }
+        ExperimentParams::FdExhaustion { target, resource } => {
+            let spec = crate::lib_fd::FdLoadSpec { target, resource };
+            crate::lib_fd::fd_load(exp.id, spec, exp.duration_seconds, metrics, cancel).await
+        }
+        ExperimentParams::PidsPressure { count, mode } => {
+            let spec = crate::lib_pids::PidsLoadSpec { count, mode };
+            crate::lib_pids::pids_load(exp.id, spec, exp.duration_seconds, metrics, cancel).await
+        }
+        ExperimentParams::NetworkProxy {
+            listen,
+            upstream,
+            faults,
+        } => {
+            let spec = crate::lib_net::TcpProxySpec {
+                listen,
+                upstream,
+                faults,
+            };
+            crate::lib_net::tcp_proxy_load(
+                exp.id,
+                spec,
+                exp.duration_seconds,
+                metrics,
+                ctrl,
+                cancel,
+            )
+            .await
+        }
+        ExperimentParams::HttpFault {
+            listen,
+            upstream,
+            rules,
+        } => {
+            let spec = crate::lib_http_fault::HttpFaultSpec {
+                listen,
+                upstream,
+                rules,
+            };
+            crate::lib_http_fault::http_fault_load(
+                exp.id,
+                spec,
+                exp.duration_seconds,
+                metrics,
+                ctrl,
+                cancel,
+            )
+            .await
+        }
+        ExperimentParams::UdpProxy {
+            listen,
+            upstream,
+            faults,
+        } => {
+            let spec = crate::lib_udp::UdpProxySpec {
+                listen,
+                upstream,
+                faults,
+            };
+            crate::lib_udp::udp_proxy_load(
+                exp.id,
+                spec,
+                exp.duration_seconds,
+                metrics,
+                ctrl,
+                cancel,
+            )
+            .await
+        }
+        ExperimentParams::DnsFault {
+            listen,
+            upstream,
+            rules,
+        } => {
+            let spec = crate::lib_dns::DnsFaultSpec {
+                listen,
+                upstream,
+                rules,
+            };
+            crate::lib_dns::dns_fault_load(
+                exp.id,
+                spec,
+                exp.duration_seconds,
+                metrics,
+                ctrl,
+                cancel,
+            )
+            .await
+        }
+        ExperimentParams::ProcessPause {
+            target,
+            max_targets,
+            duty,
+        } => {
+            let spec = crate::lib_proc::ProcessPauseSpec {
+                target,
+                max_targets,
+                duty,
+            };
+            crate::lib_proc::process_pause_load(
+                exp.id,
+                spec,
+                exp.duration_seconds,
+                metrics,
+                ctrl,
+                cancel,
+            )
+            .await
+        }
+        ExperimentParams::ProcessKill {
+            target,
+            max_targets,
+            signal,
+            interval_seconds,
+            dry_run,
+        } => {
+            let spec = crate::lib_proc::ProcessKillSpec {
+                target,
+                max_targets,
+                signal,
+                interval_seconds,
+                dry_run,
+            };
+            crate::lib_proc::process_kill_load(
+                exp.id,
+                spec,
+                exp.duration_seconds,
+                metrics,
+                ctrl,
+                cancel,
+            )
+            .await
+        }
+        ExperimentParams::ProcessThrottle {
+            target,
+            max_targets,
+            cpu_percent,
+        } => {
+            let spec = crate::lib_proc::ProcessThrottleSpec {
+                target,
+                max_targets,
+                cpu_percent,
+            };
+            crate::lib_proc::process_throttle_load(
+                exp.id,
+                spec,
+                exp.duration_seconds,
+                metrics,
+                ctrl,
+                cancel,
+            )
+            .await
+        }
+        ExperimentParams::CacheThrash {
+            level,
+            size_percent,
+            access,
+            cores,
+        } => {
+            let spec = crate::lib_cache::CacheThrashSpec {
+                level,
+                size_percent,
+                access,
+                cores,
+            };
+            crate::lib_cache::cache_thrash_load(exp.id, spec, exp.duration_seconds, metrics, cancel)
+                .await
+        }
+        ExperimentParams::PageCachePressure {
+            dir,
+            file_size_mb,
+            files,
+            rate,
+        } => {
+            let spec = crate::lib_disk::PageCachePressureSpec {
+                dir,
+                file_size_mb,
+                files,
+                rate,
+            };
+            crate::lib_disk::page_cache_pressure_load(
+                exp.id,
+                spec,
+                exp.duration_seconds,
+                metrics,
+                cancel,
+            )
+            .await
+        }
+        ExperimentParams::PortExhaustion { target, ports } => {
+            let spec = crate::lib_port::PortExhaustionSpec { target, ports };
+            crate::lib_port::port_exhaustion_load(
+                exp.id,
+                spec,
+                exp.duration_seconds,
+                metrics,
+                cancel,
+            )
+            .await
+        }
+    }
+}
+
+fn panic_message(err: tokio::task::JoinError) -> String {
+    let payload = err.into_panic();
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        (*msg).to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopOutcome {
+    NotFound,
+    Released,
+    Releasing,
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct HealthReport {
     pub status: String,
     pub running: bool,
     pub running_id: Option<String>,
+    pub running_phase: Option<ExperimentPhase>,
     pub metrics_ok: bool,
     pub registry_metrics: usize,
     pub invariants_ok: bool,
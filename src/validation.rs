@@ -1,11 +1,49 @@
 #![forbid(unsafe_code)]
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
 
-use crate::domain::{ExperimentKind, StartParams, StartRequest};
-use anyhow::{bail, Result as AnyResult};
+use crate::domain::{
+    CacheLevel, CpuCores, CpuProfile, DnsFaultAction, DnsFaultRule, ExperimentKind, FdTarget,
+    FillAmount, HttpFaultAction, HttpFaultRule, IoRate, MemoryBacking, MemoryGrowth, PortTarget,
+    ProcessMatch, StartParams, StartRequest, TcpFaults, UdpFaults,
+};
+use crate::lib_cache::{data_cache, read_caches, working_set_bytes, CACHE_SYSFS};
+use crate::lib_cpu::{allowed_cpus, available_cores, plan_workers, thread_cpu_time};
+use crate::lib_disk::{check_dir, fill_target_bytes, free_bytes};
+use crate::lib_fd::{nofile_limit, FD_RESERVE};
+use crate::lib_mem::{resident_bytes, SHM_DIR};
+use crate::lib_pids::pids_headroom;
+use crate::lib_port::{ephemeral_port_range, port_target_count};
+use crate::lib_proc::{check_target_count, find_processes};
+use actix_web::http::header::HeaderName;
+use anyhow::{bail, Context, Result as AnyResult};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+/// Colon-separated directories `DISK_FILL`, `DISK_IO`, `PAGE_CACHE_PRESSURE`
+/// and `FILE` memory backings may write under; unset refuses all of them.
+pub const DISK_FILL_ALLOWLIST_ENV: &str = "CHIMP_DISK_FILL_ALLOWLIST";
+
+/// Most threads or children a `PIDS_PRESSURE` experiment may spawn.
+pub const PIDS_CEILING_ENV: &str = "CHIMP_PIDS_CEILING";
+const DEFAULT_PIDS_CEILING: u32 = 1024;
+
+/// Largest block the disk I/O hog reads or writes in one call.
+const MAX_BLOCK_SIZE_KB: u32 = 16 * 1024;
+
+/// Memory all cache hog workers together may allocate.
+const MAX_CACHE_THRASH_BYTES: u64 = 4 << 30;
+
+/// Most scratch files one `PAGE_CACHE_PRESSURE` experiment may spread over.
+const MAX_PAGE_CACHE_FILES: u32 = 64;
+
+/// Ceiling on `max_targets` for the process experiments.
+const MAX_PROCESS_TARGETS: u32 = 256;
+
+#[allow(clippy::too_many_lines)]
 pub fn validate_start(req: &StartRequest) -> AnyResult<()> {
     if req.experiment_id.trim().is_empty() {
         bail!("experiment_id is empty");
@@ -15,13 +53,702 @@ pub fn validate_start(req: &StartRequest) -> AnyResult<()> {
     }
     let kind = ExperimentKind::from_str(&req.kind)?;
     match (kind, &req.params) {
-        (ExperimentKind::CPU, StartParams::Cpu { duty_percent }) => {
+        (
+            ExperimentKind::CPU,
+            StartParams::Cpu {
+                duty_percent,
+                cores,
+                cpu_list,
+                closed_loop,
+                profile,
+                ..
+            },
+        ) => {
             if *duty_percent == 0 || *duty_percent > 100 {
                 bail!("duty_percent must be 1..=100");
             }
+            validate_cores(*cores, cpu_list.as_deref())?;
+            if *closed_loop {
+                thread_cpu_time().context("closed_loop needs per-thread cpu accounting")?;
+            }
+            validate_profile(profile)?;
+        }
+        (
+            ExperimentKind::MEMORY,
+            StartParams::Memory {
+                memory_mb,
+                growth,
+                backing,
+                ..
+            },
+        ) => {
+            validate_growth(*growth)?;
+            validate_memory_backing(backing, *memory_mb)?;
+        }
+        (
+            ExperimentKind::DISK_IO,
+            StartParams::DiskIo {
+                dir,
+                read_percent,
+                block_size_kb,
+                file_size_mb,
+                rate,
+                ..
+            },
+        ) => {
+            check_fill_path(dir, &disk_fill_allowlist())?;
+            validate_disk_io(dir, *read_percent, *block_size_kb, *file_size_mb)?;
+            validate_io_rate(*rate)?;
+        }
+        (ExperimentKind::DISK_FILL, StartParams::DiskFill { path, amount }) => {
+            check_fill_path(path, &disk_fill_allowlist())?;
+            validate_fill_amount(path, *amount)?;
+        }
+        (ExperimentKind::FD_EXHAUSTION, StartParams::FdExhaustion { target, .. }) => {
+            validate_fd_target(*target)?;
+        }
+        (ExperimentKind::PIDS_PRESSURE, StartParams::PidsPressure { count, .. }) => {
+            validate_pids_count(*count)?;
+        }
+        (
+            ExperimentKind::NETWORK_PROXY,
+            StartParams::NetworkProxy {
+                listen,
+                upstream,
+                faults,
+            },
+        ) => {
+            validate_listen(listen)?;
+            validate_host_port(upstream)?;
+            validate_tcp_faults(faults)?;
+        }
+        (
+            ExperimentKind::HTTP_FAULT,
+            StartParams::HttpFault {
+                listen,
+                upstream,
+                rules,
+            },
+        ) => {
+            validate_listen(listen)?;
+            validate_upstream_url(upstream)?;
+            validate_http_rules(rules)?;
+        }
+        (
+            ExperimentKind::UDP_PROXY,
+            StartParams::UdpProxy {
+                listen,
+                upstream,
+                faults,
+            },
+        ) => {
+            validate_listen(listen)?;
+            validate_host_port(upstream)?;
+            validate_udp_faults(faults)?;
+        }
+        (
+            ExperimentKind::DNS_FAULT,
+            StartParams::DnsFault {
+                listen,
+                upstream,
+                rules,
+            },
+        ) => {
+            validate_listen(listen)?;
+            validate_host_port(upstream)?;
+            validate_dns_rules(rules)?;
+        }
+        (
+            ExperimentKind::PROCESS_PAUSE,
+            StartParams::ProcessPause {
+                target,
+                duty,
+                max_targets,
+            },
+        ) => {
+            let found = validate_process_match(target)?;
+            validate_max_targets(target, found, *max_targets)?;
+            if let Some(duty) = duty {
+                if duty.pause_ms == 0 || duty.resume_ms == 0 {
+                    bail!("duty pause_ms and resume_ms must be > 0");
+                }
+            }
+        }
+        (
+            ExperimentKind::PROCESS_KILL,
+            StartParams::ProcessKill {
+                target,
+                max_targets,
+                interval_seconds,
+                ..
+            },
+        ) => {
+            let found = validate_process_match(target)?;
+            validate_max_targets(target, found, *max_targets)?;
+            if *interval_seconds == Some(0) {
+                bail!("interval_seconds must be > 0");
+            }
+        }
+        (
+            ExperimentKind::PROCESS_THROTTLE,
+            StartParams::ProcessThrottle {
+                target,
+                max_targets,
+                cpu_percent,
+            },
+        ) => {
+            let found = validate_process_match(target)?;
+            validate_max_targets(target, found, *max_targets)?;
+            if !(1..=99).contains(cpu_percent) {
+                bail!("cpu_percent must be in 1..=99");
+            }
+        }
+        (ExperimentKind::PORT_EXHAUSTION, StartParams::PortExhaustion { target, ports }) => {
+            validate_port_target(target, *ports)?;
+        }
+        (
+            ExperimentKind::CACHE_THRASH,
+            StartParams::CacheThrash {
+                level,
+                size_percent,
+                cores,
+                ..
+            },
+        ) => {
+            validate_cores(*cores, None)?;
+            validate_cache_thrash(*level, *size_percent, *cores)?;
+        }
+        (
+            ExperimentKind::PAGE_CACHE_PRESSURE,
+            StartParams::PageCachePressure {
+                dir,
+                file_size_mb,
+                files,
+                rate,
+            },
+        ) => {
+            check_fill_path(dir, &disk_fill_allowlist())?;
+            validate_page_cache_files(dir, *file_size_mb, *files)?;
+            validate_io_rate(*rate)?;
         }
-        (ExperimentKind::MEMORY, StartParams::Memory { memory_mb: _ }) => {}
         _ => bail!("kind and params mismatch"),
     }
     Ok(())
 }
+
+fn validate_cache_thrash(level: CacheLevel, size_percent: u32, cores: CpuCores) -> AnyResult<()> {
+    if !(10..=1000).contains(&size_percent) {
+        bail!("size_percent must be 10..=1000");
+    }
+    let caches = read_caches(Path::new(CACHE_SYSFS))?;
+    let Some(cache) = data_cache(&caches, level) else {
+        bail!("this host reports no {level} data cache in {CACHE_SYSFS}");
+    };
+    let workers = plan_workers(cores, None).len() as u64;
+    let total = working_set_bytes(cache, size_percent).saturating_mul(workers);
+    if total > MAX_CACHE_THRASH_BYTES {
+        bail!(
+            "{workers} workers x {size_percent}% of {level} need {total} bytes, more than {MAX_CACHE_THRASH_BYTES}"
+        );
+    }
+    Ok(())
+}
+
+fn validate_cores(cores: CpuCores, cpu_list: Option<&[usize]>) -> AnyResult<()> {
+    let limit = cpu_list.map_or_else(available_cores, <[usize]>::len);
+    if let CpuCores::Count(n) = cores {
+        if n == 0 {
+            bail!("cores must be > 0");
+        }
+        if n as usize > limit {
+            bail!("cores must be <= {limit}");
+        }
+    }
+    let Some(list) = cpu_list else {
+        return Ok(());
+    };
+    if list.is_empty() {
+        bail!("cpu_list must not be empty");
+    }
+    let mut seen = HashSet::new();
+    if let Some(dup) = list.iter().find(|id| !seen.insert(**id)) {
+        bail!("cpu_list contains cpu {dup} twice");
+    }
+    if let Some(allowed) = allowed_cpus() {
+        if let Some(id) = list.iter().find(|id| !allowed.contains(id)) {
+            bail!("cpu {id} is not available to the agent");
+        }
+    }
+    Ok(())
+}
+
+fn validate_profile(profile: &CpuProfile) -> AnyResult<()> {
+    match profile {
+        CpuProfile::Constant => {}
+        CpuProfile::Ramp {
+            from_percent,
+            ramp_seconds,
+        } => {
+            if *from_percent > 100 {
+                bail!("from_percent must be 0..=100");
+            }
+            if *ramp_seconds == 0 {
+                bail!("ramp_seconds must be > 0");
+            }
+        }
+        CpuProfile::Steps {
+            percents,
+            step_seconds,
+        } => {
+            if percents.is_empty() {
+                bail!("percents must not be empty");
+            }
+            if percents.iter().any(|p| *p > 100) {
+                bail!("percents must be 0..=100");
+            }
+            if *step_seconds == 0 {
+                bail!("step_seconds must be > 0");
+            }
+        }
+        CpuProfile::Sine {
+            period_seconds,
+            amplitude_percent,
+        } => {
+            if *period_seconds < 2 {
+                bail!("period_seconds must be >= 2");
+            }
+            if *amplitude_percent > 100 {
+                bail!("amplitude_percent must be 0..=100");
+            }
+        }
+        CpuProfile::Square {
+            period_seconds,
+            low_percent,
+        } => {
+            if *period_seconds < 2 {
+                bail!("period_seconds must be >= 2");
+            }
+            if *low_percent > 100 {
+                bail!("low_percent must be 0..=100");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_growth(growth: MemoryGrowth) -> AnyResult<()> {
+    match growth {
+        MemoryGrowth::Instant => {}
+        MemoryGrowth::Ramp { ramp_seconds } => {
+            if ramp_seconds == 0 {
+                bail!("ramp_seconds must be > 0");
+            }
+        }
+        MemoryGrowth::Leak { rate_mb_per_second } => {
+            if rate_mb_per_second == 0 {
+                bail!("rate_mb_per_second must be > 0");
+            }
+        }
+        MemoryGrowth::Step {
+            step_mb,
+            step_seconds,
+        } => {
+            if step_mb == 0 || step_seconds == 0 {
+                bail!("step_mb and step_seconds must be > 0");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_memory_backing(backing: &MemoryBacking, memory_mb: u32) -> AnyResult<()> {
+    let bytes = u64::from(memory_mb) << 20;
+    let check_free = |dir: &str| -> AnyResult<()> {
+        let free = free_bytes(dir)?;
+        if bytes > free {
+            bail!(
+                "{backing} backing needs {memory_mb} MiB but {dir} has {} MiB free",
+                free >> 20
+            );
+        }
+        Ok(())
+    };
+    match backing {
+        MemoryBacking::Anonymous => {}
+        MemoryBacking::Shm => {
+            check_dir(SHM_DIR).context("SHM backing needs a tmpfs mounted at /dev/shm")?;
+            check_free(SHM_DIR)?;
+        }
+        MemoryBacking::File { dir } => {
+            check_fill_path(dir, &disk_fill_allowlist())?;
+            check_free(dir)?;
+        }
+        MemoryBacking::Mlocked => {
+            // The lock pins the agent's own pages as well as the ballast, so
+            // RLIMIT_MEMLOCK bounds it even when CAP_IPC_LOCK would not.
+            let needed = bytes + resident_bytes()?;
+            let limit = rustix::process::getrlimit(rustix::process::Resource::Memlock).current;
+            if let Some(limit) = limit.filter(|&l| l < needed) {
+                bail!(
+                    "MLOCKED backing locks the whole agent and needs an RLIMIT_MEMLOCK of at least {} MiB, not {} KiB",
+                    needed.div_ceil(1 << 20),
+                    limit >> 10
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_disk_io(
+    dir: &str,
+    read_percent: u32,
+    block_size_kb: u32,
+    file_size_mb: u32,
+) -> AnyResult<()> {
+    if read_percent > 100 {
+        bail!("read_percent must be 0..=100");
+    }
+    if block_size_kb == 0 || block_size_kb > MAX_BLOCK_SIZE_KB {
+        bail!("block_size_kb must be 1..={MAX_BLOCK_SIZE_KB}");
+    }
+    if file_size_mb == 0 || u64::from(file_size_mb) * 1024 < u64::from(block_size_kb) {
+        bail!("file_size_mb must be > 0 and hold at least one block");
+    }
+    let free = free_bytes(dir)?;
+    if u64::from(file_size_mb) << 20 > free {
+        bail!(
+            "file_size_mb {file_size_mb} exceeds the {} MiB free under {dir}",
+            free >> 20
+        );
+    }
+    Ok(())
+}
+
+fn validate_page_cache_files(dir: &str, file_size_mb: u32, files: u32) -> AnyResult<()> {
+    if file_size_mb == 0 {
+        bail!("file_size_mb must be > 0");
+    }
+    if files == 0 || files > MAX_PAGE_CACHE_FILES {
+        bail!("files must be 1..={MAX_PAGE_CACHE_FILES}");
+    }
+    let total = (u64::from(file_size_mb) * u64::from(files)) << 20;
+    let free = free_bytes(dir)?;
+    if total > free {
+        bail!(
+            "{files} files of {file_size_mb} MiB exceed the {} MiB free under {dir}",
+            free >> 20
+        );
+    }
+    Ok(())
+}
+
+fn validate_io_rate(rate: IoRate) -> AnyResult<()> {
+    match rate {
+        IoRate::Unlimited => {}
+        IoRate::Throughput { mb_per_second } => {
+            if mb_per_second == 0 {
+                bail!("mb_per_second must be > 0");
+            }
+        }
+        IoRate::Iops { ops_per_second } => {
+            if ops_per_second == 0 {
+                bail!("ops_per_second must be > 0");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_fill_amount(path: &str, amount: FillAmount) -> AnyResult<()> {
+    match amount {
+        FillAmount::Size { size_mb } => {
+            if size_mb == 0 {
+                bail!("size_mb must be > 0");
+            }
+            let free = free_bytes(path)?;
+            if fill_target_bytes(amount, free) > free {
+                bail!("size_mb exceeds the {} MiB free under {path}", free >> 20);
+            }
+        }
+        FillAmount::FreePercent { percent } => {
+            if percent == 0 || percent > 100 {
+                bail!("percent must be 1..=100");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_fd_target(target: FdTarget) -> AnyResult<()> {
+    match (target, nofile_limit()) {
+        (FdTarget::Count { count }, limit) => {
+            if count == 0 {
+                bail!("count must be > 0");
+            }
+            if let Some(limit) = limit.filter(|l| count > l.saturating_sub(FD_RESERVE)) {
+                bail!(
+                    "count must be <= RLIMIT_NOFILE ({limit}) minus the {FD_RESERVE} descriptors the agent keeps"
+                );
+            }
+        }
+        (FdTarget::LimitPercent { percent }, limit) => {
+            if percent == 0 || percent > 100 {
+                bail!("percent must be 1..=100");
+            }
+            match limit {
+                None => bail!("RLIMIT_NOFILE is unlimited, use an absolute count"),
+                Some(limit) if limit <= FD_RESERVE => bail!(
+                    "RLIMIT_NOFILE ({limit}) leaves nothing above the {FD_RESERVE} descriptors the agent keeps"
+                ),
+                Some(_) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Only loopback targets, so the experiment exhausts this host's ports and
+/// never floods a remote service with connections.
+fn validate_port_target(target: &str, ports: PortTarget) -> AnyResult<()> {
+    let addr: SocketAddr = target
+        .parse()
+        .with_context(|| format!("target must be ip:port, got {target}"))?;
+    if !addr.ip().is_loopback() || addr.port() == 0 {
+        bail!("target must be a loopback address with a port, got {target}");
+    }
+    let (low, high) = ephemeral_port_range()?;
+    let range = u32::from(high - low) + 1;
+    match ports {
+        PortTarget::Count { count } => {
+            if count == 0 || count > range {
+                bail!("count must be 1..={range}, the ephemeral range {low}-{high}");
+            }
+        }
+        PortTarget::RangePercent { percent } => {
+            if percent == 0 || percent > 100 {
+                bail!("percent must be 1..=100");
+            }
+        }
+    }
+    // One descriptor per connection, and the agent keeps its reserve.
+    let wanted = port_target_count(ports, (low, high)) as u64;
+    if let Some(limit) = nofile_limit().filter(|l| wanted > l.saturating_sub(FD_RESERVE)) {
+        bail!(
+            "{wanted} connections exceed RLIMIT_NOFILE ({limit}) minus the {FD_RESERVE} descriptors the agent keeps"
+        );
+    }
+    Ok(())
+}
+
+fn validate_pids_count(count: u32) -> AnyResult<()> {
+    let ceiling = pids_ceiling();
+    if count == 0 || count > ceiling {
+        bail!("count must be 1..={ceiling}");
+    }
+    if let Some(free) = pids_headroom().filter(|free| u64::from(count) > *free) {
+        bail!("count exceeds the {free} tasks left under the cgroup pids.max");
+    }
+    Ok(())
+}
+
+fn validate_listen(listen: &str) -> AnyResult<()> {
+    listen
+        .parse::<SocketAddr>()
+        .with_context(|| format!("listen must be ip:port, got {listen}"))?;
+    Ok(())
+}
+
+/// Accepts `host:port` (or `[v6]:port`) without resolving the host.
+fn validate_host_port(addr: &str) -> AnyResult<()> {
+    let Some((host, port)) = addr.rsplit_once(':') else {
+        bail!("{addr} must be host:port");
+    };
+    if host.trim_matches(['[', ']']).is_empty() {
+        bail!("{addr} has no host");
+    }
+    port.parse::<u16>()
+        .with_context(|| format!("{addr} has no valid port"))?;
+    Ok(())
+}
+
+fn validate_tcp_faults(faults: &TcpFaults) -> AnyResult<()> {
+    if faults.reset_percent > 100 || faults.blackhole_percent > 100 {
+        bail!("reset_percent and blackhole_percent must be 0..=100");
+    }
+    if faults.reset_percent + faults.blackhole_percent > 100 {
+        bail!("reset_percent + blackhole_percent must be <= 100");
+    }
+    Ok(())
+}
+
+fn validate_udp_faults(faults: &UdpFaults) -> AnyResult<()> {
+    for (name, percent) in [
+        ("loss_percent", faults.loss_percent),
+        ("duplicate_percent", faults.duplicate_percent),
+        ("reorder_percent", faults.reorder_percent),
+    ] {
+        if percent > 100 {
+            bail!("{name} must be 0..=100");
+        }
+    }
+    Ok(())
+}
+
+fn validate_dns_rules(rules: &[DnsFaultRule]) -> AnyResult<()> {
+    for rule in rules {
+        let name = rule.name.trim_end_matches('.');
+        let name = name.strip_prefix("*.").unwrap_or(name);
+        if name.is_empty() || name.len() > 253 {
+            bail!("rule name {:?} must be 1..=253 bytes", rule.name);
+        }
+        let label_ok = |label: &str| {
+            (1..=63).contains(&label.len())
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        };
+        if !name.split('.').all(label_ok) {
+            bail!("rule name {:?} is not a DNS name or *.suffix", rule.name);
+        }
+        if rule.probability_percent > 100 {
+            bail!("rule {}: probability_percent must be 0..=100", rule.name);
+        }
+        if let DnsFaultAction::Address { addresses, .. } = &rule.action {
+            if addresses.is_empty() {
+                bail!("rule {}: ADDRESS needs at least one address", rule.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the matcher and that it selects at least one process right now,
+/// returning how many it does.
+fn validate_process_match(target: &ProcessMatch) -> AnyResult<usize> {
+    match target {
+        ProcessMatch::Pid { pid } => {
+            // Stopping or killing init, or the agent itself, takes down more
+            // than the experiment could ever restore.
+            if *pid <= 1 || *pid == std::process::id() {
+                bail!("pid {pid} cannot be targeted");
+            }
+        }
+        ProcessMatch::Name { name } => {
+            if name.is_empty() || name.len() > 15 {
+                bail!("name must be 1..=15 bytes, as in /proc/<pid>/comm");
+            }
+        }
+        ProcessMatch::Cmdline { contains } => {
+            if contains.trim().is_empty() {
+                bail!("cmdline contains is empty");
+            }
+        }
+        ProcessMatch::Exe { name } => {
+            if name.is_empty() || name.contains('/') {
+                bail!("exe must be a file name without '/'");
+            }
+        }
+        ProcessMatch::CmdlineRegex { regex } => {
+            if regex.is_empty() {
+                bail!("cmdline regex is empty");
+            }
+        }
+        ProcessMatch::Cgroup { path } => {
+            // The root cgroup would select every process on the host.
+            if !path.starts_with('/') || path.trim_end_matches('/').is_empty() {
+                bail!("cgroup path must be absolute and below the root, got {path}");
+            }
+        }
+    }
+    // Also rejects a regex that does not compile.
+    let found = find_processes(target)?.len();
+    if found == 0 {
+        bail!("no process matches {target}");
+    }
+    Ok(found)
+}
+
+/// Checks `max_targets` and that the `found` processes fit within it.
+fn validate_max_targets(target: &ProcessMatch, found: usize, max_targets: u32) -> AnyResult<()> {
+    if max_targets == 0 || max_targets > MAX_PROCESS_TARGETS {
+        bail!("max_targets must be 1..={MAX_PROCESS_TARGETS}");
+    }
+    check_target_count(target, found, max_targets)
+}
+
+fn validate_upstream_url(upstream: &str) -> AnyResult<()> {
+    let url = reqwest::Url::parse(upstream)
+        .with_context(|| format!("upstream must be a URL, got {upstream}"))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        bail!("upstream must be an http(s) URL with a host, got {upstream}");
+    }
+    Ok(())
+}
+
+fn validate_http_rules(rules: &[HttpFaultRule]) -> AnyResult<()> {
+    let mut names = HashSet::new();
+    for rule in rules {
+        if rule.name.trim().is_empty() {
+            bail!("rule name is empty");
+        }
+        if !names.insert(rule.name.as_str()) {
+            bail!("duplicate rule name {}", rule.name);
+        }
+        if rule.probability_percent > 100 {
+            bail!("rule {}: probability_percent must be 0..=100", rule.name);
+        }
+        if let Some(method) = &rule.method {
+            reqwest::Method::from_bytes(method.as_bytes())
+                .with_context(|| format!("rule {}: invalid method {method}", rule.name))?;
+        }
+        if let Some(header) = &rule.header {
+            HeaderName::from_bytes(header.name.as_bytes()).with_context(|| {
+                format!("rule {}: invalid header name {}", rule.name, header.name)
+            })?;
+        }
+        if let HttpFaultAction::Status { status } = rule.action {
+            if !(100..=599).contains(&status) {
+                bail!("rule {}: status must be 100..=599", rule.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Ceiling from [`PIDS_CEILING_ENV`], or a conservative default when unset.
+#[must_use]
+pub fn pids_ceiling() -> u32 {
+    std::env::var(PIDS_CEILING_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_PIDS_CEILING)
+}
+
+/// Directories named by [`DISK_FILL_ALLOWLIST_ENV`].
+#[must_use]
+pub fn disk_fill_allowlist() -> Vec<PathBuf> {
+    std::env::var(DISK_FILL_ALLOWLIST_ENV)
+        .unwrap_or_default()
+        .split(':')
+        .filter(|p| !p.trim().is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Accepts an existing directory at or below one of `allowlist`, comparing
+/// canonical paths so `..` and symlinks cannot escape it.
+pub fn check_fill_path(path: &str, allowlist: &[PathBuf]) -> AnyResult<()> {
+    check_dir(path)?;
+    let target = Path::new(path)
+        .canonicalize()
+        .with_context(|| format!("resolve {path}"))?;
+    let allowed = allowlist
+        .iter()
+        .filter_map(|p| p.canonicalize().ok())
+        .any(|root| target.starts_with(root));
+    if !allowed {
+        bail!("{path} is outside the disk-fill allowlist");
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+//! Kept apart from `http_integration`: the hog here takes every descriptor
+//! the process may open except the agent's reserve, which would starve any
+//! test running alongside it.
+
+use chimp_chaos_agent::lib_fd::{nofile_limit, open_fd_count, FD_RESERVE};
+use chimp_chaos_agent::serve_listener;
+use rustix::process::{getrlimit, setrlimit, Resource, Rlimit};
+use std::time::{Duration, Instant};
+
+#[actix_web::test]
+async fn healthz_responds_while_fd_hog_is_at_cap() {
+    // A modest soft limit keeps the hog quick to reach its cap.
+    let limit = getrlimit(Resource::Nofile);
+    let soft = limit.current.map_or(4096, |c| c.min(4096));
+    setrlimit(
+        Resource::Nofile,
+        Rlimit {
+            current: Some(soft),
+            maximum: limit.maximum,
+        },
+    )
+    .unwrap();
+    assert_eq!(nofile_limit(), Some(soft));
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let server = actix_web::rt::spawn(serve_listener(listener));
+    let client = || {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(2))
+            .build()
+            .unwrap()
+    };
+
+    let body = serde_json::json!({
+        "experiment_id":"fds",
+        "kind":"FD_EXHAUSTION",
+        "duration_seconds":30,
+        "params": {"type":"FD_EXHAUSTION", "target":{"mode":"LIMIT_PERCENT","percent":100}}
+    });
+    let resp = client()
+        .post(format!("{base}/experiments"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    // The hog tops up to the cap counting the start request's sockets, which
+    // have closed since, so allow for a few descriptors less.
+    let cap = soft - FD_RESERVE - 8;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while open_fd_count().unwrap() < cap {
+        assert!(
+            Instant::now() < deadline,
+            "hog never reached {cap} descriptors"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    // Fresh clients, so every probe needs a new connection accepted.
+    for _ in 0..5 {
+        let resp = client()
+            .get(format!("{base}/healthz"))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    let resp = client()
+        .post(format!("{base}/experiments/fds/stop"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    server.abort();
+}
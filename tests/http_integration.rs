@@ -4,8 +4,9 @@
 
 use actix_web::{test, App};
 use chimp_chaos_agent::{
-    healthz, scrape_metrics, start, status, stop, AppState, LoadController, Metrics,
+    healthz, scrape_metrics, serve_listener, start, status, stop, AppState, LoadController, Metrics,
 };
+use std::time::{Duration, Instant};
 
 #[actix_web::test]
 async fn start_stop_and_metrics() {
@@ -68,3 +69,223 @@ async fn start_stop_and_metrics() {
     let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
 }
+
+#[actix_web::test]
+async fn stop_releases_running_load() {
+    let state = AppState {
+        ctrl: LoadController::default(),
+        metrics: Metrics::new().unwrap(),
+    };
+    let metrics = state.metrics.clone();
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(state))
+            .service(start)
+            .service(stop)
+            .service(status),
+    )
+    .await;
+
+    let body = serde_json::json!({
+        "experiment_id":"long",
+        "kind":"MEMORY",
+        "duration_seconds":600,
+        "params": {"type":"MEMORY", "memory_mb":1}
+    });
+    let req = test::TestRequest::post()
+        .uri("/experiments")
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(metrics.experiment_active.get(), 1);
+
+    let req = test::TestRequest::get()
+        .uri("/experiments/long/status")
+        .to_request();
+    let st: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(st["phase"] == "PENDING" || st["phase"] == "RUNNING");
+    assert!(st["ended_ts_seconds"].is_null());
+
+    let req = test::TestRequest::post()
+        .uri("/experiments/long/stop")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::OK);
+    assert_eq!(metrics.experiment_active.get(), 0);
+
+    let req = test::TestRequest::get()
+        .uri("/experiments/long/status")
+        .to_request();
+    let st: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(st["phase"], "STOPPED");
+    assert_eq!(st["termination_reason"], "stopped by operator");
+    assert!(st["ended_ts_seconds"].is_i64());
+
+    let req = test::TestRequest::post()
+        .uri("/experiments/missing/stop")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn load_failure_is_recorded() {
+    let state = AppState {
+        ctrl: LoadController::default(),
+        metrics: Metrics::new().unwrap(),
+    };
+    let metrics = state.metrics.clone();
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(state))
+            .service(start)
+            .service(status),
+    )
+    .await;
+
+    let body = serde_json::json!({
+        "experiment_id":"huge",
+        "kind":"MEMORY",
+        "duration_seconds":5,
+        "params": {"type":"MEMORY", "memory_mb":u32::MAX}
+    });
+    let req = test::TestRequest::post()
+        .uri("/experiments")
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let mut st = serde_json::Value::Null;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let req = test::TestRequest::get()
+            .uri("/experiments/huge/status")
+            .to_request();
+        st = test::call_and_read_body_json(&app, req).await;
+        if st["phase"] == "FAILED" {
+            break;
+        }
+    }
+    assert_eq!(st["phase"], "FAILED");
+    assert!(st["error"].as_str().unwrap().contains("allocate"));
+    assert_eq!(
+        metrics
+            .experiment_failures_total
+            .with_label_values(&["MEMORY"])
+            .get(),
+        1
+    );
+}
+
+#[actix_web::test]
+async fn healthz_responsive_under_full_cpu_load() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let server = actix_web::rt::spawn(serve_listener(listener));
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+        .unwrap();
+
+    let body = serde_json::json!({
+        "experiment_id":"busy",
+        "kind":"CPU",
+        "duration_seconds":30,
+        "params": {"type":"CPU", "duty_percent":100, "cores":"all"}
+    });
+    let resp = client
+        .post(format!("{base}/experiments"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    for _ in 0..10 {
+        let sent = Instant::now();
+        let resp = client.get(format!("{base}/healthz")).send().await.unwrap();
+        assert!(resp.status().is_success());
+        assert!(sent.elapsed() < Duration::from_secs(1));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let resp = client
+        .post(format!("{base}/experiments/busy/stop"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    server.abort();
+}
+
+#[actix_web::test]
+async fn network_proxy_start_stop() {
+    let echo = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = echo.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        for mut sock in echo.incoming().flatten() {
+            std::thread::spawn(move || {
+                let mut rd = sock.try_clone().unwrap();
+                let _ = std::io::copy(&mut rd, &mut sock);
+            });
+        }
+    });
+    let state = AppState {
+        ctrl: LoadController::default(),
+        metrics: Metrics::new().unwrap(),
+    };
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(state))
+            .service(start)
+            .service(stop)
+            .service(status),
+    )
+    .await;
+
+    let body = serde_json::json!({
+        "experiment_id":"proxy",
+        "kind":"NETWORK_PROXY",
+        "duration_seconds":600,
+        "params": {"type":"NETWORK_PROXY", "listen":"127.0.0.1:0", "upstream":upstream, "latency_ms":20}
+    });
+    let req = test::TestRequest::post()
+        .uri("/experiments")
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let addr = loop {
+        let req = test::TestRequest::get()
+            .uri("/experiments/proxy/status")
+            .to_request();
+        let st: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        if let Some(addr) = st["listen_addr"].as_str() {
+            break addr.to_string();
+        }
+        assert!(Instant::now() < deadline, "proxy never bound: {st}");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    };
+    let echoed = tokio::task::spawn_blocking(move || {
+        use std::io::{Read, Write};
+        let mut sock = std::net::TcpStream::connect(addr).unwrap();
+        sock.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 4];
+        sock.read_exact(&mut buf).unwrap();
+        buf
+    })
+    .await
+    .unwrap();
+    assert_eq!(&echoed, b"ping");
+
+    let req = test::TestRequest::post()
+        .uri("/experiments/proxy/stop")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::OK);
+}
@@ -0,0 +1,61 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+//! Kept apart from `unit_port`: the soft descriptor limit lowered here would
+//! starve any test running alongside it.
+
+use chimp_chaos_agent::domain::PortTarget;
+use chimp_chaos_agent::lib_fd::{open_fd_count, FD_RESERVE};
+use chimp_chaos_agent::lib_port::{port_exhaustion_load, PortExhaustionSpec};
+use chimp_chaos_agent::metrics::Metrics;
+use rustix::process::{getrlimit, setrlimit, Resource, Rlimit};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn port_hog_stops_at_the_descriptor_reserve() {
+    // Never accepted, so the peers sit in the backlog without descriptors.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let target = listener.local_addr().unwrap().to_string();
+    let room = 40;
+    let limit = getrlimit(Resource::Nofile);
+    let soft = open_fd_count().unwrap() + FD_RESERVE + room;
+    setrlimit(
+        Resource::Nofile,
+        Rlimit {
+            current: Some(soft),
+            maximum: limit.maximum,
+        },
+    )
+    .unwrap();
+
+    let m = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(port_exhaustion_load(
+        "e".into(),
+        PortExhaustionSpec {
+            target,
+            ports: PortTarget::Count { count: 100 },
+        },
+        30,
+        m.clone(),
+        cancel.clone(),
+    ));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while m.port_exhaustion_held.get() == 0 {
+        assert!(Instant::now() < deadline, "hog never reported held ports");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let held = m.port_exhaustion_held.get();
+    assert!(held <= i64::try_from(room).unwrap(), "held={held}");
+    assert!(open_fd_count().unwrap() <= soft - FD_RESERVE);
+    assert_eq!(
+        m.port_exhaustion_connect_errors_total
+            .with_label_values(&["fd_limit"])
+            .get(),
+        0
+    );
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+}
@@ -0,0 +1,129 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+use chimp_chaos_agent::domain::{CacheAccess, CacheLevel, CpuCores};
+use chimp_chaos_agent::lib_cache::{
+    cache_thrash_load, data_cache, parse_cache_size, read_caches, visit_order, working_set_bytes,
+    CacheInfo, CacheThrashSpec, CACHE_SYSFS,
+};
+use chimp_chaos_agent::metrics::Metrics;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// Fake sysfs cache directory with `(level, type, size)` per index.
+fn fake_sysfs(entries: &[(u8, &str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chimp-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (i, (level, kind, size)) in entries.iter().enumerate() {
+        let index = dir.join(format!("index{i}"));
+        std::fs::create_dir_all(&index).unwrap();
+        std::fs::write(index.join("level"), format!("{level}\n")).unwrap();
+        std::fs::write(index.join("type"), format!("{kind}\n")).unwrap();
+        std::fs::write(index.join("size"), format!("{size}\n")).unwrap();
+        std::fs::write(index.join("coherency_line_size"), "64\n").unwrap();
+    }
+    std::fs::write(dir.join("uevent"), "").unwrap();
+    dir
+}
+
+#[test]
+fn sysfs_sizes_parse() {
+    assert_eq!(parse_cache_size("48K").unwrap(), 48 << 10);
+    assert_eq!(parse_cache_size("32M\n").unwrap(), 32 << 20);
+    assert_eq!(parse_cache_size("4096").unwrap(), 4096);
+    assert!(parse_cache_size("12X").is_err());
+    assert!(parse_cache_size("K").is_err());
+}
+
+#[test]
+fn picks_data_caches_by_level() {
+    let dir = fake_sysfs(&[
+        (1, "Data", "48K"),
+        (1, "Instruction", "32K"),
+        (2, "Unified", "2048K"),
+    ]);
+    let caches = read_caches(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(caches.len(), 3);
+    let l1 = data_cache(&caches, CacheLevel::L1).unwrap();
+    assert_eq!((l1.kind.as_str(), l1.size_bytes), ("Data", 48 << 10));
+    assert_eq!(
+        data_cache(&caches, CacheLevel::L2).unwrap().size_bytes,
+        2 << 20
+    );
+    assert!(data_cache(&caches, CacheLevel::L3).is_none());
+}
+
+#[test]
+fn working_set_rounds_to_lines() {
+    let cache = CacheInfo {
+        level: 2,
+        kind: "Unified".into(),
+        size_bytes: 1000,
+        line_bytes: 64,
+    };
+    assert_eq!(working_set_bytes(&cache, 100), 1024);
+    assert_eq!(working_set_bytes(&cache, 200), 2048);
+}
+
+#[test]
+fn every_pattern_visits_each_line_once() {
+    for access in [
+        CacheAccess::Sequential,
+        CacheAccess::Strided,
+        CacheAccess::Random,
+    ] {
+        let order = visit_order(1000, 64, access);
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..1000).collect::<Vec<u32>>(), "{access}");
+    }
+    assert_eq!(
+        visit_order(1000, 64, CacheAccess::Sequential)[..3],
+        [0, 1, 2]
+    );
+    // 64 lines per 4 KiB page: the first line of every page comes first.
+    assert_eq!(
+        visit_order(1000, 64, CacheAccess::Strided)[..3],
+        [0, 64, 128]
+    );
+    assert_ne!(
+        visit_order(1000, 64, CacheAccess::Random),
+        visit_order(1000, 64, CacheAccess::Sequential)
+    );
+}
+
+#[tokio::test]
+async fn reports_bandwidth_and_clears_gauges() {
+    let caches = read_caches(Path::new(CACHE_SYSFS)).expect("host cache topology");
+    let expected = working_set_bytes(data_cache(&caches, CacheLevel::L1).unwrap(), 200);
+    let metrics = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(cache_thrash_load(
+        "c".into(),
+        CacheThrashSpec {
+            level: CacheLevel::L1,
+            size_percent: 200,
+            access: CacheAccess::Random,
+            cores: CpuCores::Count(1),
+        },
+        30,
+        metrics.clone(),
+        cancel.clone(),
+    ));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while metrics.cache_thrash_bandwidth_bytes.get() == 0 {
+        assert!(Instant::now() < deadline, "no bandwidth reported");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(
+        metrics.cache_thrash_working_set_bytes.get(),
+        i64::try_from(expected).unwrap()
+    );
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(metrics.cache_thrash_bandwidth_bytes.get(), 0);
+    assert_eq!(metrics.cache_thrash_working_set_bytes.get(), 0);
+}
@@ -2,17 +2,658 @@
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
 
+use chimp_chaos_agent::domain::{
+    CpuCores, CpuProfile, CpuWorkload, LoadController, MemoryBacking, MemoryGrowth, TouchPattern,
+};
+use chimp_chaos_agent::lib_cpu::{plan_workers, CpuLoadSpec};
+use chimp_chaos_agent::lib_disk::scratch_path;
+use chimp_chaos_agent::lib_mem::{
+    growth_target_bytes, memory_load, touch_pages, zeroed_chunks, MemoryLoadSpec, HEAP_CHUNK,
+    SHM_DIR,
+};
+use chimp_chaos_agent::metrics::Metrics;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+fn mem_spec(memory_mb: u32) -> MemoryLoadSpec {
+    MemoryLoadSpec {
+        memory_mb,
+        touch: TouchPattern::default(),
+        retouch_seconds: 10,
+        growth: MemoryGrowth::Instant,
+        backing: MemoryBacking::Anonymous,
+    }
+}
+
+fn spec(duty_percent: u32, cores: CpuCores, cpu_list: Option<Vec<usize>>) -> CpuLoadSpec {
+    CpuLoadSpec {
+        duty_percent,
+        cores,
+        cpu_list,
+        closed_loop: false,
+        profile: CpuProfile::Constant,
+        workload: CpuWorkload::Spin,
+    }
+}
+
 #[tokio::test]
 async fn cpu_runs() {
     let m = chimp_chaos_agent::metrics::Metrics::new().expect("metrics");
-    chimp_chaos_agent::lib_cpu::cpu_load("e".into(), 10, 1, m)
-        .await
-        .expect("ok");
+    chimp_chaos_agent::lib_cpu::cpu_load(
+        "e".into(),
+        spec(10, CpuCores::default(), None),
+        1,
+        m,
+        CancellationToken::new(),
+    )
+    .await
+    .expect("ok");
 }
 
 #[tokio::test]
 async fn mem_runs() {
-    chimp_chaos_agent::lib_mem::memory_load("e".into(), 1, 1)
-        .await
-        .expect("ok");
+    memory_load(
+        "e".into(),
+        mem_spec(1),
+        1,
+        Metrics::new().expect("metrics"),
+        LoadController::default(),
+        CancellationToken::new(),
+    )
+    .await
+    .expect("ok");
+}
+
+#[tokio::test]
+async fn cpu_stops_on_cancel() {
+    let m = chimp_chaos_agent::metrics::Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let canceller = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        canceller.cancel();
+    });
+    let started = Instant::now();
+    chimp_chaos_agent::lib_cpu::cpu_load(
+        "e".into(),
+        spec(50, CpuCores::All, None),
+        60,
+        m.clone(),
+        cancel,
+    )
+    .await
+    .expect("ok");
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(m.cpu_hog_active.get(), 0);
+    assert_eq!(m.cpu_hog_cores.get(), 0);
+}
+
+#[tokio::test]
+async fn cpu_pinned_workers_report_per_core() {
+    let m = chimp_chaos_agent::metrics::Metrics::new().expect("metrics");
+    let cpu = chimp_chaos_agent::lib_cpu::allowed_cpus()
+        .and_then(|ids| ids.first().copied())
+        .unwrap_or(0);
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(chimp_chaos_agent::lib_cpu::cpu_load(
+        "e".into(),
+        spec(20, CpuCores::Count(2), Some(vec![cpu])),
+        60,
+        m.clone(),
+        cancel.clone(),
+    ));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(m.cpu_hog_cores.get(), 2);
+    let text = String::from_utf8(m.encode_text().expect("encode")).expect("utf8");
+    assert!(text.contains(&format!(
+        "agent_cpu_hog_core_duty_percent{{cpu=\"{cpu}\",worker=\"1\"}} 20"
+    )));
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    let text = String::from_utf8(m.encode_text().expect("encode")).expect("utf8");
+    assert!(!text.contains("agent_cpu_hog_core_duty_percent{"));
+}
+
+#[tokio::test]
+async fn failed_worker_stops_the_rest() {
+    let cpu = chimp_chaos_agent::lib_cpu::allowed_cpus()
+        .and_then(|ids| ids.first().copied())
+        .unwrap_or(0);
+    let started = Instant::now();
+    let err = chimp_chaos_agent::lib_cpu::cpu_load(
+        "e".into(),
+        spec(20, CpuCores::Count(2), Some(vec![cpu, 1023])),
+        30,
+        Metrics::new().expect("metrics"),
+        CancellationToken::new(),
+    )
+    .await
+    .unwrap_err();
+    assert!(format!("{err:#}").contains("pin cpu worker 1"), "{err:#}");
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn plan_workers_round_robin() {
+    assert_eq!(
+        plan_workers(CpuCores::Count(3), None),
+        vec![None, None, None]
+    );
+    assert_eq!(
+        plan_workers(CpuCores::Count(3), Some(&[4, 5])),
+        vec![Some(4), Some(5), Some(4)]
+    );
+    assert_eq!(
+        plan_workers(CpuCores::All, Some(&[2, 3])),
+        vec![Some(2), Some(3)]
+    );
+}
+
+#[tokio::test]
+async fn mem_stops_on_cancel() {
+    let cancel = CancellationToken::new();
+    let canceller = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        canceller.cancel();
+    });
+    let started = Instant::now();
+    memory_load(
+        "e".into(),
+        mem_spec(1),
+        60,
+        Metrics::new().expect("metrics"),
+        LoadController::default(),
+        cancel,
+    )
+    .await
+    .expect("ok");
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn mem_reports_allocation_failure() {
+    let res = memory_load(
+        "e".into(),
+        mem_spec(u32::MAX),
+        1,
+        Metrics::new().expect("metrics"),
+        LoadController::default(),
+        CancellationToken::new(),
+    )
+    .await;
+    assert!(res.is_err());
+}
+
+#[test]
+fn parse_stat_sums_user_and_system() {
+    let stat = "4242 (cpu hog (x)) R 1 2 3 4 5 6 7 8 9 10 150 25 0 0 20 0 1 0";
+    assert_eq!(
+        chimp_chaos_agent::lib_cpu::parse_stat_cpu_ticks(stat).unwrap(),
+        175
+    );
+    assert_eq!(
+        chimp_chaos_agent::lib_cpu::parse_stat_cpu_split(stat).unwrap(),
+        (150, 25)
+    );
+    assert!(chimp_chaos_agent::lib_cpu::parse_stat_cpu_ticks("4242 (x) R 1").is_err());
+}
+
+#[test]
+fn thread_cpu_time_advances() {
+    let before = chimp_chaos_agent::lib_cpu::thread_cpu_time().unwrap();
+    let spin_until = Instant::now() + Duration::from_millis(100);
+    while Instant::now() < spin_until {
+        std::hint::spin_loop();
+    }
+    let after = chimp_chaos_agent::lib_cpu::thread_cpu_time().unwrap();
+    assert!(after > before);
+}
+
+#[test]
+fn duty_controller_compensates_contention() {
+    let mut ctrl = chimp_chaos_agent::lib_cpu::DutyController::new(40);
+    // Plant that only gets 80% of the time it spins for.
+    for _ in 0..50 {
+        let measured = ctrl.busy().as_secs_f64() * 100.0 * 0.8;
+        ctrl.observe(measured);
+    }
+    let measured = ctrl.busy().as_secs_f64() * 100.0 * 0.8;
+    assert!((measured - 40.0).abs() < 1.0, "measured {measured}");
+}
+
+#[tokio::test]
+async fn cpu_closed_loop_reports_measurement() {
+    let m = chimp_chaos_agent::metrics::Metrics::new().expect("metrics");
+    let mut spec = spec(30, CpuCores::default(), None);
+    spec.closed_loop = true;
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(chimp_chaos_agent::lib_cpu::cpu_load(
+        "e".into(),
+        spec,
+        60,
+        m.clone(),
+        cancel.clone(),
+    ));
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let text = String::from_utf8(m.encode_text().expect("encode")).expect("utf8");
+    assert!(text.contains("agent_cpu_hog_measured_percent{"));
+    assert!(text.contains("agent_cpu_hog_duty_error_percent{"));
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    // Per-worker series are removed once the load ends.
+    let text = String::from_utf8(m.encode_text().expect("encode")).expect("utf8");
+    assert!(!text.contains("agent_cpu_hog_measured_percent{"));
+}
+
+#[test]
+fn touch_patterns_cover_every_page() {
+    for pattern in [
+        TouchPattern::Sequential,
+        TouchPattern::Reverse,
+        TouchPattern::Scattered,
+    ] {
+        let len = 64 * 100 + 7;
+        let mut chunks = vec![vec![0u8; len].into_boxed_slice()];
+        touch_pages(
+            &mut chunks,
+            0..len,
+            64,
+            pattern,
+            0,
+            &CancellationToken::new(),
+        );
+        assert!(chunks[0].chunks(64).all(|p| p[0] != 0), "{pattern}");
+    }
+}
+
+/// Resident bytes of the mapping containing `addr`, from `/proc/self/smaps`,
+/// so other tests' allocations do not blur the measurement.
+fn mapping_rss(addr: usize) -> u64 {
+    let smaps = std::fs::read_to_string("/proc/self/smaps").unwrap();
+    let mut inside = false;
+    for line in smaps.lines() {
+        let first = line.split_whitespace().next().unwrap_or("");
+        if let Some((lo, hi)) = first.split_once('-') {
+            if let (Ok(lo), Ok(hi)) = (usize::from_str_radix(lo, 16), usize::from_str_radix(hi, 16))
+            {
+                inside = (lo..hi).contains(&addr);
+                continue;
+            }
+        }
+        if inside && first == "Rss:" {
+            let kib: u64 = line.split_whitespace().nth(1).unwrap().parse().unwrap();
+            return kib * 1024;
+        }
+    }
+    panic!("no mapping holds {addr:#x}");
+}
+
+#[test]
+fn heap_chunks_are_backed_only_once_touched() {
+    let page = rustix::param::page_size();
+    let cancel = CancellationToken::new();
+    let mut chunks = zeroed_chunks(HEAP_CHUNK).unwrap();
+    let addr = chunks[0].as_ptr() as usize;
+    let rss = mapping_rss(addr);
+    assert!(rss < 1 << 20, "untouched chunk holds {rss} bytes");
+    touch_pages(
+        &mut chunks,
+        0..8 << 20,
+        page,
+        TouchPattern::Reverse,
+        0,
+        &cancel,
+    );
+    let rss = mapping_rss(addr);
+    assert!((8 << 20..9 << 20).contains(&rss), "rss {rss} after 8 MiB");
+    let len = HEAP_CHUNK;
+    touch_pages(
+        &mut chunks,
+        0..len,
+        page,
+        TouchPattern::Scattered,
+        0,
+        &cancel,
+    );
+    let rss = mapping_rss(addr);
+    assert!(
+        rss >= HEAP_CHUNK as u64 - (1 << 20),
+        "rss {rss} after full touch"
+    );
+}
+
+#[test]
+fn parse_vm_rss_kib() {
+    let status = "Name:\tagent\nVmRSS:\t   2048 kB\nThreads:\t4\n";
+    assert_eq!(
+        chimp_chaos_agent::lib_mem::parse_vm_rss(status),
+        Some(2 * 1024 * 1024)
+    );
+    assert_eq!(chimp_chaos_agent::lib_mem::parse_vm_rss("Name:\tx\n"), None);
+}
+
+#[tokio::test]
+async fn mem_commits_resident_memory() {
+    let m = Metrics::new().expect("metrics");
+    let ctrl = LoadController::default();
+    let baseline = chimp_chaos_agent::lib_mem::resident_bytes().unwrap();
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(memory_load(
+        "e".into(),
+        MemoryLoadSpec {
+            memory_mb: 64,
+            touch: TouchPattern::Scattered,
+            retouch_seconds: 1,
+            growth: MemoryGrowth::Instant,
+            backing: MemoryBacking::Anonymous,
+        },
+        60,
+        m.clone(),
+        ctrl,
+        cancel.clone(),
+    ));
+    // Faulting in 64 MiB can take a while when the CPU tests run alongside.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let rss = loop {
+        let rss = u64::try_from(m.memory_hog_resident_bytes.get()).unwrap();
+        if rss >= baseline + 60 * 1024 * 1024 || Instant::now() >= deadline {
+            break rss;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    assert!(
+        rss >= baseline + 60 * 1024 * 1024,
+        "rss {rss} baseline {baseline}"
+    );
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(m.memory_hog_resident_bytes.get(), 0);
+}
+
+#[test]
+fn growth_targets() {
+    const MIB: usize = 1024 * 1024;
+    let total = 100 * MIB;
+    let at = Duration::from_secs;
+    assert_eq!(
+        growth_target_bytes(MemoryGrowth::Instant, total, at(0)),
+        total
+    );
+    let ramp = MemoryGrowth::Ramp { ramp_seconds: 10 };
+    assert_eq!(growth_target_bytes(ramp, total, at(0)), 0);
+    assert_eq!(growth_target_bytes(ramp, total, at(5)), 50 * MIB);
+    assert_eq!(growth_target_bytes(ramp, total, at(30)), total);
+    let leak = MemoryGrowth::Leak {
+        rate_mb_per_second: 3,
+    };
+    assert_eq!(
+        growth_target_bytes(leak, total, Duration::from_millis(1500)),
+        4 * MIB
+    );
+    assert_eq!(growth_target_bytes(leak, total, at(1000)), total);
+    let step = MemoryGrowth::Step {
+        step_mb: 30,
+        step_seconds: 2,
+    };
+    assert_eq!(growth_target_bytes(step, total, at(0)), 30 * MIB);
+    assert_eq!(growth_target_bytes(step, total, at(3)), 60 * MIB);
+    assert_eq!(growth_target_bytes(step, total, at(9)), total);
+}
+
+#[tokio::test]
+async fn mem_leak_grows_and_releases() {
+    let m = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(memory_load(
+        "e".into(),
+        MemoryLoadSpec {
+            memory_mb: 64,
+            touch: TouchPattern::default(),
+            retouch_seconds: 0,
+            growth: MemoryGrowth::Leak {
+                rate_mb_per_second: 16,
+            },
+            backing: MemoryBacking::Anonymous,
+        },
+        60,
+        m.clone(),
+        LoadController::default(),
+        cancel.clone(),
+    ));
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let early = m.memory_hog_allocated_bytes.get();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let later = m.memory_hog_allocated_bytes.get();
+    assert!(early > 0 && later > early, "early {early} later {later}");
+    assert!(later < 64 * 1024 * 1024);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(m.memory_hog_allocated_bytes.get(), 0);
+}
+
+#[test]
+fn duty_profiles() {
+    use chimp_chaos_agent::lib_cpu::duty_at;
+    let at = Duration::from_secs;
+    assert_eq!(duty_at(&CpuProfile::Constant, 40, at(7)), 40);
+    let ramp = CpuProfile::Ramp {
+        from_percent: 10,
+        ramp_seconds: 10,
+    };
+    assert_eq!(duty_at(&ramp, 60, at(0)), 10);
+    assert_eq!(duty_at(&ramp, 60, at(5)), 35);
+    assert_eq!(duty_at(&ramp, 60, at(20)), 60);
+    let down = CpuProfile::Ramp {
+        from_percent: 90,
+        ramp_seconds: 4,
+    };
+    assert_eq!(duty_at(&down, 10, at(2)), 50);
+    let steps = CpuProfile::Steps {
+        percents: vec![20, 50, 80],
+        step_seconds: 3,
+    };
+    assert_eq!(duty_at(&steps, 1, at(0)), 20);
+    assert_eq!(duty_at(&steps, 1, at(4)), 50);
+    assert_eq!(duty_at(&steps, 1, at(100)), 80);
+    let sine = CpuProfile::Sine {
+        period_seconds: 4,
+        amplitude_percent: 30,
+    };
+    assert_eq!(duty_at(&sine, 50, at(0)), 50);
+    assert_eq!(duty_at(&sine, 50, at(1)), 80);
+    assert_eq!(duty_at(&sine, 50, at(3)), 20);
+    let clipped = CpuProfile::Sine {
+        period_seconds: 4,
+        amplitude_percent: 100,
+    };
+    assert_eq!(duty_at(&clipped, 90, at(1)), 100);
+    assert_eq!(duty_at(&clipped, 10, at(3)), 0);
+    let square = CpuProfile::Square {
+        period_seconds: 4,
+        low_percent: 0,
+    };
+    assert_eq!(duty_at(&square, 70, at(1)), 70);
+    assert_eq!(duty_at(&square, 70, at(2)), 0);
+    assert_eq!(duty_at(&square, 70, at(5)), 70);
+}
+
+#[tokio::test]
+async fn cpu_profile_mirrors_applied_duty() {
+    let m = chimp_chaos_agent::metrics::Metrics::new().expect("metrics");
+    let mut spec = spec(60, CpuCores::default(), None);
+    spec.profile = CpuProfile::Steps {
+        percents: vec![5, 15],
+        step_seconds: 1,
+    };
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(chimp_chaos_agent::lib_cpu::cpu_load(
+        "e".into(),
+        spec,
+        60,
+        m.clone(),
+        cancel.clone(),
+    ));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(m.cpu_hog_duty_percent.get(), 5);
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(m.cpu_hog_duty_percent.get(), 15);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(m.cpu_hog_duty_percent.get(), 0);
+}
+
+#[tokio::test]
+async fn every_cpu_workload_runs() {
+    let loads: Vec<_> = [
+        CpuWorkload::Spin,
+        CpuWorkload::Integer,
+        CpuWorkload::Float,
+        CpuWorkload::Hash,
+        CpuWorkload::Syscall,
+        CpuWorkload::ContextSwitch,
+    ]
+    .into_iter()
+    .map(|workload| {
+        let mut spec = spec(20, CpuCores::default(), None);
+        spec.workload = workload;
+        let load = chimp_chaos_agent::lib_cpu::cpu_load(
+            "e".into(),
+            spec,
+            1,
+            Metrics::new().expect("metrics"),
+            CancellationToken::new(),
+        );
+        (workload, tokio::spawn(load))
+    })
+    .collect();
+    for (workload, load) in loads {
+        load.await
+            .expect("join")
+            .unwrap_or_else(|e| panic!("{workload}: {e:#}"));
+    }
+    // No other test runs the context switch workload, so any echo thread
+    // left is one these loads leaked.
+    let echoes = std::fs::read_dir("/proc/self/task")
+        .unwrap()
+        .filter_map(|task| std::fs::read_to_string(task.ok()?.path().join("comm")).ok())
+        .filter(|comm| comm.trim_end().ends_with("-echo"))
+        .count();
+    assert_eq!(echoes, 0);
+}
+
+#[tokio::test]
+async fn syscall_workload_reports_system_time() {
+    let m = Metrics::new().expect("metrics");
+    let mut spec = spec(60, CpuCores::default(), None);
+    spec.workload = CpuWorkload::Syscall;
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(chimp_chaos_agent::lib_cpu::cpu_load(
+        "e".into(),
+        spec,
+        60,
+        m.clone(),
+        cancel.clone(),
+    ));
+    let system = || {
+        m.cpu_hog_mode_percent
+            .with_label_values(&["0", "any", "system"])
+            .get()
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while system() <= 0.0 {
+        assert!(Instant::now() < deadline, "no system time reported");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    let text = String::from_utf8(m.encode_text().expect("encode")).expect("utf8");
+    assert!(!text.contains("agent_cpu_hog_mode_percent{"));
+}
+
+type Load = tokio::task::JoinHandle<anyhow::Result<()>>;
+
+/// Runs an 8 MiB memory hog on `backing` until it reports the full size.
+async fn start_backed(backing: MemoryBacking) -> (Metrics, CancellationToken, Load) {
+    let m = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let mut spec = mem_spec(8);
+    spec.backing = backing;
+    let load = tokio::spawn(memory_load(
+        format!("backed-{}", std::process::id()),
+        spec,
+        60,
+        m.clone(),
+        LoadController::default(),
+        cancel.clone(),
+    ));
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while m.memory_hog_allocated_bytes.get() < 8 << 20 {
+        assert!(Instant::now() < deadline, "ballast never reached 8 MiB");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    (m, cancel, load)
+}
+
+async fn file_backing_holds_and_removes(backing: MemoryBacking, dir: &Path) {
+    let (m, cancel, load) = start_backed(backing).await;
+    let file = scratch_path(dir, "memory", &format!("backed-{}", std::process::id()));
+    let meta = std::fs::metadata(&file).expect("ballast file");
+    assert_eq!(meta.len(), 8 << 20);
+    // Every page was touched, so none of the file is a hole.
+    assert!(meta.blocks() * 512 >= 8 << 20, "blocks {}", meta.blocks());
+    // The file's pages are not in VmRSS but still count towards the footprint.
+    // Other tests move this process's RSS around, so retry a few samples.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let rss = chimp_chaos_agent::lib_mem::resident_bytes().unwrap();
+        let footprint = u64::try_from(m.memory_hog_resident_bytes.get()).unwrap();
+        if footprint >= rss + (7 << 20) {
+            break;
+        }
+        assert!(Instant::now() < deadline, "footprint {footprint} rss {rss}");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert!(!file.exists(), "ballast file left behind");
+    assert_eq!(m.memory_hog_allocated_bytes.get(), 0);
+}
+
+#[tokio::test]
+async fn mem_shm_backing_fills_tmpfs_file() {
+    file_backing_holds_and_removes(MemoryBacking::Shm, Path::new(SHM_DIR)).await;
+}
+
+#[tokio::test]
+async fn mem_file_backing_uses_given_dir() {
+    let dir = std::env::temp_dir().join(format!("chimp-mem-file-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let backing = MemoryBacking::File {
+        dir: dir.to_str().unwrap().into(),
+    };
+    file_backing_holds_and_removes(backing, &dir).await;
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+fn locked_bytes() -> u64 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    let line = status.lines().find(|l| l.starts_with("VmLck:")).unwrap();
+    line.split_whitespace()
+        .nth(1)
+        .unwrap()
+        .parse::<u64>()
+        .unwrap()
+        * 1024
+}
+
+#[tokio::test]
+async fn mem_mlocked_backing_locks_and_unlocks() {
+    let (_m, cancel, load) = start_backed(MemoryBacking::Mlocked).await;
+    assert!(locked_bytes() >= 8 << 20, "locked {}", locked_bytes());
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(locked_bytes(), 0);
 }
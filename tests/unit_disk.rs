@@ -0,0 +1,253 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+use chimp_chaos_agent::domain::{FillAmount, IoRate};
+use chimp_chaos_agent::lib_disk::{
+    disk_fill_load, disk_io_load, fill_target_bytes, op_interval, op_is_read,
+    page_cache_pressure_load, scratch_path, DiskFillSpec, DiskIoSpec, PageCachePressureSpec,
+};
+use chimp_chaos_agent::metrics::Metrics;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chimp-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    dir
+}
+
+fn disk_spec(dir: &Path, read_percent: u32, rate: IoRate) -> DiskIoSpec {
+    DiskIoSpec {
+        dir: dir.to_str().unwrap().into(),
+        read_percent,
+        block_size_kb: 4,
+        file_size_mb: 1,
+        rate,
+        fsync_every: 4,
+    }
+}
+
+#[test]
+fn read_mix_is_spread_evenly() {
+    for pct in [0, 25, 50, 70, 100] {
+        let reads = (0..1000).filter(|&op| op_is_read(op, pct)).count();
+        assert_eq!(reads, pct as usize * 10, "read_percent={pct}");
+    }
+    let first: Vec<bool> = (0..4).map(|op| op_is_read(op, 50)).collect();
+    assert_eq!(first, [false, true, false, true]);
+}
+
+#[test]
+fn pacing_intervals() {
+    assert_eq!(op_interval(IoRate::Unlimited, 4096), None);
+    assert_eq!(
+        op_interval(
+            IoRate::Iops {
+                ops_per_second: 200
+            },
+            4096
+        ),
+        Some(Duration::from_millis(5))
+    );
+    assert_eq!(
+        op_interval(IoRate::Throughput { mb_per_second: 1 }, 64 * 1024),
+        Some(Duration::from_micros(62_500))
+    );
+}
+
+#[test]
+fn scratch_path_stays_in_dir() {
+    let p = scratch_path(Path::new("/data"), "disk-io", "../etc/x");
+    assert_eq!(p, Path::new("/data/.chimp-disk-io-___etc_x.tmp"));
+}
+
+#[tokio::test]
+async fn disk_io_moves_bytes_and_cleans_up() {
+    let dir = scratch_dir("disk-io");
+    let m = Metrics::new().expect("metrics");
+    disk_io_load(
+        "e".into(),
+        disk_spec(
+            &dir,
+            50,
+            IoRate::Iops {
+                ops_per_second: 500,
+            },
+        ),
+        1,
+        m.clone(),
+        CancellationToken::new(),
+    )
+    .await
+    .expect("ok");
+    let reads = m.disk_io_ops_total.with_label_values(&["read"]).get();
+    let writes = m.disk_io_ops_total.with_label_values(&["write"]).get();
+    // 256 writes lay out the file, then ~500 paced ops split evenly.
+    assert!(reads > 50, "reads={reads}");
+    assert!(writes > 256 + 50, "writes={writes}");
+    assert!(reads < 600, "pacing ignored: reads={reads}");
+    assert!(m.disk_io_fsyncs_total.get() > 10);
+    assert_eq!(
+        m.disk_io_bytes_total.with_label_values(&["read"]).get(),
+        reads * 4096
+    );
+    assert!(
+        !scratch_path(&dir, "disk-io", "e").exists(),
+        "scratch file left behind"
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn disk_io_stops_on_cancel() {
+    let dir = scratch_dir("disk-cancel");
+    let cancel = CancellationToken::new();
+    let trigger = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        trigger.cancel();
+    });
+    let t0 = Instant::now();
+    disk_io_load(
+        "e".into(),
+        disk_spec(
+            &dir,
+            100,
+            IoRate::Iops {
+                ops_per_second: 100,
+            },
+        ),
+        30,
+        Metrics::new().expect("metrics"),
+        cancel,
+    )
+    .await
+    .expect("ok");
+    assert!(t0.elapsed() < Duration::from_secs(2));
+    assert!(
+        !scratch_path(&dir, "disk-io", "e").exists(),
+        "scratch file left behind"
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn disk_io_fails_on_missing_dir() {
+    let err = disk_io_load(
+        "e".into(),
+        disk_spec(Path::new("/nonexistent/chimp"), 50, IoRate::Unlimited),
+        1,
+        Metrics::new().expect("metrics"),
+        CancellationToken::new(),
+    )
+    .await
+    .unwrap_err();
+    assert!(format!("{err:#}").contains("create scratch file"));
+}
+
+#[test]
+fn fill_targets() {
+    assert_eq!(
+        fill_target_bytes(FillAmount::Size { size_mb: 3 }, 0),
+        3 << 20
+    );
+    assert_eq!(
+        fill_target_bytes(FillAmount::FreePercent { percent: 25 }, 1000),
+        250
+    );
+    assert_eq!(
+        fill_target_bytes(FillAmount::FreePercent { percent: 100 }, u64::MAX),
+        u64::MAX
+    );
+}
+
+#[tokio::test]
+async fn disk_fill_holds_and_removes_ballast() {
+    let dir = scratch_dir("disk-fill");
+    let m = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(disk_fill_load(
+        "e".into(),
+        DiskFillSpec {
+            path: dir.to_str().unwrap().into(),
+            amount: FillAmount::Size { size_mb: 8 },
+        },
+        30,
+        m.clone(),
+        cancel.clone(),
+    ));
+    let ballast = scratch_path(&dir, "disk-fill", "e");
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(m.disk_fill_bytes.get(), 8 << 20);
+    assert_eq!(std::fs::metadata(&ballast).expect("ballast").len(), 8 << 20);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert!(!ballast.exists(), "ballast left behind");
+    assert_eq!(m.disk_fill_bytes.get(), 0);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn page_cache_pressure_rereads_and_removes_files() {
+    let dir = scratch_dir("page-cache");
+    let m = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(page_cache_pressure_load(
+        "e".into(),
+        PageCachePressureSpec {
+            dir: dir.to_str().unwrap().into(),
+            file_size_mb: 2,
+            files: 2,
+            rate: IoRate::Unlimited,
+        },
+        30,
+        m.clone(),
+        cancel.clone(),
+    ));
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while m.page_cache_pressure_passes_total.get() < 3 {
+        assert!(Instant::now() < deadline, "no passes completed");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(m.page_cache_pressure_file_bytes.get(), 4 << 20);
+    assert!(m.page_cache_pressure_read_bytes_total.get() >= 3 * (4 << 20));
+    for i in 0..2 {
+        let file = scratch_path(&dir, &format!("page-cache-{i}"), "e");
+        assert_eq!(std::fs::metadata(&file).expect("scratch").len(), 2 << 20);
+    }
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(m.page_cache_pressure_file_bytes.get(), 0);
+    assert_eq!(
+        std::fs::read_dir(&dir).unwrap().count(),
+        0,
+        "scratch files left behind"
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn page_cache_pressure_reads_are_paced() {
+    let dir = scratch_dir("page-cache-paced");
+    let m = Metrics::new().expect("metrics");
+    page_cache_pressure_load(
+        "e".into(),
+        PageCachePressureSpec {
+            dir: dir.to_str().unwrap().into(),
+            file_size_mb: 1,
+            files: 1,
+            rate: IoRate::Iops { ops_per_second: 4 },
+        },
+        1,
+        m.clone(),
+        CancellationToken::new(),
+    )
+    .await
+    .expect("ok");
+    // One 1 MiB block per 250ms, the first one right away.
+    let read = m.page_cache_pressure_read_bytes_total.get() >> 20;
+    assert!((3..=5).contains(&read), "read {read} MiB");
+    std::fs::remove_dir_all(&dir).ok();
+}
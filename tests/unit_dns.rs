@@ -0,0 +1,259 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+use chimp_chaos_agent::domain::{
+    DnsFaultRule, Experiment, ExperimentKind, ExperimentParams, LoadController,
+};
+use chimp_chaos_agent::lib_dns::{
+    build_response, dns_fault_load, name_matches, parse_question, DnsFaultSpec, RCODE_NOERROR,
+    RCODE_NXDOMAIN, RCODE_SERVFAIL, TYPE_A, TYPE_AAAA,
+};
+use chimp_chaos_agent::metrics::Metrics;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+
+const UPSTREAM_ADDR: &str = "192.0.2.1";
+
+fn query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+    let mut q = id.to_be_bytes().to_vec();
+    // RD set, one question.
+    q.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        q.push(u8::try_from(label.len()).unwrap());
+        q.extend_from_slice(label.as_bytes());
+    }
+    q.push(0);
+    q.extend_from_slice(&qtype.to_be_bytes());
+    q.extend_from_slice(&1u16.to_be_bytes());
+    q
+}
+
+fn rcode(resp: &[u8]) -> u8 {
+    resp[3] & 0x0F
+}
+
+/// Addresses in the answer section of a response built like ours.
+fn answers(resp: &[u8]) -> Vec<IpAddr> {
+    let count = u16::from_be_bytes([resp[6], resp[7]]);
+    let mut pos = 12;
+    while resp[pos] != 0 {
+        pos += usize::from(resp[pos]) + 1;
+    }
+    pos += 5;
+    let mut out = Vec::new();
+    for _ in 0..count {
+        let len = usize::from(u16::from_be_bytes([resp[pos + 10], resp[pos + 11]]));
+        let rdata = &resp[pos + 12..pos + 12 + len];
+        out.push(match len {
+            4 => IpAddr::from(<[u8; 4]>::try_from(rdata).unwrap()),
+            _ => IpAddr::from(<[u8; 16]>::try_from(rdata).unwrap()),
+        });
+        pos += 12 + len;
+    }
+    out
+}
+
+/// Resolver that answers every A query with [`UPSTREAM_ADDR`].
+async fn upstream_resolver() -> SocketAddr {
+    let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = sock.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 512];
+        while let Ok((n, from)) = sock.recv_from(&mut buf).await {
+            let q = parse_question(&buf[..n]).unwrap();
+            let addrs = [UPSTREAM_ADDR.parse().unwrap()];
+            let answers: &[IpAddr] = if q.qtype == TYPE_A { &addrs } else { &[] };
+            let resp = build_response(&buf[..n], &q, RCODE_NOERROR, answers, 60);
+            let _ = sock.send_to(&resp, from).await;
+        }
+    });
+    addr
+}
+
+struct Responder {
+    addr: SocketAddr,
+    metrics: Metrics,
+    cancel: CancellationToken,
+    load: JoinHandle<anyhow::Result<()>>,
+}
+
+async fn start_responder(upstream: SocketAddr, rules: &str) -> Responder {
+    let rules: Vec<DnsFaultRule> = serde_json::from_str(rules).expect("rules");
+    let ctrl = LoadController::default();
+    let params = ExperimentParams::DnsFault {
+        listen: "127.0.0.1:0".into(),
+        upstream: upstream.to_string(),
+        rules: rules.clone(),
+    };
+    let exp = Experiment::new("d".into(), ExperimentKind::DNS_FAULT, params, 30, 0);
+    let _handle = ctrl.start("d", &exp).expect("start");
+    let metrics = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(dns_fault_load(
+        "d".into(),
+        DnsFaultSpec {
+            listen: "127.0.0.1:0".into(),
+            upstream: upstream.to_string(),
+            rules,
+        },
+        30,
+        metrics.clone(),
+        ctrl.clone(),
+        cancel.clone(),
+    ));
+    let addr = loop {
+        let bound = ctrl
+            .state
+            .lock()
+            .get("d")
+            .and_then(|st| st.listen_addr.clone());
+        if let Some(addr) = bound {
+            break addr.parse().unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    Responder {
+        addr,
+        metrics,
+        cancel,
+        load,
+    }
+}
+
+impl Responder {
+    async fn resolve(&self, id: u16, name: &str, qtype: u16) -> Vec<u8> {
+        let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sock.send_to(&query(id, name, qtype), self.addr)
+            .await
+            .unwrap();
+        let mut buf = [0u8; 512];
+        let n = timeout(Duration::from_secs(5), sock.recv(&mut buf))
+            .await
+            .expect("answer")
+            .unwrap();
+        let resp = buf[..n].to_vec();
+        assert_eq!(&resp[..2], &id.to_be_bytes());
+        resp
+    }
+
+    fn hits(&self, rule: &str, action: &str) -> u64 {
+        self.metrics
+            .dns_fault_hits_total
+            .with_label_values(&[rule, action])
+            .get()
+    }
+
+    async fn stop(self) {
+        self.cancel.cancel();
+        self.load.await.expect("join").expect("ok");
+    }
+}
+
+#[test]
+fn names_match_exactly_or_below_wildcard() {
+    assert!(name_matches("api.internal", "api.internal"));
+    assert!(name_matches("API.Internal.", "api.internal"));
+    assert!(!name_matches("api.internal", "db.internal"));
+    assert!(name_matches("*.svc.local", "db.svc.local"));
+    assert!(name_matches("*.svc.local", "a.b.svc.local"));
+    assert!(!name_matches("*.svc.local", "svc.local"));
+    assert!(!name_matches("*.svc.local", "xsvc.local"));
+}
+
+#[test]
+fn question_round_trips_through_response() {
+    let q = query(0xBEEF, "Api.Internal", TYPE_AAAA);
+    let question = parse_question(&q).unwrap();
+    assert_eq!(question.name, "api.internal");
+    assert_eq!(question.qtype, TYPE_AAAA);
+    assert_eq!(question.end, q.len());
+    let v6: IpAddr = "2001:db8::1".parse().unwrap();
+    let resp = build_response(&q, &question, RCODE_NOERROR, &[v6], 5);
+    assert_eq!(&resp[..2], &[0xBE, 0xEF]);
+    assert_eq!(resp[2] & 0x80, 0x80);
+    assert_eq!(answers(&resp), vec![v6]);
+    assert!(parse_question(&resp).is_err());
+    assert!(parse_question(&q[..q.len() - 2]).is_err());
+}
+
+#[tokio::test]
+async fn unmatched_names_are_forwarded() {
+    let resp = start_responder(
+        upstream_resolver().await,
+        r#"[{"name":"broken.internal","action":"NXDOMAIN"}]"#,
+    )
+    .await;
+    let answer = resp.resolve(1, "fine.internal", TYPE_A).await;
+    assert_eq!(rcode(&answer), RCODE_NOERROR);
+    assert_eq!(
+        answers(&answer),
+        vec![UPSTREAM_ADDR.parse::<IpAddr>().unwrap()]
+    );
+    assert_eq!(resp.metrics.dns_fault_queries_total.get(), 1);
+    assert_eq!(resp.hits("broken.internal", "nxdomain"), 0);
+    resp.stop().await;
+}
+
+#[tokio::test]
+async fn rules_answer_with_configured_faults() {
+    let resp = start_responder(
+        upstream_resolver().await,
+        r#"[{"name":"gone.internal","action":"NXDOMAIN"},
+            {"name":"*.flaky.internal","action":"SERVFAIL"},
+            {"name":"moved.internal","action":"ADDRESS","addresses":["10.9.9.9","fd00::9"],"ttl_seconds":1}]"#,
+    )
+    .await;
+    assert_eq!(
+        rcode(&resp.resolve(1, "gone.internal", TYPE_A).await),
+        RCODE_NXDOMAIN
+    );
+    assert_eq!(
+        rcode(&resp.resolve(2, "db.flaky.internal", TYPE_A).await),
+        RCODE_SERVFAIL
+    );
+    let v4 = resp.resolve(3, "moved.internal", TYPE_A).await;
+    assert_eq!(rcode(&v4), RCODE_NOERROR);
+    assert_eq!(answers(&v4), vec!["10.9.9.9".parse::<IpAddr>().unwrap()]);
+    let v6 = resp.resolve(4, "MOVED.internal", TYPE_AAAA).await;
+    assert_eq!(answers(&v6), vec!["fd00::9".parse::<IpAddr>().unwrap()]);
+    assert_eq!(resp.hits("gone.internal", "nxdomain"), 1);
+    assert_eq!(resp.hits("*.flaky.internal", "servfail"), 1);
+    assert_eq!(resp.hits("moved.internal", "address"), 2);
+    resp.stop().await;
+}
+
+#[tokio::test]
+async fn delay_rule_slows_forwarded_answer() {
+    let resp = start_responder(
+        upstream_resolver().await,
+        r#"[{"name":"slow.internal","action":"DELAY","delay_ms":300}]"#,
+    )
+    .await;
+    let started = Instant::now();
+    let answer = resp.resolve(7, "slow.internal", TYPE_A).await;
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(
+        answers(&answer),
+        vec![UPSTREAM_ADDR.parse::<IpAddr>().unwrap()]
+    );
+    resp.stop().await;
+}
+
+#[tokio::test]
+async fn dead_upstream_yields_servfail() {
+    let dead = UdpSocket::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let resp = start_responder(dead, "[]").await;
+    let answer = resp.resolve(9, "any.internal", TYPE_A).await;
+    assert_eq!(rcode(&answer), RCODE_SERVFAIL);
+    assert_eq!(resp.metrics.dns_fault_upstream_failures_total.get(), 1);
+    resp.stop().await;
+}
@@ -2,14 +2,24 @@
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
 
-use chimp_chaos_agent::domain::{Experiment, ExperimentKind, ExperimentParams};
+use chimp_chaos_agent::domain::{
+    CpuCores, CpuProfile, CpuWorkload, Experiment, ExperimentKind, ExperimentParams,
+    ExperimentPhase, LoadController, MemoryBacking, MemoryGrowth, TouchPattern,
+};
 
 #[test]
 fn new_cpu_ok() {
     let e = Experiment::new(
         "e1".into(),
         ExperimentKind::CPU,
-        ExperimentParams::Cpu { duty_percent: 50 },
+        ExperimentParams::Cpu {
+            duty_percent: 50,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
         5,
         1000,
     );
@@ -21,7 +31,14 @@ fn new_cpu_bad_percent() {
     let _res = Experiment::new(
         "e1".into(),
         ExperimentKind::CPU,
-        ExperimentParams::Cpu { duty_percent: 0 },
+        ExperimentParams::Cpu {
+            duty_percent: 0,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
         5,
         1000,
     );
@@ -32,8 +49,128 @@ fn new_bad_duration() {
     let _res = Experiment::new(
         "e1".into(),
         ExperimentKind::MEMORY,
-        ExperimentParams::Memory { memory_mb: 10 },
+        ExperimentParams::Memory {
+            memory_mb: 10,
+            touch: TouchPattern::default(),
+            retouch_seconds: 10,
+            growth: MemoryGrowth::Instant,
+            backing: MemoryBacking::Anonymous,
+        },
         0,
         1000,
     );
 }
+
+#[test]
+fn phase_transitions_enforced() {
+    let ctrl = LoadController::default();
+    let e = Experiment::new(
+        "e1".into(),
+        ExperimentKind::CPU,
+        ExperimentParams::Cpu {
+            duty_percent: 50,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
+        5,
+        1000,
+    );
+    let _handle = ctrl.start(&e.id, &e).expect("start");
+    assert_eq!(ctrl.phase("e1"), Some(ExperimentPhase::Pending));
+    assert!(ctrl.transition("e1", ExperimentPhase::Running).is_ok());
+    assert!(ctrl.transition("e1", ExperimentPhase::Pending).is_err());
+    assert!(ctrl.transition("e1", ExperimentPhase::Completed).is_err());
+    assert!(ctrl
+        .finish("e1", ExperimentPhase::Stopped, "stopped", 1002)
+        .is_err());
+    assert!(ctrl
+        .finish("e1", ExperimentPhase::Completed, "done", 1005)
+        .is_ok());
+    let st = ctrl.state.lock().get("e1").cloned().unwrap();
+    assert_eq!(st.phase, ExperimentPhase::Completed);
+    assert_eq!(st.ended_ts_seconds, Some(1005));
+    assert_eq!(st.termination_reason.as_deref(), Some("done"));
+    assert!(ctrl.transition("e1", ExperimentPhase::Stopping).is_err());
+    assert!(ctrl.get_running_id().is_none());
+}
+
+#[test]
+fn mark_running_starts_the_clock_once() {
+    let ctrl = LoadController::default();
+    let e = Experiment::new(
+        "e2".into(),
+        ExperimentKind::CPU,
+        ExperimentParams::Cpu {
+            duty_percent: 50,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
+        5,
+        1000,
+    );
+    let handle = ctrl.start(&e.id, &e).expect("start");
+    assert!(!handle.running.is_cancelled());
+    let before = chrono::Utc::now().timestamp();
+    ctrl.mark_running("e2");
+    assert_eq!(ctrl.phase("e2"), Some(ExperimentPhase::Running));
+    assert!(handle.running.is_cancelled());
+    let st = ctrl.state.lock().get("e2").cloned().unwrap();
+    assert!(st.started_ts_seconds >= before);
+    assert_eq!(st.ends_ts_seconds - st.started_ts_seconds, 5);
+    ctrl.mark_running("e2");
+    assert_eq!(ctrl.phase("e2"), Some(ExperimentPhase::Running));
+    let again = ctrl.state.lock().get("e2").cloned().unwrap();
+    assert_eq!(again.started_ts_seconds, st.started_ts_seconds);
+
+    // Only one experiment may be active at a time.
+    let Err(err) = ctrl.start("e3", &e) else {
+        panic!("second active experiment accepted");
+    };
+    assert!(format!("{err:#}").contains("another experiment running: e2"));
+    assert_eq!(ctrl.phase("e3"), None);
+    assert!(ctrl
+        .finish("e2", ExperimentPhase::Completed, "done", before + 5)
+        .is_ok());
+
+    // Stopped during setup: the clock starts but the phase stays put.
+    let handle = ctrl.start("e3", &e).expect("start");
+    assert!(ctrl.transition("e3", ExperimentPhase::Stopping).is_ok());
+    ctrl.mark_running("e3");
+    assert_eq!(ctrl.phase("e3"), Some(ExperimentPhase::Stopping));
+    assert!(handle.running.is_cancelled());
+    assert!(ctrl
+        .finish(
+            "e3",
+            ExperimentPhase::Stopped,
+            "stopped by operator",
+            before + 1
+        )
+        .is_ok());
+
+    // A load stuck in setup can still be timed out.
+    let _handle = ctrl.start("e4", &e).expect("start");
+    assert!(ctrl
+        .finish(
+            "e4",
+            ExperimentPhase::TimedOut,
+            "load did not start in time",
+            1001
+        )
+        .is_ok());
+}
+
+#[test]
+fn phase_terminal_set() {
+    assert!(!ExperimentPhase::Pending.is_terminal());
+    assert!(!ExperimentPhase::Stopping.is_terminal());
+    assert!(ExperimentPhase::TimedOut.is_terminal());
+    assert!(ExperimentPhase::Pending.can_transition_to(ExperimentPhase::Stopping));
+    assert!(ExperimentPhase::Stopping.can_transition_to(ExperimentPhase::Stopped));
+    assert!(!ExperimentPhase::Running.can_transition_to(ExperimentPhase::Stopped));
+}
@@ -0,0 +1,97 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+use chimp_chaos_agent::domain::{FdResource, FdTarget};
+use chimp_chaos_agent::lib_fd::{
+    fd_load, fd_target_count, nofile_limit, open_fd_count, FdLoadSpec, FD_RESERVE,
+};
+use chimp_chaos_agent::metrics::Metrics;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Serializes tests that move this process's descriptor count, since one of
+/// them deliberately exhausts it.
+static FD_TESTS: Mutex<()> = Mutex::const_new(());
+
+#[test]
+fn fd_targets() {
+    assert_eq!(fd_target_count(FdTarget::Count { count: 7 }, None), 7);
+    assert_eq!(fd_target_count(FdTarget::Count { count: 7 }, Some(1024)), 7);
+    assert_eq!(
+        fd_target_count(FdTarget::Count { count: 1024 }, Some(1024)),
+        1024 - FD_RESERVE
+    );
+    assert_eq!(
+        fd_target_count(FdTarget::LimitPercent { percent: 50 }, Some(1024)),
+        (1024 - FD_RESERVE) / 2
+    );
+    assert_eq!(
+        fd_target_count(FdTarget::LimitPercent { percent: 100 }, Some(FD_RESERVE)),
+        0
+    );
+    assert_eq!(
+        fd_target_count(FdTarget::LimitPercent { percent: 50 }, None),
+        0
+    );
+}
+
+async fn holds_and_releases(resource: FdResource) {
+    let _serial = FD_TESTS.lock().await;
+    let m = Metrics::new().expect("metrics");
+    let before = open_fd_count().expect("count");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(fd_load(
+        "e".into(),
+        FdLoadSpec {
+            target: FdTarget::Count {
+                count: before + 100,
+            },
+            resource,
+        },
+        30,
+        m.clone(),
+        cancel.clone(),
+    ));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let held = m.fd_hog_held.get();
+    assert!((90..=100).contains(&held), "held={held}");
+    assert!(open_fd_count().expect("count") >= before + 90);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(m.fd_hog_held.get(), 0);
+    assert!(open_fd_count().expect("count") < before + 50);
+}
+
+#[tokio::test]
+async fn fd_hog_files() {
+    holds_and_releases(FdResource::File).await;
+}
+
+#[tokio::test]
+async fn fd_hog_sockets() {
+    holds_and_releases(FdResource::Socket).await;
+}
+
+#[tokio::test]
+async fn fd_hog_stops_at_limit() {
+    let Some(limit) = nofile_limit() else {
+        return;
+    };
+    let _serial = FD_TESTS.lock().await;
+    let m = Metrics::new().expect("metrics");
+    fd_load(
+        "e".into(),
+        FdLoadSpec {
+            target: FdTarget::Count { count: limit + 10 },
+            resource: FdResource::File,
+        },
+        1,
+        m.clone(),
+        CancellationToken::new(),
+    )
+    .await
+    .expect("hitting the limit is not a failure");
+    assert_eq!(m.fd_hog_held.get(), 0);
+}
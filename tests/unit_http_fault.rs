@@ -0,0 +1,239 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use chimp_chaos_agent::domain::{
+    Experiment, ExperimentKind, ExperimentParams, HttpFaultRule, LoadController,
+};
+use chimp_chaos_agent::lib_http_fault::{http_fault_load, rule_matches, HttpFaultSpec};
+use chimp_chaos_agent::metrics::Metrics;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+const UPSTREAM_BODY: &str = "hello from upstream";
+
+/// Answers every request with a fixed body and closes the connection.
+async fn upstream_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut sock, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut req = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !req.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match sock.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => req.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let res = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nx-upstream: yes\r\nconnection: close\r\n\r\n{UPSTREAM_BODY}",
+                    UPSTREAM_BODY.len()
+                );
+                let _ = sock.write_all(res.as_bytes()).await;
+            });
+        }
+    });
+    format!("http://{addr}")
+}
+
+fn rules(json: &str) -> Vec<HttpFaultRule> {
+    serde_json::from_str(json).expect("rules")
+}
+
+struct Proxy {
+    base: String,
+    metrics: Metrics,
+    cancel: CancellationToken,
+    load: JoinHandle<anyhow::Result<()>>,
+}
+
+async fn start_proxy(rules: Vec<HttpFaultRule>) -> Proxy {
+    let upstream = upstream_server().await;
+    let ctrl = LoadController::default();
+    let params = ExperimentParams::HttpFault {
+        listen: "127.0.0.1:0".into(),
+        upstream: upstream.clone(),
+        rules: rules.clone(),
+    };
+    let exp = Experiment::new("h".into(), ExperimentKind::HTTP_FAULT, params, 30, 0);
+    let _handle = ctrl.start("h", &exp).expect("start");
+    let metrics = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(http_fault_load(
+        "h".into(),
+        HttpFaultSpec {
+            listen: "127.0.0.1:0".into(),
+            upstream,
+            rules,
+        },
+        30,
+        metrics.clone(),
+        ctrl.clone(),
+        cancel.clone(),
+    ));
+    let addr = loop {
+        let bound = ctrl
+            .state
+            .lock()
+            .get("h")
+            .and_then(|st| st.listen_addr.clone());
+        if let Some(addr) = bound {
+            break addr;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    Proxy {
+        base: format!("http://{addr}"),
+        metrics,
+        cancel,
+        load,
+    }
+}
+
+impl Proxy {
+    async fn stop(self) {
+        self.cancel.cancel();
+        self.load.await.expect("join").expect("ok");
+    }
+
+    fn hits(&self, rule: &str, action: &str) -> u64 {
+        self.metrics
+            .http_fault_hits_total
+            .with_label_values(&[rule, action])
+            .get()
+    }
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder().no_proxy().build().unwrap()
+}
+
+#[test]
+fn rules_match_on_method_path_and_header() {
+    let rule = &rules(
+        r#"[{"name":"r","method":"post","path_prefix":"/api","header":{"name":"x-canary","value":"1"},"action":"ABORT"}]"#,
+    )[0];
+    let mut headers = HeaderMap::new();
+    assert!(!rule_matches(rule, "POST", "/api/users", &headers));
+    headers.insert(
+        HeaderName::from_static("x-canary"),
+        HeaderValue::from_static("1"),
+    );
+    assert!(rule_matches(rule, "POST", "/api/users", &headers));
+    assert!(!rule_matches(rule, "GET", "/api/users", &headers));
+    assert!(!rule_matches(rule, "POST", "/health", &headers));
+    headers.insert(
+        HeaderName::from_static("x-canary"),
+        HeaderValue::from_static("0"),
+    );
+    assert!(!rule_matches(rule, "POST", "/api/users", &headers));
+
+    let any = &rules(r#"[{"name":"any","action":"STATUS","status":500}]"#)[0];
+    assert!(rule_matches(any, "DELETE", "/", &HeaderMap::new()));
+    assert_eq!(any.probability_percent, 100);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn unmatched_requests_are_forwarded() {
+    let proxy = start_proxy(rules(
+        r#"[{"name":"api","path_prefix":"/api","action":"STATUS","status":503}]"#,
+    ))
+    .await;
+    let res = client()
+        .get(format!("{}/health?full=1", proxy.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-upstream"], "yes");
+    assert_eq!(res.text().await.unwrap(), UPSTREAM_BODY);
+    assert_eq!(proxy.metrics.http_fault_requests_total.get(), 1);
+    assert_eq!(proxy.hits("api", "status"), 0);
+    proxy.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn status_rule_short_circuits() {
+    let proxy = start_proxy(rules(
+        r#"[{"name":"api","path_prefix":"/api","action":"STATUS","status":503}]"#,
+    ))
+    .await;
+    for _ in 0..3 {
+        let res = client()
+            .get(format!("{}/api/users", proxy.base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 503);
+        assert!(res.headers().get("x-upstream").is_none());
+    }
+    assert_eq!(proxy.hits("api", "status"), 3);
+    proxy.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn zero_probability_never_fires() {
+    let proxy = start_proxy(rules(
+        r#"[{"name":"never","action":"STATUS","status":500,"probability_percent":0}]"#,
+    ))
+    .await;
+    let res = client().get(&proxy.base).send().await.unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(proxy.hits("never", "status"), 0);
+    proxy.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn delay_rule_holds_the_request() {
+    let proxy = start_proxy(rules(
+        r#"[{"name":"slow","action":"DELAY","delay_ms":300}]"#,
+    ))
+    .await;
+    let started = Instant::now();
+    let res = client().get(&proxy.base).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), UPSTREAM_BODY);
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(proxy.hits("slow", "delay"), 1);
+    proxy.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn truncate_and_abort_break_the_body() {
+    let proxy = start_proxy(rules(
+        r#"[{"name":"cut","path_prefix":"/cut","action":"TRUNCATE","keep_bytes":5},
+            {"name":"drop","path_prefix":"/drop","action":"ABORT"}]"#,
+    ))
+    .await;
+    let cut = client().get(format!("{}/cut", proxy.base)).send().await;
+    assert!(match cut {
+        Ok(res) => res.bytes().await.is_err(),
+        Err(_) => true,
+    });
+    let dropped = client().get(format!("{}/drop", proxy.base)).send().await;
+    assert!(dropped.is_err(), "abort sent a response: {dropped:?}");
+
+    // Nothing of the response, not even a status line, reaches the wire.
+    let mut sock = tokio::net::TcpStream::connect(proxy.base.trim_start_matches("http://"))
+        .await
+        .unwrap();
+    sock.write_all(b"GET /drop HTTP/1.1\r\nhost: x\r\n\r\n")
+        .await
+        .unwrap();
+    let mut seen = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(5), sock.read_to_end(&mut seen)).await;
+    assert!(read.is_ok(), "abort left the connection open");
+    assert!(
+        seen.is_empty(),
+        "abort wrote {:?}",
+        String::from_utf8_lossy(&seen)
+    );
+    assert_eq!(proxy.hits("cut", "truncate"), 1);
+    assert_eq!(proxy.hits("drop", "abort"), 2);
+    proxy.stop().await;
+}
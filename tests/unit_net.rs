@@ -0,0 +1,227 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+use chimp_chaos_agent::domain::{
+    Experiment, ExperimentKind, ExperimentParams, LoadController, TcpFaults,
+};
+use chimp_chaos_agent::lib_net::{chunk_delay, tcp_proxy_load, TcpProxySpec, MAX_CONNECTIONS};
+use chimp_chaos_agent::metrics::Metrics;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+async fn echo_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut sock, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let (mut rd, mut wr) = sock.split();
+                let _ = tokio::io::copy(&mut rd, &mut wr).await;
+            });
+        }
+    });
+    addr
+}
+
+struct Proxy {
+    addr: SocketAddr,
+    metrics: Metrics,
+    cancel: CancellationToken,
+    load: JoinHandle<anyhow::Result<()>>,
+}
+
+async fn start_proxy(faults: TcpFaults) -> Proxy {
+    let upstream = echo_server().await.to_string();
+    let ctrl = LoadController::default();
+    let params = ExperimentParams::NetworkProxy {
+        listen: "127.0.0.1:0".into(),
+        upstream: upstream.clone(),
+        faults,
+    };
+    let exp = Experiment::new("p".into(), ExperimentKind::NETWORK_PROXY, params, 30, 0);
+    let _handle = ctrl.start("p", &exp).expect("start");
+    let metrics = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(tcp_proxy_load(
+        "p".into(),
+        TcpProxySpec {
+            listen: "127.0.0.1:0".into(),
+            upstream,
+            faults,
+        },
+        30,
+        metrics.clone(),
+        ctrl.clone(),
+        cancel.clone(),
+    ));
+    let addr = loop {
+        let bound = ctrl
+            .state
+            .lock()
+            .get("p")
+            .and_then(|st| st.listen_addr.clone());
+        if let Some(addr) = bound {
+            break addr.parse().unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    Proxy {
+        addr,
+        metrics,
+        cancel,
+        load,
+    }
+}
+
+impl Proxy {
+    async fn stop(self) {
+        self.cancel.cancel();
+        self.load.await.expect("join").expect("ok");
+    }
+}
+
+async fn round_trip(addr: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let mut sock = TcpStream::connect(addr).await.unwrap();
+    sock.write_all(payload).await.unwrap();
+    let mut got = vec![0u8; payload.len()];
+    sock.read_exact(&mut got).await.unwrap();
+    got
+}
+
+#[test]
+fn chunk_delay_stays_in_band() {
+    assert_eq!(chunk_delay(0, 0), Duration::ZERO);
+    assert_eq!(chunk_delay(50, 0), Duration::from_millis(50));
+    for _ in 0..1000 {
+        let d = chunk_delay(50, 20);
+        assert!(d >= Duration::from_millis(30) && d <= Duration::from_millis(70));
+        assert!(chunk_delay(5, 20) <= Duration::from_millis(25));
+    }
+}
+
+#[tokio::test]
+async fn proxy_forwards_and_counts_bytes() {
+    let proxy = start_proxy(TcpFaults::default()).await;
+    let got = round_trip(proxy.addr, b"hello through the proxy").await;
+    assert_eq!(got, b"hello through the proxy");
+    let bytes = &proxy.metrics.tcp_proxy_bytes_total;
+    assert_eq!(bytes.with_label_values(&["upstream"]).get(), 23);
+    assert_eq!(bytes.with_label_values(&["downstream"]).get(), 23);
+    assert_eq!(proxy.metrics.tcp_proxy_connections_total.get(), 1);
+    proxy.stop().await;
+}
+
+#[tokio::test]
+async fn proxy_adds_latency_each_way() {
+    let proxy = start_proxy(TcpFaults {
+        latency_ms: 150,
+        ..TcpFaults::default()
+    })
+    .await;
+    let t0 = Instant::now();
+    round_trip(proxy.addr, b"ping").await;
+    assert!(
+        t0.elapsed() >= Duration::from_millis(300),
+        "{:?}",
+        t0.elapsed()
+    );
+    let faults = &proxy.metrics.tcp_proxy_faults_total;
+    assert_eq!(faults.with_label_values(&["latency"]).get(), 2);
+    proxy.stop().await;
+}
+
+#[tokio::test]
+async fn proxy_caps_bandwidth() {
+    let proxy = start_proxy(TcpFaults {
+        bandwidth_kb_per_second: 64,
+        ..TcpFaults::default()
+    })
+    .await;
+    let payload = vec![7u8; 64 * 1024];
+    let t0 = Instant::now();
+    let got = round_trip(proxy.addr, &payload).await;
+    assert_eq!(got, payload);
+    assert!(
+        t0.elapsed() >= Duration::from_millis(700),
+        "{:?}",
+        t0.elapsed()
+    );
+    proxy.stop().await;
+}
+
+#[tokio::test]
+async fn proxy_resets_connections() {
+    let proxy = start_proxy(TcpFaults {
+        reset_percent: 100,
+        ..TcpFaults::default()
+    })
+    .await;
+    let mut sock = TcpStream::connect(proxy.addr).await.unwrap();
+    let mut buf = [0u8; 8];
+    let err = sock.read(&mut buf).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::ConnectionReset);
+    let faults = &proxy.metrics.tcp_proxy_faults_total;
+    assert_eq!(faults.with_label_values(&["reset"]).get(), 1);
+    proxy.stop().await;
+}
+
+#[tokio::test]
+async fn proxy_blackholes_and_releases_on_stop() {
+    let proxy = start_proxy(TcpFaults {
+        blackhole_percent: 100,
+        ..TcpFaults::default()
+    })
+    .await;
+    let mut sock = TcpStream::connect(proxy.addr).await.unwrap();
+    sock.write_all(b"anyone there?").await.unwrap();
+    let mut buf = [0u8; 8];
+    let read = tokio::time::timeout(Duration::from_millis(300), sock.read(&mut buf)).await;
+    assert!(read.is_err(), "blackholed connection answered");
+    assert_eq!(proxy.metrics.tcp_proxy_active_connections.get(), 1);
+    let metrics = proxy.metrics.clone();
+    proxy.stop().await;
+    assert_eq!(metrics.tcp_proxy_active_connections.get(), 0);
+    let closed = tokio::time::timeout(Duration::from_secs(1), sock.read(&mut buf)).await;
+    assert!(matches!(closed, Ok(Ok(0) | Err(_))), "connection kept open");
+}
+
+#[tokio::test]
+async fn connections_past_the_cap_are_closed() {
+    // Blackholed, so the held connections never reach an upstream.
+    let proxy = start_proxy(TcpFaults {
+        blackhole_percent: 100,
+        ..TcpFaults::default()
+    })
+    .await;
+    let mut held = Vec::new();
+    for _ in 0..MAX_CONNECTIONS {
+        held.push(TcpStream::connect(proxy.addr).await.unwrap());
+    }
+    let cap = i64::try_from(MAX_CONNECTIONS).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while proxy.metrics.tcp_proxy_active_connections.get() < cap {
+        assert!(Instant::now() < deadline, "held connections not accepted");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let mut extra = TcpStream::connect(proxy.addr).await.unwrap();
+    let mut buf = [0u8; 8];
+    let closed = tokio::time::timeout(Duration::from_secs(2), extra.read(&mut buf)).await;
+    assert!(
+        matches!(closed, Ok(Ok(0) | Err(_))),
+        "connection past the cap kept open"
+    );
+    let faults = &proxy.metrics.tcp_proxy_faults_total;
+    assert_eq!(faults.with_label_values(&["connection_limit"]).get(), 1);
+    assert_eq!(proxy.metrics.tcp_proxy_active_connections.get(), cap);
+    assert_eq!(
+        proxy.metrics.tcp_proxy_connections_total.get(),
+        u64::try_from(MAX_CONNECTIONS).unwrap()
+    );
+    proxy.stop().await;
+}
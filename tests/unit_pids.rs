@@ -0,0 +1,119 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+use chimp_chaos_agent::domain::PidsMode;
+use chimp_chaos_agent::lib_pids::{parse_pids_max, pids_cgroup_dir, pids_load, PidsLoadSpec};
+use chimp_chaos_agent::metrics::Metrics;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Serializes tests that count this process's threads and children.
+static PIDS_TESTS: Mutex<()> = Mutex::const_new(());
+
+fn thread_count() -> usize {
+    std::fs::read_dir("/proc/self/task").unwrap().count()
+}
+
+/// Live `cat` children of this process, found by scanning `/proc`.
+fn cat_children() -> usize {
+    let me = std::process::id().to_string();
+    std::fs::read_dir("/proc")
+        .unwrap()
+        .filter_map(|e| std::fs::read_to_string(e.ok()?.path().join("stat")).ok())
+        .filter(|stat| {
+            let Some((comm, rest)) = stat.split_once(") ") else {
+                return false;
+            };
+            let mut fields = rest.split_whitespace();
+            let state = fields.next().unwrap_or("");
+            comm.ends_with("(cat") && state != "Z" && fields.next() == Some(me.as_str())
+        })
+        .count()
+}
+
+#[test]
+fn cgroup_dir_lookup() {
+    let root = std::env::temp_dir().join(format!("chimp-cgroup-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("pids/kubepods/pod1")).unwrap();
+    std::fs::write(root.join("pids/kubepods/pod1/pids.max"), "100\n").unwrap();
+    std::fs::create_dir_all(root.join("unified")).unwrap();
+    std::fs::write(root.join("unified/pids.max"), "max\n").unwrap();
+    let v1 = "8:pids:/kubepods/pod1\n1:cpu,cpuacct:/kubepods/pod1\n0::/\n";
+    assert_eq!(
+        pids_cgroup_dir(&root, v1),
+        Some(root.join("pids/kubepods/pod1"))
+    );
+    assert_eq!(
+        pids_cgroup_dir(&root, "0::/unified\n"),
+        Some(root.join("unified"))
+    );
+    assert_eq!(pids_cgroup_dir(&root, "0::/missing\n"), None);
+    assert_eq!(parse_pids_max("100\n"), Some(100));
+    assert_eq!(parse_pids_max("max\n"), None);
+    std::fs::remove_dir_all(&root).ok();
+}
+
+#[tokio::test]
+async fn pids_hog_threads_joined() {
+    let _serial = PIDS_TESTS.lock().await;
+    let m = Metrics::new().expect("metrics");
+    let before = thread_count();
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(pids_load(
+        "e".into(),
+        PidsLoadSpec {
+            count: 50,
+            mode: PidsMode::Threads,
+        },
+        30,
+        m.clone(),
+        cancel.clone(),
+    ));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(m.pids_hog_live.get(), 50);
+    assert!(thread_count() >= before + 50);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(m.pids_hog_live.get(), 0);
+    assert!(thread_count() < before + 50);
+}
+
+#[tokio::test]
+async fn pids_hog_children_reaped() {
+    let _serial = PIDS_TESTS.lock().await;
+    let m = Metrics::new().expect("metrics");
+    pids_load(
+        "e".into(),
+        PidsLoadSpec {
+            count: 5,
+            mode: PidsMode::Processes,
+        },
+        1,
+        m.clone(),
+        CancellationToken::new(),
+    )
+    .await
+    .expect("ok");
+    assert_eq!(m.pids_hog_live.get(), 0);
+    assert_eq!(cat_children(), 0);
+
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(pids_load(
+        "e".into(),
+        PidsLoadSpec {
+            count: 5,
+            mode: PidsMode::Processes,
+        },
+        30,
+        m.clone(),
+        cancel.clone(),
+    ));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(m.pids_hog_live.get(), 5);
+    assert_eq!(cat_children(), 5);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(cat_children(), 0);
+}
@@ -0,0 +1,127 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+use chimp_chaos_agent::domain::PortTarget;
+use chimp_chaos_agent::lib_port::{
+    connect_error_reason, parse_port_range, port_exhaustion_load, port_target_count,
+    PortExhaustionSpec,
+};
+use chimp_chaos_agent::metrics::Metrics;
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+/// Listener that holds every connection and counts the ones the peer closed.
+async fn sink() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let closed = Arc::new(AtomicUsize::new(0));
+    let counter = closed.clone();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let counter = counter.clone();
+            tokio::spawn(async move {
+                let mut buf = [0u8; 16];
+                while matches!(stream.read(&mut buf).await, Ok(n) if n > 0) {}
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+    });
+    (addr, closed)
+}
+
+async fn wait_for(what: &str, mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[test]
+fn port_range_parses_and_sizes_targets() {
+    assert_eq!(parse_port_range("32768\t60999\n").unwrap(), (32768, 60999));
+    assert!(parse_port_range("32768").is_err());
+    assert!(parse_port_range("60999 32768").is_err());
+    assert!(parse_port_range("1 2 3").is_err());
+    let range = (1000, 1999);
+    assert_eq!(port_target_count(PortTarget::Count { count: 7 }, range), 7);
+    assert_eq!(
+        port_target_count(PortTarget::RangePercent { percent: 25 }, range),
+        250
+    );
+    assert_eq!(
+        port_target_count(PortTarget::RangePercent { percent: 150 }, range),
+        1000
+    );
+}
+
+#[test]
+fn connect_errors_are_classified() {
+    let errno = |raw| std::io::Error::from_raw_os_error(raw);
+    assert_eq!(connect_error_reason(&errno(99)), "addr_not_available");
+    assert_eq!(connect_error_reason(&errno(24)), "fd_limit");
+    assert_eq!(connect_error_reason(&errno(111)), "refused");
+    let timed_out = std::io::Error::from(ErrorKind::TimedOut);
+    assert_eq!(connect_error_reason(&timed_out), "timeout");
+    assert_eq!(connect_error_reason(&errno(13)), "other");
+}
+
+#[tokio::test]
+async fn holds_connections_until_stopped() {
+    let (target, closed) = sink().await;
+    let metrics = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(port_exhaustion_load(
+        "p".into(),
+        PortExhaustionSpec {
+            target,
+            ports: PortTarget::Count { count: 100 },
+        },
+        30,
+        metrics.clone(),
+        cancel.clone(),
+    ));
+    wait_for("100 held", || metrics.port_exhaustion_held.get() == 100).await;
+    assert_eq!(closed.load(Ordering::SeqCst), 0);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(metrics.port_exhaustion_held.get(), 0);
+    wait_for("all closed", || closed.load(Ordering::SeqCst) == 100).await;
+}
+
+#[tokio::test]
+async fn refused_connects_are_counted_and_retried() {
+    let dead = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let metrics = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(port_exhaustion_load(
+        "p".into(),
+        PortExhaustionSpec {
+            target: dead.to_string(),
+            ports: PortTarget::Count { count: 1 },
+        },
+        30,
+        metrics.clone(),
+        cancel.clone(),
+    ));
+    let refused = || {
+        metrics
+            .port_exhaustion_connect_errors_total
+            .with_label_values(&["refused"])
+            .get()
+    };
+    wait_for("two refusals", || refused() >= 2).await;
+    assert_eq!(metrics.port_exhaustion_held.get(), 0);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+}
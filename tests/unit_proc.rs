@@ -0,0 +1,584 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+use chimp_chaos_agent::domain::{
+    Experiment, ExperimentKind, ExperimentParams, KillSignal, LoadController, PauseDuty,
+    ProcessMatch,
+};
+use chimp_chaos_agent::lib_proc::{
+    find_processes, join_cmdline, parse_stat_start_time, process_kill_load, process_pause_load,
+    process_start_time, process_throttle_load, ProcessInfo, ProcessKillSpec, ProcessMatcher,
+    ProcessPauseSpec, ProcessThrottleSpec,
+};
+use chimp_chaos_agent::metrics::Metrics;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+/// `sleep` child with an argument no other process in the sandbox carries.
+struct Sleeper(Child);
+
+impl Sleeper {
+    /// Returns once exec has filled in the cmdline, which happens after the
+    /// comm already reads `sleep`.
+    fn spawn(tag: &str) -> Self {
+        let child = Self(Command::new("sleep").arg(tag).spawn().expect("spawn sleep"));
+        let cmdline = format!("/proc/{}/cmdline", child.pid());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !std::fs::read(&cmdline)
+            .unwrap_or_default()
+            .starts_with(b"sleep\0")
+        {
+            assert!(Instant::now() < deadline, "sleep {tag} never exec'd");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        child
+    }
+
+    fn pid(&self) -> u32 {
+        self.0.id()
+    }
+
+    fn wait_exit(&mut self) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while self.0.try_wait().unwrap().is_none() {
+            assert!(
+                Instant::now() < deadline,
+                "pid {} still running",
+                self.pid()
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Scheduler state letter from `/proc/<pid>/stat`, e.g. `S` or `T`.
+    fn state(&self) -> char {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", self.pid())).unwrap();
+        let after = stat.rsplit_once(") ").unwrap().1;
+        after.chars().next().unwrap()
+    }
+
+    fn wait_state(&self, want: char) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while self.state() != want {
+            assert!(
+                Instant::now() < deadline,
+                "state {} != {want}",
+                self.state()
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Drop for Sleeper {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn matches(target: &ProcessMatch, info: &ProcessInfo) -> bool {
+    ProcessMatcher::new(target).unwrap().matches(info)
+}
+
+#[test]
+fn matchers_check_every_field() {
+    let cmdline = join_cmdline(b"java\0-jar\0app.jar\0");
+    assert_eq!(cmdline, "java -jar app.jar");
+    let java = ProcessInfo {
+        pid: 42,
+        comm: "java".into(),
+        cmdline,
+        exe: Some("java-17".into()),
+        cgroups: vec!["/system.slice/app.service".into()],
+        start_time: 0,
+    };
+    let other = ProcessInfo {
+        pid: 43,
+        comm: "javac".into(),
+        cmdline: "java -cp app".into(),
+        exe: None,
+        cgroups: vec!["/system.slice/app.service-old".into()],
+        start_time: 0,
+    };
+    let cases = [
+        ProcessMatch::Pid { pid: 42 },
+        ProcessMatch::Name {
+            name: "java".into(),
+        },
+        ProcessMatch::Cmdline {
+            contains: "-jar app".into(),
+        },
+        ProcessMatch::Exe {
+            name: "java-17".into(),
+        },
+        ProcessMatch::CmdlineRegex {
+            regex: r"-jar \w+\.jar$".into(),
+        },
+        ProcessMatch::Cgroup {
+            path: "/system.slice/app.service".into(),
+        },
+    ];
+    for target in &cases {
+        assert!(matches(target, &java), "{target}");
+        assert!(!matches(target, &other), "{target}");
+    }
+    let parent = ProcessMatch::Cgroup {
+        path: "/system.slice/".into(),
+    };
+    assert!(matches(&parent, &java) && matches(&parent, &other));
+    assert!(ProcessMatcher::new(&ProcessMatch::CmdlineRegex {
+        regex: "(unclosed".into()
+    })
+    .is_err());
+}
+
+#[test]
+fn reads_exe_and_cgroups_of_a_child() {
+    let child = Sleeper::spawn("911.101");
+    let info = ProcessInfo::read(child.pid()).unwrap();
+    assert_eq!(info.comm, "sleep");
+    assert_eq!(info.cmdline, "sleep 911.101");
+    let exe = std::fs::read_link(format!("/proc/{}/exe", child.pid())).unwrap();
+    assert_eq!(
+        info.exe.as_deref(),
+        exe.file_name().and_then(|n| n.to_str())
+    );
+    let own = ProcessInfo::read(std::process::id()).unwrap();
+    assert_eq!(info.cgroups, own.cgroups);
+    let by_exe = ProcessMatch::Exe {
+        name: info.exe.clone().unwrap(),
+    };
+    assert!(find_processes(&by_exe).unwrap().contains(&child.pid()));
+}
+
+#[test]
+fn finds_children_but_never_the_agent() {
+    let a = Sleeper::spawn("911.001");
+    let b = Sleeper::spawn("911.002");
+    let found = find_processes(&ProcessMatch::Cmdline {
+        contains: "sleep 911.00".into(),
+    })
+    .unwrap();
+    let mut want = vec![a.pid(), b.pid()];
+    want.sort_unstable();
+    assert_eq!(found, want);
+    assert_eq!(
+        find_processes(&ProcessMatch::Pid { pid: a.pid() }).unwrap(),
+        vec![a.pid()]
+    );
+    let own = std::process::id();
+    assert!(find_processes(&ProcessMatch::Pid { pid: own })
+        .unwrap()
+        .is_empty());
+    let comm = std::fs::read_to_string(format!("/proc/{own}/comm")).unwrap();
+    assert!(!find_processes(&ProcessMatch::Name {
+        name: comm.trim_end().into()
+    })
+    .unwrap()
+    .contains(&own));
+    assert!(find_processes(&ProcessMatch::Pid { pid: 1 })
+        .unwrap()
+        .is_empty());
+    let init = std::fs::read_to_string("/proc/1/comm").unwrap();
+    assert!(!find_processes(&ProcessMatch::Name {
+        name: init.trim_end().into()
+    })
+    .unwrap()
+    .contains(&1));
+}
+
+#[test]
+fn start_time_tells_reused_pids_apart() {
+    let stat = "4242 (odd) name (x)) S 1 4242 4242 0 -1 4194560 100 0 0 0 \
+                5 3 0 0 20 0 1 0 987654 1000 10 18446744073709551615";
+    assert_eq!(parse_stat_start_time(stat), Some(987_654));
+    assert_eq!(parse_stat_start_time("4242 (short) S 1"), None);
+
+    let mut child = Sleeper::spawn("911.201");
+    let pid = child.pid();
+    let started = process_start_time(pid).unwrap();
+    assert_eq!(ProcessInfo::read(pid).unwrap().start_time, started);
+    assert_ne!(process_start_time(std::process::id()), Some(0));
+    child.0.kill().unwrap();
+    child.0.wait().unwrap();
+    assert_eq!(process_start_time(pid), None);
+}
+
+fn start(
+    id: &str,
+    target: ProcessMatch,
+    duty: Option<PauseDuty>,
+) -> (
+    LoadController,
+    Metrics,
+    tokio_util::sync::CancellationToken,
+    tokio::task::JoinHandle<anyhow::Result<()>>,
+) {
+    let ctrl = LoadController::default();
+    let params = ExperimentParams::ProcessPause {
+        target: target.clone(),
+        max_targets: 16,
+        duty,
+    };
+    let exp = Experiment::new(id.into(), ExperimentKind::PROCESS_PAUSE, params, 30, 0);
+    let _handle = ctrl.start(id, &exp).expect("start");
+    let metrics = Metrics::new().expect("metrics");
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let load = tokio::spawn(process_pause_load(
+        id.into(),
+        ProcessPauseSpec {
+            target,
+            max_targets: 16,
+            duty,
+        },
+        30,
+        metrics.clone(),
+        ctrl.clone(),
+        cancel.clone(),
+    ));
+    (ctrl, metrics, cancel, load)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn freeze_stops_target_until_cancelled() {
+    let child = Sleeper::spawn("912.001");
+    let (ctrl, metrics, cancel, load) = start(
+        "freeze",
+        ProcessMatch::Cmdline {
+            contains: "sleep 912.001".into(),
+        },
+        None,
+    );
+    child.wait_state('T');
+    assert_eq!(
+        ctrl.state.lock().get("freeze").unwrap().target_pids,
+        vec![child.pid()]
+    );
+    assert_eq!(metrics.process_paused.get(), 1);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_ne!(child.state(), 'T');
+    assert_eq!(metrics.process_paused.get(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn duty_cycle_alternates_and_resumes() {
+    let child = Sleeper::spawn("913.001");
+    let (_ctrl, metrics, cancel, load) = start(
+        "duty",
+        ProcessMatch::Pid { pid: child.pid() },
+        Some(PauseDuty {
+            pause_ms: 100,
+            resume_ms: 100,
+        }),
+    );
+    let mut seen = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while Instant::now() < deadline {
+        seen.push(child.state());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(seen.contains(&'T'), "{seen:?}");
+    assert!(seen.iter().any(|&s| s != 'T'), "{seen:?}");
+    assert!(metrics.process_pause_cycles_total.get() >= 3);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_ne!(child.state(), 'T');
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn duty_cycle_drops_exited_targets() {
+    let mut gone = Sleeper::spawn("913.101");
+    let kept = Sleeper::spawn("913.102");
+    let (ctrl, _metrics, cancel, load) = start(
+        "shrink",
+        ProcessMatch::Cmdline {
+            contains: "sleep 913.10".into(),
+        },
+        Some(PauseDuty {
+            pause_ms: 50,
+            resume_ms: 50,
+        }),
+    );
+    let targets = || ctrl.state.lock().get("shrink").unwrap().target_pids.clone();
+    let wait_targets = |want: Vec<u32>| async move {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while targets() != want {
+            assert!(Instant::now() < deadline, "targets {:?}", targets());
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    let mut both = vec![gone.pid(), kept.pid()];
+    both.sort_unstable();
+    wait_targets(both).await;
+    gone.0.kill().unwrap();
+    gone.wait_exit();
+    wait_targets(vec![kept.pid()]).await;
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+}
+
+#[tokio::test]
+async fn freeze_drops_exited_targets() {
+    let mut gone = Sleeper::spawn("913.201");
+    let kept = Sleeper::spawn("913.202");
+    let (ctrl, metrics, cancel, load) = start(
+        "frozen",
+        ProcessMatch::Cmdline {
+            contains: "sleep 913.20".into(),
+        },
+        None,
+    );
+    let targets = || ctrl.state.lock().get("frozen").unwrap().target_pids.clone();
+    let wait_targets = |want: Vec<u32>| async move {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while targets() != want {
+            assert!(Instant::now() < deadline, "targets {:?}", targets());
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    let mut both = vec![gone.pid(), kept.pid()];
+    both.sort_unstable();
+    wait_targets(both).await;
+    gone.0.kill().unwrap();
+    gone.wait_exit();
+    wait_targets(vec![kept.pid()]).await;
+    assert_eq!(metrics.process_paused.get(), 1);
+    assert_eq!(kept.state(), 'T');
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+}
+
+#[tokio::test]
+async fn unmatched_target_fails() {
+    let (_ctrl, _metrics, _cancel, load) = start(
+        "none",
+        ProcessMatch::Cmdline {
+            contains: "no such process 914.001".into(),
+        },
+        None,
+    );
+    assert!(load.await.expect("join").is_err());
+}
+
+#[tokio::test]
+async fn broader_match_than_max_targets_fails() {
+    let a = Sleeper::spawn("914.101");
+    let b = Sleeper::spawn("914.102");
+    let (_ctrl, metrics, _cancel, load) = start_kill(
+        "wide",
+        ProcessKillSpec {
+            target: ProcessMatch::Cmdline {
+                contains: "sleep 914.10".into(),
+            },
+            max_targets: 1,
+            signal: KillSignal::Sigkill,
+            interval_seconds: None,
+            dry_run: false,
+        },
+    );
+    let err = load.await.expect("join").unwrap_err();
+    assert!(format!("{err:#}").contains("max_targets 1"), "{err:#}");
+    assert_eq!(kills(&metrics, "SIGKILL", "sent"), 0);
+    assert_eq!(a.state(), 'S');
+    assert_eq!(b.state(), 'S');
+}
+
+fn start_kill(
+    id: &str,
+    spec: ProcessKillSpec,
+) -> (
+    LoadController,
+    Metrics,
+    tokio_util::sync::CancellationToken,
+    tokio::task::JoinHandle<anyhow::Result<()>>,
+) {
+    let ctrl = LoadController::default();
+    let params = ExperimentParams::ProcessKill {
+        target: spec.target.clone(),
+        max_targets: spec.max_targets,
+        signal: spec.signal,
+        interval_seconds: spec.interval_seconds,
+        dry_run: spec.dry_run,
+    };
+    let exp = Experiment::new(id.into(), ExperimentKind::PROCESS_KILL, params, 30, 0);
+    let _handle = ctrl.start(id, &exp).expect("start");
+    let metrics = Metrics::new().expect("metrics");
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let load = tokio::spawn(process_kill_load(
+        id.into(),
+        spec,
+        30,
+        metrics.clone(),
+        ctrl.clone(),
+        cancel.clone(),
+    ));
+    (ctrl, metrics, cancel, load)
+}
+
+fn events(ctrl: &LoadController, id: &str) -> Vec<String> {
+    ctrl.state
+        .lock()
+        .get(id)
+        .unwrap()
+        .events
+        .iter()
+        .map(|e| e.message.clone())
+        .collect()
+}
+
+fn kills(metrics: &Metrics, signal: &str, outcome: &str) -> u64 {
+    metrics
+        .process_kills_total
+        .with_label_values(&[signal, outcome])
+        .get()
+}
+
+/// The counter moves after the event is logged, so the log is complete too.
+async fn wait_kills(metrics: &Metrics, signal: &str, outcome: &str, n: u64) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while kills(metrics, signal, outcome) < n {
+        assert!(
+            Instant::now() < deadline,
+            "fewer than {n} {signal} {outcome}"
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn kill_once_signals_and_logs() {
+    let mut child = Sleeper::spawn("916.001");
+    let (ctrl, metrics, cancel, load) = start_kill(
+        "once",
+        ProcessKillSpec {
+            target: ProcessMatch::CmdlineRegex {
+                regex: r"^sleep 916\.00\d$".into(),
+            },
+            max_targets: 16,
+            signal: KillSignal::Sigterm,
+            interval_seconds: None,
+            dry_run: false,
+        },
+    );
+    child.wait_exit();
+    wait_kills(&metrics, "SIGTERM", "sent", 1).await;
+    assert_eq!(
+        events(&ctrl, "once"),
+        vec![format!("sent SIGTERM to pid {} (sleep)", child.pid())]
+    );
+    assert_eq!(kills(&metrics, "SIGTERM", "sent"), 1);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn dry_run_only_reports() {
+    let child = Sleeper::spawn("917.001");
+    let (ctrl, metrics, cancel, load) = start_kill(
+        "dry",
+        ProcessKillSpec {
+            target: ProcessMatch::Pid { pid: child.pid() },
+            max_targets: 16,
+            signal: KillSignal::Sigkill,
+            interval_seconds: None,
+            dry_run: true,
+        },
+    );
+    wait_kills(&metrics, "SIGKILL", "dry_run", 1).await;
+    assert_eq!(
+        events(&ctrl, "dry"),
+        vec![format!(
+            "dry run: would send SIGKILL to pid {} (sleep)",
+            child.pid()
+        )]
+    );
+    assert_eq!(
+        ctrl.state.lock().get("dry").unwrap().target_pids,
+        vec![child.pid()]
+    );
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_eq!(child.state(), 'S');
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn interval_hits_restarted_processes() {
+    let target = ProcessMatch::Cmdline {
+        contains: "sleep 918.001".into(),
+    };
+    let mut first = Sleeper::spawn("918.001");
+    let (ctrl, metrics, cancel, load) = start_kill(
+        "loop",
+        ProcessKillSpec {
+            target,
+            max_targets: 16,
+            signal: KillSignal::Sigkill,
+            interval_seconds: Some(1),
+            dry_run: false,
+        },
+    );
+    first.wait_exit();
+    // What a supervisor would do: bring the target back under a new pid.
+    let mut second = Sleeper::spawn("918.001");
+    second.wait_exit();
+    wait_kills(&metrics, "SIGKILL", "sent", 2).await;
+    let log = events(&ctrl, "loop");
+    assert!(log.contains(&format!("sent SIGKILL to pid {} (sleep)", first.pid())));
+    assert!(log.contains(&format!("sent SIGKILL to pid {} (sleep)", second.pid())));
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn throttle_stops_target_for_the_rest_of_each_window() {
+    let child = Sleeper::spawn("920.001");
+    let ctrl = LoadController::default();
+    let target = ProcessMatch::Pid { pid: child.pid() };
+    let params = ExperimentParams::ProcessThrottle {
+        target: target.clone(),
+        max_targets: 16,
+        cpu_percent: 25,
+    };
+    let exp = Experiment::new("t".into(), ExperimentKind::PROCESS_THROTTLE, params, 30, 0);
+    let _handle = ctrl.start("t", &exp).expect("start");
+    let metrics = Metrics::new().expect("metrics");
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let load = tokio::spawn(process_throttle_load(
+        "t".into(),
+        ProcessThrottleSpec {
+            target,
+            max_targets: 16,
+            cpu_percent: 25,
+        },
+        30,
+        metrics.clone(),
+        ctrl.clone(),
+        cancel.clone(),
+    ));
+    let mut stopped = 0u32;
+    let mut samples = 0u32;
+    let deadline = Instant::now() + Duration::from_secs(3);
+    while Instant::now() < deadline {
+        samples += 1;
+        if child.state() == 'T' {
+            stopped += 1;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    // Stopped for about 75% of wall time; wide bounds for a loaded host.
+    let share = f64::from(stopped) / f64::from(samples);
+    assert!(
+        (0.5..0.95).contains(&share),
+        "stopped {share:.2} of the time"
+    );
+    assert_eq!(metrics.process_throttle_percent.get(), 25);
+    assert!(metrics.process_pause_cycles_total.get() >= 2);
+    cancel.cancel();
+    load.await.expect("join").expect("ok");
+    assert_ne!(child.state(), 'T');
+    assert_eq!(metrics.process_paused.get(), 0);
+    assert_eq!(metrics.process_throttle_percent.get(), 0);
+}
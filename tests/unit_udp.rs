@@ -0,0 +1,307 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![warn(clippy::pedantic)]
+
+use chimp_chaos_agent::domain::{
+    Experiment, ExperimentKind, ExperimentParams, LoadController, UdpFaults,
+};
+use chimp_chaos_agent::lib_udp::{udp_proxy_load, UdpProxySpec, UdpShaper, MAX_SESSIONS};
+use chimp_chaos_agent::metrics::Metrics;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+
+/// Upstream that echoes every datagram back and reports it on `seen`.
+async fn upstream_server() -> (SocketAddr, mpsc::UnboundedReceiver<Vec<u8>>) {
+    let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = sock.local_addr().unwrap();
+    let (seen, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 2048];
+        while let Ok((n, from)) = sock.recv_from(&mut buf).await {
+            let _ = seen.send(buf[..n].to_vec());
+            let _ = sock.send_to(&buf[..n], from).await;
+        }
+    });
+    (addr, rx)
+}
+
+struct Proxy {
+    addr: SocketAddr,
+    seen: mpsc::UnboundedReceiver<Vec<u8>>,
+    ctrl: LoadController,
+    metrics: Metrics,
+    cancel: CancellationToken,
+    load: JoinHandle<anyhow::Result<()>>,
+}
+
+async fn start_proxy(faults: UdpFaults) -> Proxy {
+    let (upstream, seen) = upstream_server().await;
+    start_proxy_to(upstream, seen, faults).await
+}
+
+async fn start_proxy_to(
+    upstream: SocketAddr,
+    seen: mpsc::UnboundedReceiver<Vec<u8>>,
+    faults: UdpFaults,
+) -> Proxy {
+    let ctrl = LoadController::default();
+    let params = ExperimentParams::UdpProxy {
+        listen: "127.0.0.1:0".into(),
+        upstream: upstream.to_string(),
+        faults,
+    };
+    let exp = Experiment::new("u".into(), ExperimentKind::UDP_PROXY, params, 30, 0);
+    let _handle = ctrl.start("u", &exp).expect("start");
+    let metrics = Metrics::new().expect("metrics");
+    let cancel = CancellationToken::new();
+    let load = tokio::spawn(udp_proxy_load(
+        "u".into(),
+        UdpProxySpec {
+            listen: "127.0.0.1:0".into(),
+            upstream: upstream.to_string(),
+            faults,
+        },
+        30,
+        metrics.clone(),
+        ctrl.clone(),
+        cancel.clone(),
+    ));
+    let addr = loop {
+        let bound = ctrl
+            .state
+            .lock()
+            .get("u")
+            .and_then(|st| st.listen_addr.clone());
+        if let Some(addr) = bound {
+            break addr.parse().unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    Proxy {
+        addr,
+        seen,
+        ctrl,
+        metrics,
+        cancel,
+        load,
+    }
+}
+
+impl Proxy {
+    async fn stop(self) {
+        self.cancel.cancel();
+        self.load.await.expect("join").expect("ok");
+    }
+
+    fn seed(&self) -> Option<u64> {
+        self.ctrl.state.lock().get("u").and_then(|st| st.seed)
+    }
+
+    fn faults(&self, fault: &str) -> u64 {
+        self.metrics
+            .udp_proxy_faults_total
+            .with_label_values(&[fault])
+            .get()
+    }
+
+    /// Datagrams the upstream received within `wait`.
+    async fn drain_seen(&mut self, wait: Duration) -> Vec<Vec<u8>> {
+        let mut seen = Vec::new();
+        while let Ok(Some(d)) = timeout(wait, self.seen.recv()).await {
+            seen.push(d);
+        }
+        seen
+    }
+}
+
+async fn client(proxy: SocketAddr) -> UdpSocket {
+    let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    sock.connect(proxy).await.unwrap();
+    sock
+}
+
+#[test]
+fn shaper_replays_from_seed() {
+    let faults = UdpFaults {
+        loss_percent: 30,
+        duplicate_percent: 20,
+        reorder_percent: 10,
+        delay_ms: 40,
+        jitter_ms: 30,
+        seed: None,
+    };
+    let mut a = UdpShaper::new(faults, 7);
+    let mut b = UdpShaper::new(faults, 7);
+    let run_a: Vec<_> = (0..500).map(|_| a.verdict()).collect();
+    let run_b: Vec<_> = (0..500).map(|_| b.verdict()).collect();
+    assert_eq!(run_a, run_b);
+    let lost = run_a.iter().filter(|v| v.lost).count();
+    assert!((100..=200).contains(&lost), "lost {lost} of 500");
+    assert!(run_a.iter().all(|v| !(v.lost && v.duplicated)));
+    assert!(run_a
+        .iter()
+        .filter(|v| !v.reordered)
+        .all(|v| v.delay >= Duration::from_millis(10) && v.delay <= Duration::from_millis(70)));
+
+    let mut clean = UdpShaper::new(UdpFaults::default(), 1);
+    assert!((0..100).all(|_| {
+        let v = clean.verdict();
+        !v.lost && !v.duplicated && !v.reordered && v.delay.is_zero()
+    }));
+}
+
+#[tokio::test]
+async fn relays_both_ways_without_faults() {
+    let proxy = start_proxy(UdpFaults::default()).await;
+    let sock = client(proxy.addr).await;
+    let mut buf = [0u8; 64];
+    for i in 0..3u8 {
+        sock.send(&[i; 8]).await.unwrap();
+        let n = timeout(Duration::from_secs(2), sock.recv(&mut buf))
+            .await
+            .expect("reply")
+            .unwrap();
+        assert_eq!(&buf[..n], &[i; 8]);
+    }
+    let delivered = |dir: &str| {
+        proxy
+            .metrics
+            .udp_proxy_datagrams_total
+            .with_label_values(&[dir])
+            .get()
+    };
+    assert_eq!(delivered("upstream"), 3);
+    assert_eq!(delivered("downstream"), 3);
+    assert_eq!(proxy.metrics.udp_proxy_sessions.get(), 1);
+    // A seed drawn at random is still recorded for replay.
+    assert!(proxy.seed().is_some());
+    let metrics = proxy.metrics.clone();
+    proxy.stop().await;
+    assert_eq!(metrics.udp_proxy_sessions.get(), 0);
+}
+
+#[tokio::test]
+async fn total_loss_drops_everything() {
+    let mut proxy = start_proxy(UdpFaults {
+        loss_percent: 100,
+        ..UdpFaults::default()
+    })
+    .await;
+    let sock = client(proxy.addr).await;
+    for _ in 0..5 {
+        sock.send(b"gone").await.unwrap();
+    }
+    assert!(proxy
+        .drain_seen(Duration::from_millis(300))
+        .await
+        .is_empty());
+    assert_eq!(proxy.faults("loss"), 5);
+    proxy.stop().await;
+}
+
+#[tokio::test]
+async fn duplicates_reach_upstream_twice() {
+    let mut proxy = start_proxy(UdpFaults {
+        duplicate_percent: 100,
+        ..UdpFaults::default()
+    })
+    .await;
+    let sock = client(proxy.addr).await;
+    sock.send(b"twice").await.unwrap();
+    let seen = proxy.drain_seen(Duration::from_millis(300)).await;
+    assert_eq!(seen, vec![b"twice".to_vec(), b"twice".to_vec()]);
+    // Both echoes are duplicated again on the way back.
+    assert_eq!(proxy.faults("duplicate"), 3);
+    proxy.stop().await;
+}
+
+#[tokio::test]
+async fn reordered_datagrams_are_overtaken() {
+    let mut proxy = start_proxy(UdpFaults {
+        reorder_percent: 50,
+        seed: Some(42),
+        ..UdpFaults::default()
+    })
+    .await;
+    let sock = client(proxy.addr).await;
+    let sent: Vec<Vec<u8>> = (0..20u8).map(|i| vec![i]).collect();
+    for d in &sent {
+        sock.send(d).await.unwrap();
+    }
+    let seen = proxy.drain_seen(Duration::from_millis(300)).await;
+    let mut sorted = seen.clone();
+    sorted.sort();
+    assert_eq!(sorted, sent);
+    assert_ne!(seen, sent);
+    assert!(proxy.faults("reorder") > 0);
+    assert_eq!(proxy.seed(), Some(42));
+    proxy.stop().await;
+}
+
+#[tokio::test]
+async fn new_clients_past_session_cap_are_dropped() {
+    let mut proxy = start_proxy(UdpFaults::default()).await;
+    let mut clients = Vec::new();
+    let mut buf = [0u8; 16];
+    for _ in 0..MAX_SESSIONS {
+        let sock = client(proxy.addr).await;
+        sock.send(b"in").await.unwrap();
+        timeout(Duration::from_secs(2), sock.recv(&mut buf))
+            .await
+            .expect("reply")
+            .unwrap();
+        clients.push(sock);
+    }
+    let sessions = i64::try_from(MAX_SESSIONS).unwrap();
+    assert_eq!(proxy.metrics.udp_proxy_sessions.get(), sessions);
+    proxy.drain_seen(Duration::from_millis(50)).await;
+
+    let extra = client(proxy.addr).await;
+    extra.send(b"out").await.unwrap();
+    assert!(timeout(Duration::from_millis(300), extra.recv(&mut buf))
+        .await
+        .is_err());
+    assert_eq!(proxy.faults("session_limit"), 1);
+    assert_eq!(proxy.metrics.udp_proxy_sessions.get(), sessions);
+    assert!(proxy.drain_seen(Duration::from_millis(50)).await.is_empty());
+    // Known clients are still relayed.
+    clients[0].send(b"again").await.unwrap();
+    timeout(Duration::from_secs(2), clients[0].recv(&mut buf))
+        .await
+        .expect("reply")
+        .unwrap();
+    proxy.stop().await;
+}
+
+#[tokio::test]
+async fn failed_session_open_drops_the_datagram() {
+    // Connecting to broadcast without SO_BROADCAST is refused, so every
+    // session open fails.
+    let (_seen_tx, seen) = mpsc::unbounded_channel();
+    let proxy = start_proxy_to(
+        "255.255.255.255:9".parse().unwrap(),
+        seen,
+        UdpFaults::default(),
+    )
+    .await;
+    let sock = client(proxy.addr).await;
+    for _ in 0..2 {
+        sock.send(b"nowhere").await.unwrap();
+    }
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+    while proxy.faults("session_open") < 2 {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "session opens not counted"
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(!proxy.load.is_finished());
+    assert_eq!(proxy.metrics.udp_proxy_sessions.get(), 0);
+    proxy.stop().await;
+}
@@ -2,8 +2,19 @@
 #![deny(warnings)]
 #![warn(clippy::pedantic)]
 
-use chimp_chaos_agent::domain::{StartParams, StartRequest};
-use chimp_chaos_agent::validation::validate_start;
+use chimp_chaos_agent::domain::{
+    CacheAccess, CacheLevel, CpuCores, CpuProfile, CpuWorkload, FdResource, FdTarget, FillAmount,
+    IoRate, KillSignal, MemoryBacking, MemoryGrowth, PauseDuty, PidsMode, PortTarget, ProcessMatch,
+    StartParams, StartRequest, TcpFaults, TouchPattern, UdpFaults,
+};
+use chimp_chaos_agent::lib_fd::{nofile_limit, FD_RESERVE};
+use chimp_chaos_agent::lib_port::ephemeral_port_range;
+use chimp_chaos_agent::validation::{check_fill_path, validate_start, DISK_FILL_ALLOWLIST_ENV};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Serializes tests that set [`DISK_FILL_ALLOWLIST_ENV`].
+static ALLOWLIST_ENV: Mutex<()> = Mutex::new(());
 
 #[test]
 fn ok_cpu_defaults() {
@@ -11,7 +22,14 @@ fn ok_cpu_defaults() {
         experiment_id: "e1".into(),
         kind: "CPU".into(),
         duration_seconds: 1,
-        params: StartParams::Cpu { duty_percent: 50 },
+        params: StartParams::Cpu {
+            duty_percent: 50,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
     };
     assert!(validate_start(&r).is_ok());
 }
@@ -22,7 +40,13 @@ fn ok_memory_defaults() {
         experiment_id: "e1".into(),
         kind: "MEMORY".into(),
         duration_seconds: 1,
-        params: StartParams::Memory { memory_mb: 10 },
+        params: StartParams::Memory {
+            memory_mb: 10,
+            touch: TouchPattern::default(),
+            retouch_seconds: 10,
+            growth: MemoryGrowth::Instant,
+            backing: MemoryBacking::Anonymous,
+        },
     };
     assert!(validate_start(&r).is_ok());
 }
@@ -33,7 +57,14 @@ fn err_empty_experiment() {
         experiment_id: " ".into(),
         kind: "CPU".into(),
         duration_seconds: 1,
-        params: StartParams::Cpu { duty_percent: 10 },
+        params: StartParams::Cpu {
+            duty_percent: 10,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
     };
     assert!(validate_start(&r).is_err());
 }
@@ -44,7 +75,14 @@ fn err_zero_duration() {
         experiment_id: "e".into(),
         kind: "CPU".into(),
         duration_seconds: 0,
-        params: StartParams::Cpu { duty_percent: 10 },
+        params: StartParams::Cpu {
+            duty_percent: 10,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
     };
     assert!(validate_start(&r).is_err());
 }
@@ -55,14 +93,28 @@ fn err_cpu_percent_range() {
         experiment_id: "e".into(),
         kind: "CPU".into(),
         duration_seconds: 1,
-        params: StartParams::Cpu { duty_percent: 0 },
+        params: StartParams::Cpu {
+            duty_percent: 0,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
     };
     assert!(validate_start(&r1).is_err());
     let r2 = StartRequest {
         experiment_id: "e".into(),
         kind: "CPU".into(),
         duration_seconds: 1,
-        params: StartParams::Cpu { duty_percent: 101 },
+        params: StartParams::Cpu {
+            duty_percent: 101,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
     };
     assert!(validate_start(&r2).is_err());
 }
@@ -73,7 +125,1008 @@ fn err_kind_unsupported() {
         experiment_id: "e".into(),
         kind: "NET".into(),
         duration_seconds: 1,
-        params: StartParams::Cpu { duty_percent: 10 },
+        params: StartParams::Cpu {
+            duty_percent: 10,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
     };
     assert!(validate_start(&r).is_err());
 }
+
+fn cpu_request(cores: CpuCores, cpu_list: Option<Vec<usize>>) -> StartRequest {
+    StartRequest {
+        experiment_id: "e".into(),
+        kind: "CPU".into(),
+        duration_seconds: 1,
+        params: StartParams::Cpu {
+            duty_percent: 10,
+            cores,
+            cpu_list,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            workload: CpuWorkload::Spin,
+        },
+    }
+}
+
+#[test]
+fn cpu_cores_bounds() {
+    let available = u32::try_from(chimp_chaos_agent::lib_cpu::available_cores()).unwrap();
+    assert!(validate_start(&cpu_request(CpuCores::All, None)).is_ok());
+    assert!(validate_start(&cpu_request(CpuCores::Count(available), None)).is_ok());
+    assert!(validate_start(&cpu_request(CpuCores::Count(0), None)).is_err());
+    assert!(validate_start(&cpu_request(CpuCores::Count(available + 1), None)).is_err());
+}
+
+#[test]
+fn cpu_list_checked() {
+    assert!(validate_start(&cpu_request(CpuCores::All, Some(vec![0]))).is_ok());
+    assert!(validate_start(&cpu_request(CpuCores::All, Some(vec![]))).is_err());
+    assert!(validate_start(&cpu_request(CpuCores::All, Some(vec![0, 0]))).is_err());
+    assert!(validate_start(&cpu_request(CpuCores::Count(2), Some(vec![0]))).is_err());
+    assert!(validate_start(&cpu_request(CpuCores::All, Some(vec![usize::MAX]))).is_err());
+}
+
+#[test]
+fn cpu_cores_deserialize() {
+    let p: StartParams =
+        serde_json::from_str(r#"{"type":"CPU","duty_percent":10,"cores":"all"}"#).unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Cpu {
+            cores: CpuCores::All,
+            ..
+        }
+    ));
+    let p: StartParams =
+        serde_json::from_str(r#"{"type":"CPU","duty_percent":10,"cores":3}"#).unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Cpu {
+            cores: CpuCores::Count(3),
+            ..
+        }
+    ));
+    let p: StartParams = serde_json::from_str(r#"{"type":"CPU","duty_percent":10}"#).unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Cpu {
+            cores: CpuCores::Count(1),
+            cpu_list: None,
+            closed_loop: false,
+            profile: CpuProfile::Constant,
+            ..
+        }
+    ));
+    assert!(
+        serde_json::from_str::<StartParams>(r#"{"type":"CPU","duty_percent":10,"cores":"x"}"#)
+            .is_err()
+    );
+}
+
+#[test]
+fn cpu_workload_deserializes() {
+    let p: StartParams = serde_json::from_str(r#"{"type":"CPU","duty_percent":10}"#).unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Cpu {
+            workload: CpuWorkload::Spin,
+            ..
+        }
+    ));
+    let p: StartParams =
+        serde_json::from_str(r#"{"type":"CPU","duty_percent":10,"workload":"CONTEXT_SWITCH"}"#)
+            .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Cpu {
+            workload: CpuWorkload::ContextSwitch,
+            ..
+        }
+    ));
+    assert!(serde_json::from_str::<StartParams>(
+        r#"{"type":"CPU","duty_percent":10,"workload":"IDLE"}"#
+    )
+    .is_err());
+}
+
+#[test]
+fn memory_touch_defaults() {
+    let p: StartParams = serde_json::from_str(r#"{"type":"MEMORY","memory_mb":10}"#).unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Memory {
+            touch: TouchPattern::Sequential,
+            retouch_seconds: 10,
+            growth: MemoryGrowth::Instant,
+            ..
+        }
+    ));
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"MEMORY","memory_mb":10,"touch":"SCATTERED","retouch_seconds":0}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Memory {
+            touch: TouchPattern::Scattered,
+            retouch_seconds: 0,
+            growth: MemoryGrowth::Instant,
+            ..
+        }
+    ));
+}
+
+fn memory_request(growth: MemoryGrowth) -> StartRequest {
+    StartRequest {
+        experiment_id: "e".into(),
+        kind: "MEMORY".into(),
+        duration_seconds: 1,
+        params: StartParams::Memory {
+            memory_mb: 10,
+            touch: TouchPattern::default(),
+            retouch_seconds: 10,
+            growth,
+            backing: MemoryBacking::Anonymous,
+        },
+    }
+}
+
+#[test]
+fn memory_growth_checked() {
+    assert!(validate_start(&memory_request(MemoryGrowth::Ramp { ramp_seconds: 5 })).is_ok());
+    assert!(validate_start(&memory_request(MemoryGrowth::Ramp { ramp_seconds: 0 })).is_err());
+    assert!(validate_start(&memory_request(MemoryGrowth::Leak {
+        rate_mb_per_second: 0
+    }))
+    .is_err());
+    assert!(validate_start(&memory_request(MemoryGrowth::Step {
+        step_mb: 5,
+        step_seconds: 0
+    }))
+    .is_err());
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"MEMORY","memory_mb":10,"growth":{"mode":"LEAK","rate_mb_per_second":2}}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Memory {
+            growth: MemoryGrowth::Leak {
+                rate_mb_per_second: 2
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn cpu_profile_checked() {
+    let with_profile = |profile: CpuProfile| StartRequest {
+        experiment_id: "e".into(),
+        kind: "CPU".into(),
+        duration_seconds: 1,
+        params: StartParams::Cpu {
+            duty_percent: 50,
+            cores: CpuCores::default(),
+            cpu_list: None,
+            closed_loop: false,
+            profile,
+            workload: CpuWorkload::Spin,
+        },
+    };
+    assert!(validate_start(&with_profile(CpuProfile::Square {
+        period_seconds: 4,
+        low_percent: 0
+    }))
+    .is_ok());
+    assert!(validate_start(&with_profile(CpuProfile::Steps {
+        percents: vec![],
+        step_seconds: 1
+    }))
+    .is_err());
+    assert!(validate_start(&with_profile(CpuProfile::Steps {
+        percents: vec![10, 101],
+        step_seconds: 1
+    }))
+    .is_err());
+    assert!(validate_start(&with_profile(CpuProfile::Sine {
+        period_seconds: 1,
+        amplitude_percent: 10
+    }))
+    .is_err());
+    assert!(validate_start(&with_profile(CpuProfile::Ramp {
+        from_percent: 0,
+        ramp_seconds: 0
+    }))
+    .is_err());
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"CPU","duty_percent":50,"profile":{"shape":"SINE","period_seconds":10,"amplitude_percent":20}}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Cpu {
+            profile: CpuProfile::Sine { .. },
+            ..
+        }
+    ));
+}
+
+fn disk_request(dir: &str, rate: IoRate) -> StartRequest {
+    StartRequest {
+        experiment_id: "e".into(),
+        kind: "DISK_IO".into(),
+        duration_seconds: 1,
+        params: StartParams::DiskIo {
+            dir: dir.into(),
+            read_percent: 50,
+            block_size_kb: 64,
+            file_size_mb: 1,
+            rate,
+            fsync_every: 0,
+        },
+    }
+}
+
+#[test]
+fn disk_io_checked() {
+    let tmp = std::env::temp_dir();
+    let tmp = tmp.to_str().unwrap();
+    let allowed = std::env::temp_dir().join(format!("chimp-io-allow-{}", std::process::id()));
+    std::fs::create_dir_all(&allowed).unwrap();
+    let dir = allowed.to_str().unwrap();
+    {
+        let _serial = ALLOWLIST_ENV.lock().unwrap();
+        std::env::set_var(DISK_FILL_ALLOWLIST_ENV, dir);
+        assert!(validate_start(&disk_request(dir, IoRate::Unlimited)).is_ok());
+        assert!(validate_start(&disk_request("/nonexistent/chimp", IoRate::Unlimited)).is_err());
+        assert!(validate_start(&disk_request(dir, IoRate::Iops { ops_per_second: 0 })).is_err());
+        let mut req = disk_request(dir, IoRate::Unlimited);
+        if let StartParams::DiskIo { block_size_kb, .. } = &mut req.params {
+            *block_size_kb = 2048;
+        }
+        assert!(validate_start(&req).is_err(), "block larger than the file");
+        let mut req = disk_request(dir, IoRate::Unlimited);
+        if let StartParams::DiskIo { file_size_mb, .. } = &mut req.params {
+            *file_size_mb = u32::MAX;
+        }
+        let err = validate_start(&req).unwrap_err();
+        assert!(format!("{err:#}").contains("free"), "{err:#}");
+        let err = validate_start(&disk_request(tmp, IoRate::Unlimited)).unwrap_err();
+        assert!(format!("{err:#}").contains("allowlist"), "{err:#}");
+        assert!(validate_start(&disk_request("/", IoRate::Unlimited)).is_err());
+        std::env::remove_var(DISK_FILL_ALLOWLIST_ENV);
+        assert!(validate_start(&disk_request(dir, IoRate::Unlimited)).is_err());
+    }
+    std::fs::remove_dir_all(&allowed).ok();
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"DISK_IO","dir":"/tmp","rate":{"limit":"THROUGHPUT","mb_per_second":5}}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::DiskIo {
+            read_percent: 50,
+            block_size_kb: 64,
+            file_size_mb: 256,
+            rate: IoRate::Throughput { mb_per_second: 5 },
+            fsync_every: 0,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn disk_fill_allowlist_enforced() {
+    let tmp = std::env::temp_dir().canonicalize().unwrap();
+    let dir = tmp.join(format!("chimp-allow-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    let allow = [dir.clone()];
+    let _serial = ALLOWLIST_ENV.lock().unwrap();
+    assert!(check_fill_path(dir.join("sub").to_str().unwrap(), &allow).is_ok());
+    assert!(check_fill_path(tmp.to_str().unwrap(), &allow).is_err());
+    let escape = format!("{}/sub/../..", dir.display());
+    assert!(check_fill_path(&escape, &allow).is_err());
+    assert!(check_fill_path(dir.to_str().unwrap(), &[]).is_err());
+
+    let req = |amount| StartRequest {
+        experiment_id: "e".into(),
+        kind: "DISK_FILL".into(),
+        duration_seconds: 1,
+        params: StartParams::DiskFill {
+            path: dir.to_str().unwrap().into(),
+            amount,
+        },
+    };
+    std::env::set_var(
+        DISK_FILL_ALLOWLIST_ENV,
+        format!("/nonexistent:{}", dir.display()),
+    );
+    assert!(validate_start(&req(FillAmount::FreePercent { percent: 10 })).is_ok());
+    assert!(validate_start(&req(FillAmount::FreePercent { percent: 0 })).is_err());
+    assert!(validate_start(&req(FillAmount::Size {
+        size_mb: u64::MAX >> 20
+    }))
+    .is_err());
+    std::env::remove_var(DISK_FILL_ALLOWLIST_ENV);
+    assert!(validate_start(&req(FillAmount::Size { size_mb: 1 })).is_err());
+    std::fs::remove_dir_all(PathBuf::from(&dir)).ok();
+}
+
+#[test]
+fn fd_target_checked() {
+    let req = |target| StartRequest {
+        experiment_id: "e".into(),
+        kind: "FD_EXHAUSTION".into(),
+        duration_seconds: 1,
+        params: StartParams::FdExhaustion {
+            target,
+            resource: FdResource::default(),
+        },
+    };
+    assert!(validate_start(&req(FdTarget::Count { count: 10 })).is_ok());
+    assert!(validate_start(&req(FdTarget::Count { count: 0 })).is_err());
+    assert!(validate_start(&req(FdTarget::Count { count: u64::MAX })).is_err());
+    if let Some(limit) = nofile_limit() {
+        let at_cap = limit - FD_RESERVE;
+        assert!(validate_start(&req(FdTarget::Count { count: at_cap })).is_ok());
+        let err = validate_start(&req(FdTarget::Count { count: at_cap + 1 })).unwrap_err();
+        assert!(format!("{err:#}").contains("agent keeps"), "{err:#}");
+    }
+    assert!(validate_start(&req(FdTarget::LimitPercent { percent: 101 })).is_err());
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"FD_EXHAUSTION","target":{"mode":"LIMIT_PERCENT","percent":80},"resource":"SOCKET"}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::FdExhaustion {
+            target: FdTarget::LimitPercent { percent: 80 },
+            resource: FdResource::Socket,
+        }
+    ));
+}
+
+#[test]
+fn pids_count_checked() {
+    let req = |count| StartRequest {
+        experiment_id: "e".into(),
+        kind: "PIDS_PRESSURE".into(),
+        duration_seconds: 1,
+        params: StartParams::PidsPressure {
+            count,
+            mode: PidsMode::default(),
+        },
+    };
+    assert!(validate_start(&req(10)).is_ok());
+    assert!(validate_start(&req(0)).is_err());
+    assert!(validate_start(&req(u32::MAX)).is_err());
+}
+
+#[test]
+fn network_proxy_checked() {
+    let req = |listen: &str, upstream: &str, faults| StartRequest {
+        experiment_id: "e".into(),
+        kind: "NETWORK_PROXY".into(),
+        duration_seconds: 1,
+        params: StartParams::NetworkProxy {
+            listen: listen.into(),
+            upstream: upstream.into(),
+            faults,
+        },
+    };
+    let ok = TcpFaults::default();
+    assert!(validate_start(&req("127.0.0.1:0", "db.internal:5432", ok)).is_ok());
+    assert!(validate_start(&req("[::1]:9000", "[::1]:5432", ok)).is_ok());
+    assert!(validate_start(&req("localhost:9000", "db:5432", ok)).is_err());
+    assert!(validate_start(&req("127.0.0.1:0", "db", ok)).is_err());
+    assert!(validate_start(&req("127.0.0.1:0", ":5432", ok)).is_err());
+    let too_many = TcpFaults {
+        reset_percent: 60,
+        blackhole_percent: 60,
+        ..TcpFaults::default()
+    };
+    assert!(validate_start(&req("127.0.0.1:0", "db:5432", too_many)).is_err());
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"NETWORK_PROXY","listen":"0.0.0.0:9000","upstream":"db:5432","latency_ms":100,"jitter_ms":20}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::NetworkProxy {
+            faults: TcpFaults {
+                latency_ms: 100,
+                jitter_ms: 20,
+                bandwidth_kb_per_second: 0,
+                ..
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn http_fault_checked() {
+    let req = |upstream: &str, rules: &str| {
+        let params: StartParams = serde_json::from_str(&format!(
+            r#"{{"type":"HTTP_FAULT","listen":"127.0.0.1:0","upstream":"{upstream}","rules":{rules}}}"#
+        ))
+        .unwrap();
+        StartRequest {
+            experiment_id: "e".into(),
+            kind: "HTTP_FAULT".into(),
+            duration_seconds: 1,
+            params,
+        }
+    };
+    let ok = r#"[{"name":"a","method":"GET","header":{"name":"x-canary"},"action":"STATUS","status":503},
+                 {"name":"b","path_prefix":"/slow","action":"DELAY","delay_ms":200,"probability_percent":10}]"#;
+    assert!(validate_start(&req("http://api.internal:8080", ok)).is_ok());
+    assert!(validate_start(&req("https://api.internal/", "[]")).is_ok());
+    assert!(validate_start(&req("api.internal:8080", "[]")).is_err());
+    assert!(validate_start(&req("ftp://api.internal", "[]")).is_err());
+    let bad_rules = [
+        r#"[{"name":"","action":"ABORT"}]"#,
+        r#"[{"name":"a","action":"ABORT"},{"name":"a","action":"ABORT"}]"#,
+        r#"[{"name":"a","action":"STATUS","status":42}]"#,
+        r#"[{"name":"a","action":"ABORT","probability_percent":101}]"#,
+        r#"[{"name":"a","method":"GE T","action":"ABORT"}]"#,
+        r#"[{"name":"a","header":{"name":"bad header"},"action":"ABORT"}]"#,
+    ];
+    for rules in bad_rules {
+        assert!(
+            validate_start(&req("http://api.internal", rules)).is_err(),
+            "{rules}"
+        );
+    }
+}
+
+#[test]
+fn udp_proxy_checked() {
+    let req = |listen: &str, upstream: &str, faults| StartRequest {
+        experiment_id: "e".into(),
+        kind: "UDP_PROXY".into(),
+        duration_seconds: 1,
+        params: StartParams::UdpProxy {
+            listen: listen.into(),
+            upstream: upstream.into(),
+            faults,
+        },
+    };
+    let ok = UdpFaults {
+        loss_percent: 10,
+        duplicate_percent: 5,
+        reorder_percent: 5,
+        delay_ms: 20,
+        jitter_ms: 10,
+        seed: Some(1),
+    };
+    assert!(validate_start(&req("127.0.0.1:0", "dns.internal:53", ok)).is_ok());
+    assert!(validate_start(&req("localhost:5353", "dns:53", ok)).is_err());
+    assert!(validate_start(&req("127.0.0.1:0", "dns", ok)).is_err());
+    let lossy = UdpFaults {
+        loss_percent: 101,
+        ..UdpFaults::default()
+    };
+    assert!(validate_start(&req("127.0.0.1:0", "dns:53", lossy)).is_err());
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"UDP_PROXY","listen":"0.0.0.0:5353","upstream":"dns:53","loss_percent":5,"seed":9}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::UdpProxy {
+            faults: UdpFaults {
+                loss_percent: 5,
+                seed: Some(9),
+                ..
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn dns_fault_checked() {
+    let req = |upstream: &str, rules: &str| {
+        let params: StartParams = serde_json::from_str(&format!(
+            r#"{{"type":"DNS_FAULT","listen":"127.0.0.1:5353","upstream":"{upstream}","rules":{rules}}}"#
+        ))
+        .unwrap();
+        StartRequest {
+            experiment_id: "e".into(),
+            kind: "DNS_FAULT".into(),
+            duration_seconds: 1,
+            params,
+        }
+    };
+    let ok = r#"[{"name":"api.internal","action":"NXDOMAIN","probability_percent":50},
+                 {"name":"api.internal","action":"SERVFAIL"},
+                 {"name":"*.svc.cluster.local.","action":"ADDRESS","addresses":["10.0.0.1"]},
+                 {"name":"slow.internal","action":"DELAY","delay_ms":500}]"#;
+    assert!(validate_start(&req("10.96.0.10:53", ok)).is_ok());
+    assert!(validate_start(&req("10.96.0.10", "[]")).is_err());
+    let bad_rules = [
+        r#"[{"name":"","action":"NXDOMAIN"}]"#,
+        r#"[{"name":"a..b","action":"NXDOMAIN"}]"#,
+        r#"[{"name":"api.*.internal","action":"NXDOMAIN"}]"#,
+        r#"[{"name":"a","action":"ADDRESS","addresses":[]}]"#,
+        r#"[{"name":"a","action":"SERVFAIL","probability_percent":101}]"#,
+    ];
+    for rules in bad_rules {
+        assert!(
+            validate_start(&req("10.96.0.10:53", rules)).is_err(),
+            "{rules}"
+        );
+    }
+}
+
+#[test]
+fn process_pause_checked() {
+    // Seconds derived from our pid, so a child leaked by an earlier run never matches.
+    let secs = format!("915.{}", std::process::id());
+    let mut child = std::process::Command::new("sleep")
+        .arg(&secs)
+        .spawn()
+        .unwrap();
+    // The cmdline is filled in late in exec, after the comm already reads "sleep".
+    let cmdline = format!("/proc/{}/cmdline", child.id());
+    while !std::fs::read(&cmdline)
+        .unwrap_or_default()
+        .starts_with(b"sleep\0")
+    {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    let req = |target, duty| StartRequest {
+        experiment_id: "e".into(),
+        kind: "PROCESS_PAUSE".into(),
+        duration_seconds: 1,
+        params: StartParams::ProcessPause {
+            target,
+            duty,
+            max_targets: 16,
+        },
+    };
+    let by_cmdline = ProcessMatch::Cmdline {
+        contains: format!("sleep {secs}"),
+    };
+    assert!(validate_start(&req(by_cmdline.clone(), None)).is_ok());
+    assert!(validate_start(&req(ProcessMatch::Pid { pid: child.id() }, None)).is_ok());
+    let duty = |pause_ms, resume_ms| {
+        Some(PauseDuty {
+            pause_ms,
+            resume_ms,
+        })
+    };
+    assert!(validate_start(&req(by_cmdline.clone(), duty(200, 800))).is_ok());
+    assert!(validate_start(&req(by_cmdline.clone(), duty(0, 800))).is_err());
+    assert!(validate_start(&req(ProcessMatch::Pid { pid: 0 }, None)).is_err());
+    assert!(validate_start(&req(ProcessMatch::Pid { pid: 1 }, None)).is_err());
+    let own = ProcessMatch::Pid {
+        pid: std::process::id(),
+    };
+    assert!(validate_start(&req(own, None)).is_err());
+    let long = ProcessMatch::Name {
+        name: "a-name-longer-than-comm".into(),
+    };
+    assert!(validate_start(&req(long, None)).is_err());
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(validate_start(&req(by_cmdline, None)).is_err());
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"PROCESS_PAUSE","match":"NAME","name":"java","duty":{"pause_ms":500,"resume_ms":1500}}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::ProcessPause {
+            target: ProcessMatch::Name { .. },
+            duty: Some(PauseDuty { pause_ms: 500, .. }),
+            max_targets: 16,
+        }
+    ));
+}
+
+#[test]
+fn process_kill_checked() {
+    let secs = format!("919.{}", std::process::id());
+    let mut child = std::process::Command::new("sleep")
+        .arg(&secs)
+        .spawn()
+        .unwrap();
+    let cmdline = format!("/proc/{}/cmdline", child.id());
+    while !std::fs::read(&cmdline)
+        .unwrap_or_default()
+        .starts_with(b"sleep\0")
+    {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    let req = |target, interval_seconds| StartRequest {
+        experiment_id: "e".into(),
+        kind: "PROCESS_KILL".into(),
+        duration_seconds: 1,
+        params: StartParams::ProcessKill {
+            target,
+            max_targets: 16,
+            signal: KillSignal::Sigterm,
+            interval_seconds,
+            dry_run: true,
+        },
+    };
+    let by_regex = ProcessMatch::CmdlineRegex {
+        regex: format!(r"^sleep {}$", secs.replace('.', r"\.")),
+    };
+    assert!(validate_start(&req(by_regex.clone(), None)).is_ok());
+    assert!(validate_start(&req(by_regex.clone(), Some(5))).is_ok());
+    assert!(validate_start(&req(by_regex, Some(0))).is_err());
+    let bad_regex = ProcessMatch::CmdlineRegex {
+        regex: "(sleep".into(),
+    };
+    assert!(validate_start(&req(bad_regex, None)).is_err());
+    let path_exe = ProcessMatch::Exe {
+        name: "/usr/bin/sleep".into(),
+    };
+    assert!(validate_start(&req(path_exe, None)).is_err());
+    let root = ProcessMatch::Cgroup { path: "/".into() };
+    assert!(validate_start(&req(root, None)).is_err());
+    let relative = ProcessMatch::Cgroup {
+        path: "system.slice".into(),
+    };
+    assert!(validate_start(&req(relative, None)).is_err());
+    let _ = child.kill();
+    let _ = child.wait();
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"PROCESS_KILL","match":"CGROUP","path":"/system.slice/app.service","interval_seconds":10}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::ProcessKill {
+            target: ProcessMatch::Cgroup { .. },
+            max_targets: 16,
+            signal: KillSignal::Sigkill,
+            interval_seconds: Some(10),
+            dry_run: false,
+        }
+    ));
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"PROCESS_KILL","match":"EXE","name":"java","signal":"SIGUSR1","dry_run":true}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::ProcessKill {
+            signal: KillSignal::Sigusr1,
+            interval_seconds: None,
+            dry_run: true,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn process_max_targets_checked() {
+    let tag = format!("922.{}", std::process::id());
+    let mut children: Vec<_> = (0..2)
+        .map(|_| {
+            std::process::Command::new("sleep")
+                .arg(&tag)
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for child in &children {
+        let cmdline = format!("/proc/{}/cmdline", child.id());
+        while !std::fs::read(&cmdline)
+            .unwrap_or_default()
+            .starts_with(b"sleep\0")
+        {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+    let target = ProcessMatch::Cmdline {
+        contains: format!("sleep {tag}"),
+    };
+    let req = |kind: &str, params| StartRequest {
+        experiment_id: "e".into(),
+        kind: kind.into(),
+        duration_seconds: 1,
+        params,
+    };
+    let pause = |max_targets| {
+        req(
+            "PROCESS_PAUSE",
+            StartParams::ProcessPause {
+                target: target.clone(),
+                duty: None,
+                max_targets,
+            },
+        )
+    };
+    assert!(validate_start(&pause(2)).is_ok());
+    let err = validate_start(&pause(1)).unwrap_err();
+    assert!(
+        format!("{err:#}").contains("more than max_targets"),
+        "{err:#}"
+    );
+    assert!(validate_start(&pause(0)).is_err());
+    assert!(validate_start(&pause(257)).is_err());
+    let kill = |max_targets| {
+        req(
+            "PROCESS_KILL",
+            StartParams::ProcessKill {
+                target: target.clone(),
+                max_targets,
+                signal: KillSignal::Sigterm,
+                interval_seconds: None,
+                dry_run: true,
+            },
+        )
+    };
+    assert!(validate_start(&kill(2)).is_ok());
+    assert!(validate_start(&kill(1)).is_err());
+    let throttle = |max_targets| {
+        req(
+            "PROCESS_THROTTLE",
+            StartParams::ProcessThrottle {
+                target: target.clone(),
+                max_targets,
+                cpu_percent: 50,
+            },
+        )
+    };
+    assert!(validate_start(&throttle(2)).is_ok());
+    assert!(validate_start(&throttle(1)).is_err());
+    for child in &mut children {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+#[test]
+fn process_throttle_checked() {
+    let mut child = std::process::Command::new("sleep")
+        .arg(format!("921.{}", std::process::id()))
+        .spawn()
+        .unwrap();
+    let pid = child.id();
+    let req = |cpu_percent| StartRequest {
+        experiment_id: "e".into(),
+        kind: "PROCESS_THROTTLE".into(),
+        duration_seconds: 1,
+        params: StartParams::ProcessThrottle {
+            target: ProcessMatch::Pid { pid },
+            max_targets: 16,
+            cpu_percent,
+        },
+    };
+    assert!(validate_start(&req(30)).is_ok());
+    assert!(validate_start(&req(0)).is_err());
+    assert!(validate_start(&req(100)).is_err());
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(validate_start(&req(30)).is_err());
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"PROCESS_THROTTLE","match":"PID","pid":4242,"cpu_percent":10}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::ProcessThrottle {
+            target: ProcessMatch::Pid { pid: 4242 },
+            max_targets: 16,
+            cpu_percent: 10,
+        }
+    ));
+}
+
+#[test]
+fn port_exhaustion_checked() {
+    let req = |target: &str, ports| StartRequest {
+        experiment_id: "e".into(),
+        kind: "PORT_EXHAUSTION".into(),
+        duration_seconds: 1,
+        params: StartParams::PortExhaustion {
+            target: target.into(),
+            ports,
+        },
+    };
+    let count = |count| PortTarget::Count { count };
+    let percent = |percent| PortTarget::RangePercent { percent };
+    assert!(validate_start(&req("127.0.0.1:8080", count(100))).is_ok());
+    assert!(validate_start(&req("[::1]:8080", percent(50))).is_ok());
+    assert!(validate_start(&req("10.0.0.1:8080", count(100))).is_err());
+    assert!(validate_start(&req("localhost:8080", count(100))).is_err());
+    assert!(validate_start(&req("127.0.0.1:0", count(100))).is_err());
+    assert!(validate_start(&req("127.0.0.1:8080", count(0))).is_err());
+    assert!(validate_start(&req("127.0.0.1:8080", count(70_000))).is_err());
+    assert!(validate_start(&req("127.0.0.1:8080", percent(0))).is_err());
+    assert!(validate_start(&req("127.0.0.1:8080", percent(101))).is_err());
+    // Every connection costs a descriptor, so the agent's reserve bounds both
+    // modes where RLIMIT_NOFILE is smaller than the ephemeral range.
+    let (low, high) = ephemeral_port_range().unwrap();
+    let range = u64::from(high - low) + 1;
+    if let Some(usable) = nofile_limit()
+        .map(|l| l.saturating_sub(FD_RESERVE))
+        .filter(|u| *u < range)
+    {
+        let over = u32::try_from(usable + 1).unwrap();
+        let err = validate_start(&req("127.0.0.1:8080", count(over))).unwrap_err();
+        assert!(format!("{err:#}").contains("descriptors"), "{err:#}");
+        let err = validate_start(&req("127.0.0.1:8080", percent(100))).unwrap_err();
+        assert!(format!("{err:#}").contains("descriptors"), "{err:#}");
+    }
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"PORT_EXHAUSTION","target":"127.0.0.1:9000","ports":{"mode":"RANGE_PERCENT","percent":90}}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::PortExhaustion {
+            ports: PortTarget::RangePercent { percent: 90 },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn cache_thrash_checked() {
+    let req = |level, size_percent, cores| StartRequest {
+        experiment_id: "e".into(),
+        kind: "CACHE_THRASH".into(),
+        duration_seconds: 1,
+        params: StartParams::CacheThrash {
+            level,
+            size_percent,
+            access: CacheAccess::Strided,
+            cores,
+        },
+    };
+    let one = CpuCores::Count(1);
+    assert!(validate_start(&req(CacheLevel::L2, 200, one)).is_ok());
+    assert!(validate_start(&req(CacheLevel::L1, 5, one)).is_err());
+    assert!(validate_start(&req(CacheLevel::L1, 2000, one)).is_err());
+    assert!(validate_start(&req(CacheLevel::L1, 200, CpuCores::Count(0))).is_err());
+    let p: StartParams = serde_json::from_str(r#"{"type":"CACHE_THRASH"}"#).unwrap();
+    assert!(matches!(
+        p,
+        StartParams::CacheThrash {
+            level: CacheLevel::L3,
+            size_percent: 200,
+            access: CacheAccess::Sequential,
+            cores: CpuCores::Count(1),
+        }
+    ));
+    let p: StartParams =
+        serde_json::from_str(r#"{"type":"CACHE_THRASH","level":"L2","access":"RANDOM"}"#).unwrap();
+    assert!(matches!(
+        p,
+        StartParams::CacheThrash {
+            level: CacheLevel::L2,
+            access: CacheAccess::Random,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn page_cache_pressure_checked() {
+    let allowed = std::env::temp_dir().join(format!("chimp-cache-allow-{}", std::process::id()));
+    std::fs::create_dir_all(&allowed).unwrap();
+    let dir = allowed.to_str().unwrap();
+    let req = |dir: &str, file_size_mb, files| StartRequest {
+        experiment_id: "e".into(),
+        kind: "PAGE_CACHE_PRESSURE".into(),
+        duration_seconds: 1,
+        params: StartParams::PageCachePressure {
+            dir: dir.into(),
+            file_size_mb,
+            files,
+            rate: IoRate::Throughput { mb_per_second: 50 },
+        },
+    };
+    {
+        let _serial = ALLOWLIST_ENV.lock().unwrap();
+        std::env::set_var(DISK_FILL_ALLOWLIST_ENV, dir);
+        assert!(validate_start(&req(dir, 1, 2)).is_ok());
+        assert!(validate_start(&req("/nonexistent/chimp", 1, 1)).is_err());
+        assert!(validate_start(&req(dir, 0, 1)).is_err());
+        assert!(validate_start(&req(dir, 1, 0)).is_err());
+        assert!(validate_start(&req(dir, 1, 65)).is_err());
+        let err = validate_start(&req(dir, u32::MAX, 64)).unwrap_err();
+        assert!(format!("{err:#}").contains("free"), "{err:#}");
+        let tmp = std::env::temp_dir();
+        let err = validate_start(&req(tmp.to_str().unwrap(), 1, 1)).unwrap_err();
+        assert!(format!("{err:#}").contains("allowlist"), "{err:#}");
+        std::env::remove_var(DISK_FILL_ALLOWLIST_ENV);
+        assert!(validate_start(&req(dir, 1, 1)).is_err());
+    }
+    std::fs::remove_dir_all(&allowed).ok();
+    let p: StartParams =
+        serde_json::from_str(r#"{"type":"PAGE_CACHE_PRESSURE","dir":"/var/tmp"}"#).unwrap();
+    assert!(matches!(
+        p,
+        StartParams::PageCachePressure {
+            file_size_mb: 1024,
+            files: 1,
+            rate: IoRate::Unlimited,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn memory_backing_checked() {
+    let tmp = std::env::temp_dir();
+    let tmp = tmp.to_str().unwrap();
+    let req = |memory_mb, backing| StartRequest {
+        experiment_id: "e".into(),
+        kind: "MEMORY".into(),
+        duration_seconds: 1,
+        params: StartParams::Memory {
+            memory_mb,
+            touch: TouchPattern::default(),
+            retouch_seconds: 10,
+            growth: MemoryGrowth::Instant,
+            backing,
+        },
+    };
+    let file = |dir: &str| MemoryBacking::File { dir: dir.into() };
+    assert!(validate_start(&req(1, MemoryBacking::Shm)).is_ok());
+    let err = validate_start(&req(u32::MAX, MemoryBacking::Shm)).unwrap_err();
+    assert!(format!("{err:#}").contains("free"), "{err:#}");
+
+    // FILE backings write where DISK_FILL may, and nowhere else.
+    let allowed = std::env::temp_dir().join(format!("chimp-mem-allow-{}", std::process::id()));
+    std::fs::create_dir_all(&allowed).unwrap();
+    let allowed_dir = allowed.to_str().unwrap();
+    {
+        let _serial = ALLOWLIST_ENV.lock().unwrap();
+        std::env::set_var(DISK_FILL_ALLOWLIST_ENV, allowed_dir);
+        assert!(validate_start(&req(1, file(allowed_dir))).is_ok());
+        let err = validate_start(&req(1, file(tmp))).unwrap_err();
+        assert!(format!("{err:#}").contains("allowlist"), "{err:#}");
+        assert!(validate_start(&req(1, file("/nonexistent/chimp"))).is_err());
+        assert!(validate_start(&req(u32::MAX, file(allowed_dir))).is_err());
+        std::env::remove_var(DISK_FILL_ALLOWLIST_ENV);
+        assert!(validate_start(&req(1, file(allowed_dir))).is_err());
+    }
+    std::fs::remove_dir_all(&allowed).ok();
+    let p: StartParams = serde_json::from_str(
+        r#"{"type":"MEMORY","memory_mb":10,"backing":{"kind":"FILE","dir":"/var/tmp"}}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Memory {
+            backing: MemoryBacking::File { ref dir },
+            ..
+        } if dir == "/var/tmp"
+    ));
+    let p: StartParams = serde_json::from_str(r#"{"type":"MEMORY","memory_mb":10}"#).unwrap();
+    assert!(matches!(
+        p,
+        StartParams::Memory {
+            backing: MemoryBacking::Anonymous,
+            ..
+        }
+    ));
+    // MLOCKED pins the whole agent, so the memlock limit bounds it even as root.
+    let limit = rustix::process::getrlimit(rustix::process::Resource::Memlock).current;
+    if let Some(limit) = limit {
+        let over = u32::try_from((limit >> 20) + 1).unwrap_or(u32::MAX);
+        let err = validate_start(&req(over, MemoryBacking::Mlocked)).unwrap_err();
+        assert!(format!("{err:#}").contains("RLIMIT_MEMLOCK"), "{err:#}");
+    } else {
+        assert!(validate_start(&req(1, MemoryBacking::Mlocked)).is_ok());
+    }
+    assert!(serde_json::from_str::<StartParams>(
+        r#"{"type":"MEMORY","memory_mb":10,"backing":{"kind":"HUGETLB"}}"#
+    )
+    .is_err());
+}